@@ -0,0 +1,49 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::layers::LABEL_Z_OFFSET;
+
+const BOT_TAG_MARGIN: f32 = 12.0;
+/// How much of its color a bot keeps, the rest is replaced by gray.
+const BOT_SATURATION: f32 = 0.35;
+
+/// Returns the color of a bot, desaturated to tell it apart from the real players.
+pub fn bot_color(color: Color) -> Color {
+    let [r, g, b, a] = color.as_rgba_f32();
+    let gray = 0.3 * r + 0.59 * g + 0.11 * b;
+    let desaturate = |c: f32| gray + (c - gray) * BOT_SATURATION;
+    Color::rgba(desaturate(r), desaturate(g), desaturate(b), a)
+}
+
+/// Writes "BOT" under the cube of a player moved by the server.
+pub fn spawn_bot_tag(parent: &mut ChildBuilder, font: Handle<Font>) {
+    let style = TextStyle { font, font_size: 12.0, color: Color::GRAY };
+    let alignment =
+        TextAlignment { vertical: VerticalAlign::Center, horizontal: HorizontalAlign::Center };
+    parent.spawn_bundle(Text2dBundle {
+        text: Text::with_section("BOT", style, alignment),
+        transform: Transform::from_xyz(
+            0.,
+            -PLAYER_SQUARE_HEIGHT / 2. - BOT_TAG_MARGIN,
+            LABEL_Z_OFFSET,
+        ),
+        ..default()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bots_are_desaturated() {
+        let [r, g, b, _] = bot_color(Color::rgb(0.5, 0.5, 0.5)).as_rgba_f32();
+        assert!((r - 0.5).abs() < 1e-5 && (g - 0.5).abs() < 1e-5 && (b - 0.5).abs() < 1e-5);
+
+        let [r, g, b, a] = bot_color(Color::rgba(1., 0., 0., 0.8)).as_rgba_f32();
+        assert_eq!(a, 0.8);
+        assert!(r < 1. && g > 0. && b > 0.);
+        // The color is still recognizable.
+        assert!(r > g && g == b);
+    }
+}
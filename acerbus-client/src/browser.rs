@@ -0,0 +1,310 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::connecting::{
+    ConnectParams, ConnectionLostPanel, ConnectionStatus, DisconnectReason, LocalPlayer,
+};
+use crate::layers::{UiLayer, MENU_Z};
+use crate::ready::ReadyCheck;
+use crate::teleport_pads::TeleportPads;
+use crate::{new_renet_client, GameAssets};
+
+pub const BROWSER_TOGGLE_KEY: KeyCode = KeyCode::F4;
+const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+const BROWSER_ROW_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BROWSER_SELECTED_ROW_COLOR: Color = Color::rgb(0.25, 0.25, 0.55);
+
+/// A server listed in the browser, we only know about it once it answered a probe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowserEntry {
+    pub addr: SocketAddr,
+    pub info: Option<ServerInfo>,
+    pub ping: Option<Duration>,
+}
+
+impl BrowserEntry {
+    pub fn new(addr: SocketAddr) -> BrowserEntry {
+        BrowserEntry { addr, info: None, ping: None }
+    }
+
+    fn label(&self) -> String {
+        let ping = self.ping.map_or_else(|| "?".to_string(), |ping| ping.as_millis().to_string());
+        match &self.info {
+            Some(info) => format!(
+                "{:<20} {:>3}/{:<3} {:>5}ms  {}",
+                info.name, info.players, info.max_players, ping, self.addr
+            ),
+            None => format!("{:<20} {:>7} {:>5}ms  {}", "unknown", "-", ping, self.addr),
+        }
+    }
+}
+
+/// Sorts the servers by ping, the ones that never answered last, then by name.
+pub fn sort_browser_entries(entries: &mut [BrowserEntry]) {
+    entries.sort_by(|a, b| {
+        let by_ping = match (a.ping, b.ping) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        let name = |entry: &BrowserEntry| entry.info.as_ref().map(|info| info.name.clone());
+        by_ping.then_with(|| name(a).cmp(&name(b))).then_with(|| a.addr.cmp(&b.addr))
+    });
+}
+
+/// The servers added by hand, there is no discovery of the servers of the network yet.
+pub struct ServerBrowser {
+    pub visible: bool,
+    pub entries: Vec<BrowserEntry>,
+    pub selected: usize,
+}
+
+/// The probes sent to the servers of the browser and not answered yet.
+pub struct ServerProber {
+    socket: UdpSocket,
+    probes: HashMap<SocketAddr, (u64, Instant)>,
+    last_probe: Option<Instant>,
+    next_nonce: u64,
+}
+
+impl Default for ServerProber {
+    fn default() -> ServerProber {
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+        ServerProber { socket, probes: HashMap::new(), last_probe: None, next_nonce: 0 }
+    }
+}
+
+impl ServerBrowser {
+    pub fn new(servers: impl IntoIterator<Item = SocketAddr>, visible: bool) -> ServerBrowser {
+        let mut entries: Vec<_> = servers.into_iter().map(BrowserEntry::new).collect();
+        entries.sort_by_key(|entry| entry.addr);
+        entries.dedup_by_key(|entry| entry.addr);
+        ServerBrowser { visible, entries, selected: 0 }
+    }
+
+    fn selected_addr(&self) -> Option<SocketAddr> {
+        self.entries.get(self.selected).map(|entry| entry.addr)
+    }
+
+    /// Sorts the entries while keeping the same server selected.
+    fn sort(&mut self) {
+        let selected = self.selected_addr();
+        sort_browser_entries(&mut self.entries);
+        if let Some(selected) = selected {
+            self.selected = self.entries.iter().position(|e| e.addr == selected).unwrap_or(0);
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct BrowserPanel;
+
+#[derive(Component)]
+pub struct BrowserRow(usize);
+
+/// Probe the listed servers regularly to know their players and measure their ping.
+pub fn probe_servers_system(mut browser: ResMut<ServerBrowser>, mut prober: ResMut<ServerProber>) {
+    let now = Instant::now();
+    if !browser.visible {
+        return;
+    }
+
+    let prober = &mut *prober;
+    if prober.last_probe.map_or(true, |last| now.duration_since(last) >= PROBE_INTERVAL) {
+        prober.last_probe = Some(now);
+        for addr in browser.entries.iter().filter_map(|entry| probe_addr(entry.addr)) {
+            let nonce = prober.next_nonce;
+            prober.next_nonce += 1;
+            if prober.socket.send_to(&probe_request(nonce), addr).is_ok() {
+                prober.probes.insert(addr, (nonce, now));
+            }
+        }
+    }
+
+    let mut buffer = [0; 1024];
+    let mut answered = Vec::new();
+    while let Ok((len, addr)) = prober.socket.recv_from(&mut buffer) {
+        let response: ProbeResponse = match try_decode(&buffer[..len]) {
+            Ok(response) => response,
+            Err(_) => continue,
+        };
+        match prober.probes.get(&addr) {
+            Some((nonce, sent_at)) if *nonce == response.nonce => {
+                answered.push((addr, response.info, now.duration_since(*sent_at)));
+                prober.probes.remove(&addr);
+            }
+            _ => continue,
+        }
+    }
+
+    // The browser is only touched when a server answered so that it is only redrawn then.
+    if !answered.is_empty() {
+        for (addr, info, ping) in answered {
+            if let Some(entry) =
+                browser.entries.iter_mut().find(|e| probe_addr(e.addr) == Some(addr))
+            {
+                entry.info = Some(info);
+                entry.ping = Some(ping);
+            }
+        }
+        browser.sort();
+    }
+}
+
+/// Navigate in the browser with the arrows, connect with Enter or by clicking on a server.
+#[allow(clippy::too_many_arguments)]
+pub fn browser_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut browser: ResMut<ServerBrowser>,
+    mut params: ResMut<ConnectParams>,
+    mut status: ResMut<ConnectionStatus>,
+    mut local_player: ResMut<LocalPlayer>,
+    mut reason: ResMut<DisconnectReason>,
+    mut lobby: ResMut<Lobby>,
+    mut assembler: ResMut<WorldSyncAssembler>,
+    mut ready_check: ResMut<ReadyCheck>,
+    rows: Query<(&Interaction, &BrowserRow), Changed<Interaction>>,
+    lost_panels: Query<Entity, With<ConnectionLostPanel>>,
+) {
+    if keyboard_input.just_pressed(BROWSER_TOGGLE_KEY) {
+        browser.visible = !browser.visible;
+    }
+    if !browser.visible || browser.entries.is_empty() {
+        return;
+    }
+
+    let last = browser.entries.len() - 1;
+    if keyboard_input.just_pressed(KeyCode::Up) && browser.selected > 0 {
+        browser.selected -= 1;
+    } else if keyboard_input.just_pressed(KeyCode::Down) && browser.selected < last {
+        browser.selected += 1;
+    }
+
+    let mut connect = keyboard_input.just_pressed(KeyCode::Return);
+    for (interaction, row) in rows.iter() {
+        if *interaction == Interaction::Clicked {
+            browser.selected = row.0.min(last);
+            connect = true;
+        }
+    }
+
+    let addr = match browser.selected_addr() {
+        Some(addr) if connect => addr,
+        _ => return,
+    };
+
+    // Coming back to the server we lost, we keep its players and only hear about the changes,
+    // otherwise we leave the current server and forget everything about it.
+    let resume = addr == params.server_addr && matches!(*status, ConnectionStatus::Lost { .. });
+    if !resume {
+        for entity in std::mem::take(&mut lobby.players).into_values() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for entity in lost_panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *assembler = WorldSyncAssembler::default();
+    *ready_check = ReadyCheck::default();
+    commands.remove_resource::<Arena>();
+    commands.remove_resource::<TeleportPads>();
+
+    params.server_addr = addr;
+    if let Ok(mut request) = ConnectRequest::from_user_data(&params.user_data) {
+        request.resume = resume;
+        if let Ok(user_data) = request.to_user_data() {
+            params.user_data = user_data;
+        }
+    }
+    commands.insert_resource(new_renet_client(&params));
+    *local_player = LocalPlayer::default();
+    reason.0 = None;
+    *status = ConnectionStatus::Connecting { since: Instant::now() };
+    browser.visible = false;
+}
+
+/// (Re)draw the browser every time its entries or the selection change.
+pub fn browser_panel_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    browser: Res<ServerBrowser>,
+    panels: Query<Entity, With<BrowserPanel>>,
+) {
+    if !browser.is_changed() {
+        return;
+    }
+
+    for panel in panels.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+    if !browser.visible {
+        return;
+    }
+
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 18.0, color: Color::WHITE };
+    let title = "Servers - Up/Down to select, Enter to connect, F4 to close";
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(10.0), top: Val::Px(60.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(BrowserPanel)
+        .insert(UiLayer(MENU_Z))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(title, text_style.clone(), default()),
+                ..default()
+            });
+
+            for (index, entry) in browser.entries.iter().enumerate() {
+                let color = if index == browser.selected {
+                    BROWSER_SELECTED_ROW_COLOR
+                } else {
+                    BROWSER_ROW_COLOR
+                };
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style { padding: Rect::all(Val::Px(4.0)), ..default() },
+                        color: color.into(),
+                        ..default()
+                    })
+                    .insert(BrowserRow(index))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(entry.label(), text_style.clone(), default()),
+                            ..default()
+                        });
+                    });
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_servers_are_listed_once() {
+        let a: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let b: SocketAddr = "127.0.0.1:6000".parse().unwrap();
+        let browser = ServerBrowser::new([a, b, a], false);
+        let addrs: Vec<_> = browser.entries.iter().map(|entry| entry.addr).collect();
+        assert_eq!(addrs, [a, b]);
+    }
+}
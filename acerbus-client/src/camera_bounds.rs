@@ -0,0 +1,68 @@
+use acerbus_common::Arena;
+use bevy::prelude::*;
+
+/// Whether the following camera is kept inside the arena.
+pub struct ClampCameraToArena(pub bool);
+
+/// Returns half the size of the world seen through this projection,
+/// its bounds already follow the aspect of the window.
+pub fn visible_half_size(projection: &OrthographicProjection) -> Vec2 {
+    let width = projection.right - projection.left;
+    let height = projection.top - projection.bottom;
+    Vec2::new(width, height) * projection.scale / 2.0
+}
+
+/// Moves the camera center so that the viewport doesn't show anything outside the arena,
+/// the arena is centered on an axis where it is smaller than the viewport.
+pub fn clamp_to_arena(center: Vec2, half_view: Vec2, arena: Arena) -> Vec2 {
+    let clamp_axis = |center: f32, half_view: f32, arena_size: f32| {
+        let max = arena_size / 2.0 - half_view;
+        if max <= 0.0 {
+            0.0
+        } else {
+            center.clamp(-max, max)
+        }
+    };
+
+    Vec2::new(
+        clamp_axis(center.x, half_view.x, arena.width),
+        clamp_axis(center.y, half_view.y, arena.height),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_stays_inside_the_arena() {
+        let arena = Arena { width: 1000., height: 600. };
+        let half_view = Vec2::new(200., 100.);
+        assert_eq!(clamp_to_arena(Vec2::new(50., -20.), half_view, arena), Vec2::new(50., -20.));
+        assert_eq!(
+            clamp_to_arena(Vec2::new(450., -290.), half_view, arena),
+            Vec2::new(300., -200.)
+        );
+    }
+
+    #[test]
+    fn small_arenas_are_centered() {
+        let arena = Arena { width: 300., height: 600. };
+        let half_view = Vec2::new(200., 100.);
+        let center = clamp_to_arena(Vec2::new(100., 250.), half_view, arena);
+        assert_eq!(center, Vec2::new(0., 200.));
+    }
+
+    #[test]
+    fn zooming_out_shows_more() {
+        let projection = OrthographicProjection {
+            left: -400.,
+            right: 400.,
+            bottom: -300.,
+            top: 300.,
+            scale: 2.,
+            ..default()
+        };
+        assert_eq!(visible_half_size(&projection), Vec2::new(800., 600.));
+    }
+}
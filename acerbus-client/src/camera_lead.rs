@@ -0,0 +1,66 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::connecting::LocalPlayer;
+
+/// How fast, per second, the lead catches up with the velocity of the player.
+const CAMERA_LEAD_SMOOTHING: f32 = 4.0;
+
+/// Moves the camera ahead of our player in the direction it goes, at most `max_distance` pixels.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CameraLead {
+    pub max_distance: f32,
+    pub offset: Vec2,
+    last_position: Option<Vec2>,
+}
+
+impl CameraLead {
+    pub fn new(max_distance: f32) -> CameraLead {
+        CameraLead { max_distance, ..default() }
+    }
+}
+
+/// Returns how far ahead the camera should be for this velocity, the full distance is
+/// reached at the speed of the players and never exceeded.
+pub fn lead_offset(velocity: Vec2, max_distance: f32) -> Vec2 {
+    (velocity / PLAYER_MOVE_SPEED * max_distance).clamp_length_max(max_distance)
+}
+
+/// Derives the velocity of our player from its positions and moves the lead toward it.
+pub fn camera_lead_system(
+    time: Res<Time>,
+    local_player: Res<LocalPlayer>,
+    lobby: Res<Lobby>,
+    mut lead: ResMut<CameraLead>,
+    transforms: Query<&Transform, With<Player>>,
+) {
+    let entity = local_player.player.and_then(|player| lobby.players.get(&player));
+    let position = match entity.and_then(|entity| transforms.get(*entity).ok()) {
+        Some(transform) => transform.translation.truncate(),
+        None => return,
+    };
+
+    let delta = time.delta_seconds();
+    let previous = lead.last_position.replace(position);
+    if let (Some(previous), true) = (previous, delta > 0.0) {
+        let target = lead_offset((position - previous) / delta, lead.max_distance);
+        let offset = lead.offset;
+        lead.offset = offset + (target - offset) * (CAMERA_LEAD_SMOOTHING * delta).min(1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lead_follows_the_velocity_up_to_the_max_distance() {
+        assert_eq!(lead_offset(Vec2::ZERO, 100.), Vec2::ZERO);
+
+        let half = lead_offset(Vec2::new(PLAYER_MOVE_SPEED / 2., 0.), 100.);
+        assert!((half - Vec2::new(50., 0.)).length() < 1e-3);
+
+        let fast = lead_offset(Vec2::new(0., -PLAYER_MOVE_SPEED * 3.), 100.);
+        assert!((fast - Vec2::new(0., -100.)).length() < 1e-3);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use acerbus_common::{fallback_name, Player};
+use bevy::prelude::*;
+
+use crate::layers::{UiLayer, HUD_Z};
+use crate::GameAssets;
+
+/// How many lines of the history are shown at once.
+const CHAT_VISIBLE_LINES: usize = 8;
+
+/// The last lines of the chat, the older ones are forgotten but still written to the log.
+pub struct ChatHistory {
+    lines: VecDeque<String>,
+    capacity: usize,
+    /// How many lines we scrolled back from the most recent one.
+    scroll: usize,
+    log: Option<BufWriter<File>>,
+    /// The names the players write with, as the server told us when they connected.
+    names: HashMap<Player, String>,
+}
+
+impl ChatHistory {
+    pub fn new(capacity: usize) -> ChatHistory {
+        let names = HashMap::new();
+        ChatHistory { lines: VecDeque::new(), capacity, scroll: 0, log: None, names }
+    }
+
+    pub fn set_name(&mut self, player: Player, name: String) {
+        self.names.insert(player, name);
+    }
+
+    /// The name of the player, or the one the server gives to the players without a name.
+    pub fn name(&self, player: Player) -> String {
+        self.names.get(&player).cloned().unwrap_or_else(|| fallback_name(player))
+    }
+
+    /// Also appends every line of the chat to this file.
+    pub fn with_log(mut self, path: &Path) -> io::Result<ChatHistory> {
+        let file = File::options().create(true).append(true).open(path)?;
+        self.log = Some(BufWriter::new(file));
+        Ok(self)
+    }
+
+    pub fn push(&mut self, line: String) {
+        if let Some(log) = &mut self.log {
+            if let Err(e) = writeln!(log, "{}", line).and_then(|()| log.flush()) {
+                warn!("Failed to write to the chat log, it is now disabled: {}", e);
+                self.log = None;
+            }
+        }
+
+        self.lines.push_back(line);
+        while self.lines.len() > self.capacity {
+            self.lines.pop_front();
+        }
+        // We keep looking at the same lines when scrolled back.
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.max_scroll());
+        }
+    }
+
+    /// The lines to show, at most `count` of them, taking the scrollback into account.
+    pub fn visible_lines(&self, count: usize) -> impl Iterator<Item = &str> {
+        let end = self.lines.len() - self.scroll;
+        self.lines.range(end.saturating_sub(count)..end).map(String::as_str)
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(CHAT_VISIBLE_LINES)
+    }
+}
+
+#[derive(Component)]
+pub struct ChatText;
+
+pub fn setup_chat(mut commands: Commands, game_assets: Res<GameAssets>) {
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 16.0, color: Color::WHITE };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(10.0), bottom: Val::Px(60.0), ..default() },
+                ..default()
+            },
+            text: Text::with_section(String::new(), text_style, default()),
+            ..default()
+        })
+        .insert(ChatText)
+        .insert(UiLayer(HUD_Z));
+}
+
+/// Scroll back in the history with Page Up and Page Down.
+pub fn chat_scroll_system(keyboard_input: Res<Input<KeyCode>>, mut history: ResMut<ChatHistory>) {
+    if keyboard_input.just_pressed(KeyCode::PageUp) {
+        let max_scroll = history.max_scroll();
+        history.scroll = (history.scroll + CHAT_VISIBLE_LINES).min(max_scroll);
+    } else if keyboard_input.just_pressed(KeyCode::PageDown) {
+        history.scroll = history.scroll.saturating_sub(CHAT_VISIBLE_LINES);
+    }
+}
+
+pub fn chat_text_system(history: Res<ChatHistory>, mut texts: Query<&mut Text, With<ChatText>>) {
+    if !history.is_changed() {
+        return;
+    }
+
+    for mut text in texts.iter_mut() {
+        let lines: Vec<_> = history.visible_lines(CHAT_VISIBLE_LINES).collect();
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn the_oldest_lines_are_forgotten() {
+        let mut history = ChatHistory::new(3);
+        for i in 0..5 {
+            history.push(i.to_string());
+        }
+        assert_eq!(history.visible_lines(10).collect::<Vec<_>>(), ["2", "3", "4"]);
+        assert_eq!(history.visible_lines(2).collect::<Vec<_>>(), ["3", "4"]);
+    }
+
+    #[test]
+    fn the_log_keeps_every_line() {
+        let path = std::env::temp_dir().join(format!("acerbus-chat-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut history = ChatHistory::new(1).with_log(&path).unwrap();
+        history.push("hello".to_string());
+        history.push("world".to_string());
+
+        assert_eq!(history.visible_lines(10).collect::<Vec<_>>(), ["world"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello\nworld\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn scrolled_back_lines_stay_in_place() {
+        let mut history = ChatHistory::new(100);
+        for i in 0..20 {
+            history.push(i.to_string());
+        }
+        history.scroll = 4;
+        let before: Vec<_> = history.visible_lines(2).map(String::from).collect();
+        history.push("new".to_string());
+        assert_eq!(history.visible_lines(2).collect::<Vec<_>>(), before);
+        assert_eq!(before, ["14", "15"]);
+    }
+
+    #[test]
+    fn unnamed_players_get_the_fallback_name() {
+        let mut history = ChatHistory::new(4);
+        history.set_name(Player { id: 1 }, "Alice".to_string());
+        assert_eq!(history.name(Player { id: 1 }), "Alice");
+        assert_eq!(history.name(Player { id: 2 }), fallback_name(Player { id: 2 }));
+    }
+}
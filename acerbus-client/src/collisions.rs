@@ -0,0 +1,69 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+/// Whether the player moved by the client is stopped by the walls of the arena, with a simple
+/// overlap test much cheaper than running the physics of the server. The server stays the
+/// authority and may have no walls, in which case this must be disabled.
+pub struct ClientCollisions(pub bool);
+
+/// Returns by how much a box must be moved to stop overlapping an obstacle,
+/// along the axis where the overlap is the smallest.
+pub fn push_out(center: Vec2, half_size: Vec2, obstacle: Vec2, obstacle_half_size: Vec2) -> Vec2 {
+    let delta = center - obstacle;
+    let overlap = half_size + obstacle_half_size - delta.abs();
+    if overlap.x <= 0. || overlap.y <= 0. {
+        Vec2::ZERO
+    } else if overlap.x < overlap.y {
+        Vec2::new(overlap.x.copysign(delta.x), 0.)
+    } else {
+        Vec2::new(0., overlap.y.copysign(delta.y))
+    }
+}
+
+/// Moves a player out of the walls of the arena it went into, even the ones it grew into.
+/// A round player is tested as the square around it, the walls are straight.
+pub fn resolve_wall_collisions(
+    position: Vec2,
+    shape: BodyShape,
+    scale: Vec2,
+    arena: Arena,
+) -> Vec2 {
+    let half_size = shape.half_extents(SizeClass::Normal) * scale;
+    wall_bounds(arena).iter().fold(position, |position, (wall, wall_half_size)| {
+        position + push_out(position, half_size, *wall, *wall_half_size)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxes_are_pushed_along_the_smallest_overlap() {
+        let half_size = Vec2::splat(10.);
+        let push = push_out(Vec2::new(15., 2.), half_size, Vec2::ZERO, half_size);
+        assert_eq!(push, Vec2::new(5., 0.));
+        let push = push_out(Vec2::new(1., -18.), half_size, Vec2::ZERO, half_size);
+        assert_eq!(push, Vec2::new(0., -2.));
+        // Touching is not overlapping.
+        let push = push_out(Vec2::new(20., 0.), half_size, Vec2::ZERO, half_size);
+        assert_eq!(push, Vec2::ZERO);
+    }
+
+    #[test]
+    fn players_stay_inside_the_walls() {
+        let arena = Arena { width: 400., height: 300. };
+        let max = (Vec2::new(arena.width, arena.height)
+            - Vec2::new(PLAYER_SQUARE_WIDTH, PLAYER_SQUARE_HEIGHT))
+            / 2.;
+        let position =
+            resolve_wall_collisions(Vec2::new(10., -20.), BodyShape::Square, Vec2::ONE, arena);
+        assert_eq!(position, Vec2::new(10., -20.));
+        let position =
+            resolve_wall_collisions(max + Vec2::new(3., 2.), BodyShape::Square, Vec2::ONE, arena);
+        assert!((position - max).length() < 1e-3, "{} is not {}", position, max);
+        // A bigger player is pushed further from the walls.
+        let position = resolve_wall_collisions(max, BodyShape::Square, Vec2::splat(2.), arena);
+        assert!(position.x < max.x && position.y < max.y);
+    }
+}
@@ -0,0 +1,287 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::app::AppExit;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy_renet::renet::{RenetClient, NETCODE_USER_DATA_BYTES};
+
+use crate::{new_renet_client, GameAssets};
+
+/// How long the world stays frozen before exiting once the connection is lost.
+pub const CONNECTION_LOST_EXIT_DELAY: Duration = Duration::from_secs(5);
+
+/// What is needed to connect to the server again.
+pub struct ConnectParams {
+    pub server_addr: SocketAddr,
+    pub user_data: [u8; NETCODE_USER_DATA_BYTES],
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Connecting {
+        since: Instant,
+    },
+    /// The server welcomed us, we know about the world and our own player.
+    Connected,
+    /// The server never answered, we wait for the player to retry or quit.
+    Unreachable,
+    /// The server vanished while we were playing, we exit after a countdown.
+    Lost {
+        since: Instant,
+    },
+}
+
+impl ConnectionStatus {
+    /// We are only connected once welcomed, being connected to the server isn't enough.
+    pub fn update(
+        self,
+        now: Instant,
+        timeout: Duration,
+        welcomed: bool,
+        connected: bool,
+    ) -> ConnectionStatus {
+        match self {
+            ConnectionStatus::Connecting { .. } if welcomed => ConnectionStatus::Connected,
+            ConnectionStatus::Connected if !connected => ConnectionStatus::Lost { since: now },
+            ConnectionStatus::Connecting { since } if now.duration_since(since) >= timeout => {
+                ConnectionStatus::Unreachable
+            }
+            status => status,
+        }
+    }
+}
+
+/// The player we are, told by the server in the `InitState`, and whether
+/// it sent us the `Welcome` message since we last connected.
+#[derive(Debug, Default)]
+pub struct LocalPlayer {
+    pub player: Option<Player>,
+    pub welcomed: bool,
+    /// The server trusts us to move our player ourselves.
+    pub trusted: bool,
+    /// The shape of all the players of the server.
+    pub body_shape: BodyShape,
+    /// The number of teleports of our player, as last sent by the server.
+    pub teleports: u8,
+}
+
+/// Returns the number of seconds left before exiting, `None` once it is time to exit.
+pub fn connection_lost_countdown(since: Instant, now: Instant) -> Option<u64> {
+    let left = CONNECTION_LOST_EXIT_DELAY.checked_sub(now.duration_since(since))?;
+    (!left.is_zero()).then(|| left.as_secs() + (left.subsec_nanos() > 0) as u64)
+}
+
+/// Why the server closed the connection, if it told us.
+#[derive(Debug, Default)]
+pub struct DisconnectReason(pub Option<String>);
+
+impl DisconnectReason {
+    /// Returns the reason as the end of a sentence, or nothing if we don't know it.
+    pub fn suffix(&self) -> String {
+        self.0.as_ref().map_or_else(String::new, |reason| format!(": {}", reason))
+    }
+}
+
+#[derive(Component)]
+pub struct UnreachablePanel;
+
+#[derive(Component)]
+pub struct ConnectionLostPanel;
+
+/// Runs the systems that need the world and our player to exist.
+pub fn run_if_in_game(status: Res<ConnectionStatus>, client: Res<RenetClient>) -> ShouldRun {
+    if *status == ConnectionStatus::Connected && client.is_connected() {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+pub fn connection_status_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    params: Res<ConnectParams>,
+    local_player: Res<LocalPlayer>,
+    client: Res<RenetClient>,
+    reason: Res<DisconnectReason>,
+    mut status: ResMut<ConnectionStatus>,
+) {
+    let now = Instant::now();
+    let new_status =
+        status.update(now, params.timeout, local_player.welcomed, client.is_connected());
+    if new_status == *status {
+        return;
+    }
+
+    *status = new_status;
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 30.0, color: Color::WHITE };
+    let text_bundle = |message: String| TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect { left: Val::Px(10.0), bottom: Val::Px(10.0), ..default() },
+            ..default()
+        },
+        text: Text::with_section(message, text_style.clone(), default()),
+        ..default()
+    };
+
+    match new_status {
+        ConnectionStatus::Unreachable => {
+            let message = format!(
+                "Could not reach {}{}\nPress R to retry or Escape to quit",
+                params.server_addr,
+                reason.suffix(),
+            );
+            commands.spawn_bundle(text_bundle(message)).insert(UnreachablePanel);
+        }
+        ConnectionStatus::Lost { .. } => {
+            let message = String::with_capacity(32);
+            commands.spawn_bundle(text_bundle(message)).insert(ConnectionLostPanel);
+        }
+        _ => (),
+    }
+}
+
+/// Freezes our player and counts down before exiting once the server vanished,
+/// the connection is still closed cleanly by the systems listening for `AppExit`.
+pub fn connection_lost_system(
+    status: Res<ConnectionStatus>,
+    reason: Res<DisconnectReason>,
+    mut player_input: ResMut<PlayerInput>,
+    mut exit: EventWriter<AppExit>,
+    mut panels: Query<&mut Text, With<ConnectionLostPanel>>,
+) {
+    let since = match *status {
+        ConnectionStatus::Lost { since } => since,
+        _ => return,
+    };
+
+    *player_input = PlayerInput::default();
+    match connection_lost_countdown(since, Instant::now()) {
+        Some(seconds) => {
+            for mut text in panels.iter_mut() {
+                let value = &mut text.sections[0].value;
+                value.clear();
+                let message =
+                    format!("Connection lost{}\nExiting in {}...", reason.suffix(), seconds);
+                value.push_str(&message);
+            }
+        }
+        None => exit.send(AppExit),
+    }
+}
+
+/// Looks for the reason of the disconnection among the messages received just before
+/// the connection was closed, they are not read by the systems that need a connection.
+pub fn disconnect_reason_system(
+    mut client: ResMut<RenetClient>,
+    mut reason: ResMut<DisconnectReason>,
+) {
+    if client.is_connected() {
+        return;
+    }
+
+    while let Some(message) = client.receive_message(CONNECTION_EVENTS_CHANNEL) {
+        if let Ok(ServerMessage::Disconnecting { reason: text }) = try_decode(&message) {
+            reason.0 = Some(text);
+        }
+    }
+}
+
+/// Retry to connect to the server or quit when it is unreachable.
+#[allow(clippy::too_many_arguments)]
+pub fn unreachable_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    params: Res<ConnectParams>,
+    mut status: ResMut<ConnectionStatus>,
+    mut local_player: ResMut<LocalPlayer>,
+    mut reason: ResMut<DisconnectReason>,
+    mut exit: EventWriter<AppExit>,
+    panels: Query<Entity, With<UnreachablePanel>>,
+) {
+    if *status != ConnectionStatus::Unreachable {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::R) {
+        for panel in panels.iter() {
+            commands.entity(panel).despawn_recursive();
+        }
+        commands.insert_resource(new_renet_client(&params));
+        *local_player = LocalPlayer::default();
+        reason.0 = None;
+        *status = ConnectionStatus::Connecting { since: Instant::now() };
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreachable_after_the_timeout() {
+        let since = Instant::now();
+        let timeout = Duration::from_secs(5);
+        let connecting = ConnectionStatus::Connecting { since };
+
+        let soon = since + Duration::from_secs(4);
+        assert_eq!(connecting.update(soon, timeout, false, false), connecting);
+        // Being connected isn't enough, the server must welcome us.
+        assert_eq!(connecting.update(soon, timeout, false, true), connecting);
+        let late = since + timeout;
+        let unreachable = ConnectionStatus::Unreachable;
+        assert_eq!(connecting.update(late, timeout, false, false), unreachable);
+        // Nothing changes until the player retries.
+        let later = late + timeout;
+        assert_eq!(unreachable.update(later, timeout, true, true), unreachable);
+    }
+
+    #[test]
+    fn connected_once_welcomed_before_the_timeout() {
+        let since = Instant::now();
+        let timeout = Duration::from_secs(5);
+        let connecting = ConnectionStatus::Connecting { since };
+        let soon = since + Duration::from_secs(1);
+        assert_eq!(connecting.update(soon, timeout, true, true), ConnectionStatus::Connected);
+        let late = since + timeout * 2;
+        assert_eq!(connecting.update(late, timeout, true, true), ConnectionStatus::Connected);
+    }
+
+    #[test]
+    fn lost_when_disconnected_while_playing() {
+        let now = Instant::now();
+        let timeout = Duration::from_secs(5);
+        let connected = ConnectionStatus::Connected;
+        assert_eq!(connected.update(now, timeout, true, true), connected);
+        let lost = ConnectionStatus::Lost { since: now };
+        assert_eq!(connected.update(now, timeout, true, false), lost);
+        // We don't get back in game by ourselves.
+        assert_eq!(lost.update(now + timeout, timeout, true, true), lost);
+    }
+
+    #[test]
+    fn the_disconnect_reason_ends_the_messages() {
+        assert_eq!(DisconnectReason(None).suffix(), "");
+        let reason = DisconnectReason(Some("the server is full".to_string()));
+        assert_eq!(reason.suffix(), ": the server is full");
+    }
+
+    #[test]
+    fn the_countdown_rounds_the_seconds_up() {
+        let since = Instant::now();
+        let countdown = |elapsed| connection_lost_countdown(since, since + elapsed);
+        assert_eq!(countdown(Duration::ZERO), Some(5));
+        assert_eq!(countdown(Duration::from_millis(1)), Some(5));
+        assert_eq!(countdown(Duration::from_millis(1000)), Some(4));
+        assert_eq!(countdown(Duration::from_millis(4999)), Some(1));
+        assert_eq!(countdown(CONNECTION_LOST_EXIT_DELAY), None);
+        assert_eq!(countdown(Duration::from_secs(60)), None);
+    }
+}
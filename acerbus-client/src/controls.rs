@@ -0,0 +1,339 @@
+use std::fmt;
+use std::str::FromStr;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use serde::{Serialize, Serializer};
+
+use crate::layers::{UiLayer, MENU_Z};
+use crate::settings::SettingsFile;
+use crate::GameAssets;
+
+pub const CONTROLS_MENU_KEY: KeyCode = KeyCode::F5;
+/// Dashes in the direction we move, whatever the control scheme.
+pub const DASH_KEY: KeyCode = KeyCode::LShift;
+
+/// The keys that move our player, a preset or the keys chosen by the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlScheme {
+    /// WASD and the arrows.
+    Wasd,
+    Arrows,
+    /// IJKL, for the left-handed players.
+    Ijkl,
+    /// The keys given with `--keys`.
+    Custom,
+}
+
+impl Default for ControlScheme {
+    fn default() -> ControlScheme {
+        ControlScheme::Wasd
+    }
+}
+
+impl FromStr for ControlScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ControlScheme, String> {
+        match s {
+            "wasd" => Ok(ControlScheme::Wasd),
+            "arrows" => Ok(ControlScheme::Arrows),
+            "ijkl" => Ok(ControlScheme::Ijkl),
+            "custom" => Ok(ControlScheme::Custom),
+            _ => Err(format!("unknown controls {:?}, expected wasd, arrows, ijkl or custom", s)),
+        }
+    }
+}
+
+impl fmt::Display for ControlScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ControlScheme::Wasd => "wasd",
+            ControlScheme::Arrows => "arrows",
+            ControlScheme::Ijkl => "ijkl",
+            ControlScheme::Custom => "custom",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Written as the `--controls` flag takes it.
+impl Serialize for ControlScheme {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The keys of the four directions, in the up, down, left and right order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirectionKeys(pub [KeyCode; 4]);
+
+impl DirectionKeys {
+    /// The keys of a preset, the custom scheme has none.
+    pub fn preset(scheme: ControlScheme) -> Option<DirectionKeys> {
+        use KeyCode::*;
+        match scheme {
+            ControlScheme::Wasd => Some(DirectionKeys([W, S, A, D])),
+            ControlScheme::Arrows => Some(DirectionKeys([Up, Down, Left, Right])),
+            ControlScheme::Ijkl => Some(DirectionKeys([I, K, J, L])),
+            ControlScheme::Custom => None,
+        }
+    }
+}
+
+impl FromStr for DirectionKeys {
+    type Err = String;
+
+    /// Parses four comma-separated keys, like `W,S,A,D`, letters, digits or arrows.
+    fn from_str(s: &str) -> Result<DirectionKeys, String> {
+        let keys: Vec<_> = s.split(',').map(|key| parse_key(key.trim())).collect();
+        match keys.as_slice() {
+            [Some(up), Some(down), Some(left), Some(right)] => {
+                Ok(DirectionKeys([*up, *down, *left, *right]))
+            }
+            _ => Err(format!("invalid keys {:?}, expected four keys like W,S,A,D", s)),
+        }
+    }
+}
+
+impl fmt::Display for DirectionKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [up, down, left, right] = self.0;
+        write!(f, "{:?},{:?},{:?},{:?}", up, down, left, right)
+    }
+}
+
+/// Written as the `--keys` flag takes them.
+impl Serialize for DirectionKeys {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Parses a letter, a digit or an arrow, as `KeyCode` names them.
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    use KeyCode::*;
+    const LETTERS: [KeyCode; 26] =
+        [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z];
+    const DIGITS: [KeyCode; 10] = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    let name = name.to_ascii_uppercase();
+    match name.as_bytes() {
+        [letter @ b'A'..=b'Z'] => Some(LETTERS[(letter - b'A') as usize]),
+        [digit @ b'0'..=b'9'] => Some(DIGITS[(digit - b'0') as usize]),
+        [b'K', b'E', b'Y', digit @ b'0'..=b'9'] => Some(DIGITS[(digit - b'0') as usize]),
+        _ => match name.as_str() {
+            "UP" => Some(Up),
+            "DOWN" => Some(Down),
+            "LEFT" => Some(Left),
+            "RIGHT" => Some(Right),
+            _ => None,
+        },
+    }
+}
+
+/// Mirrors the directions for the players more at ease with them inverted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AxisMirror {
+    /// Left moves right and right moves left.
+    pub x: bool,
+    /// Up moves down and down moves up.
+    pub y: bool,
+    /// The horizontal keys move vertically and the vertical keys horizontally.
+    pub swap: bool,
+}
+
+impl AxisMirror {
+    pub fn apply(self, input: PlayerInput) -> PlayerInput {
+        let mut input = input;
+        if self.swap {
+            input = PlayerInput {
+                up: input.right,
+                down: input.left,
+                left: input.down,
+                right: input.up,
+                dash: input.dash,
+            };
+        }
+        if self.x {
+            std::mem::swap(&mut input.left, &mut input.right);
+        }
+        if self.y {
+            std::mem::swap(&mut input.up, &mut input.down);
+        }
+        input
+    }
+}
+
+/// The keys that move our player and how the directions are mirrored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    pub scheme: ControlScheme,
+    /// The keys of the custom scheme, if the player gave some.
+    pub custom: Option<DirectionKeys>,
+    pub mirror: AxisMirror,
+}
+
+impl KeyBindings {
+    /// The keys in use, the custom scheme without keys falls back to WASD.
+    pub fn keys(&self) -> DirectionKeys {
+        match (self.scheme, self.custom) {
+            (ControlScheme::Custom, Some(custom)) => custom,
+            (scheme, _) => DirectionKeys::preset(scheme)
+                .unwrap_or_else(|| DirectionKeys::preset(ControlScheme::Wasd).unwrap()),
+        }
+    }
+
+    /// Returns the directions pressed on the keyboard, before mirroring.
+    pub fn input(&self, keyboard_input: &Input<KeyCode>) -> PlayerInput {
+        let [up, down, left, right] = self.keys().0;
+        // The arrows always work with the default scheme, like they always did.
+        let arrows = self.scheme == ControlScheme::Wasd;
+        PlayerInput {
+            up: keyboard_input.pressed(up) || (arrows && keyboard_input.pressed(KeyCode::Up)),
+            down: keyboard_input.pressed(down) || (arrows && keyboard_input.pressed(KeyCode::Down)),
+            left: keyboard_input.pressed(left) || (arrows && keyboard_input.pressed(KeyCode::Left)),
+            right: keyboard_input.pressed(right)
+                || (arrows && keyboard_input.pressed(KeyCode::Right)),
+            dash: keyboard_input.pressed(DASH_KEY),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ControlsMenu;
+
+/// Returns the text of the controls menu, with the current choices marked.
+pub fn controls_menu_text(bindings: &KeyBindings) -> String {
+    let mark = |on: bool| if on { "x" } else { " " };
+    let scheme = |scheme: ControlScheme| mark(bindings.scheme == scheme);
+    let custom = match bindings.custom {
+        Some(keys) => keys.to_string(),
+        None => "none, use --keys".to_string(),
+    };
+    format!(
+        "Controls - {:?} to close\n\
+         \n\
+         [{}] 1 WASD and the arrows\n\
+         [{}] 2 the arrows only\n\
+         [{}] 3 IJKL\n\
+         [{}] 4 custom keys ({})\n\
+         \n\
+         [{}] 7 mirror left and right\n\
+         [{}] 8 mirror up and down\n\
+         [{}] 9 swap the axes",
+        CONTROLS_MENU_KEY,
+        scheme(ControlScheme::Wasd),
+        scheme(ControlScheme::Arrows),
+        scheme(ControlScheme::Ijkl),
+        scheme(ControlScheme::Custom),
+        custom,
+        mark(bindings.mirror.x),
+        mark(bindings.mirror.y),
+        mark(bindings.mirror.swap),
+    )
+}
+
+/// Opens the controls menu, picks a scheme with the digits and remembers it in the settings.
+pub fn controls_menu_system(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    game_assets: Res<GameAssets>,
+    mut bindings: ResMut<KeyBindings>,
+    mut settings: ResMut<SettingsFile>,
+    mut open: Local<bool>,
+    menus: Query<Entity, With<ControlsMenu>>,
+) {
+    let mut changed = keyboard_input.just_pressed(CONTROLS_MENU_KEY);
+    if changed {
+        *open = !*open;
+    }
+
+    if *open {
+        let previous = bindings.clone();
+        for key in keyboard_input.get_just_pressed() {
+            match key {
+                KeyCode::Key1 => bindings.scheme = ControlScheme::Wasd,
+                KeyCode::Key2 => bindings.scheme = ControlScheme::Arrows,
+                KeyCode::Key3 => bindings.scheme = ControlScheme::Ijkl,
+                KeyCode::Key4 if bindings.custom.is_some() => {
+                    bindings.scheme = ControlScheme::Custom
+                }
+                KeyCode::Key7 => bindings.mirror.x = !bindings.mirror.x,
+                KeyCode::Key8 => bindings.mirror.y = !bindings.mirror.y,
+                KeyCode::Key9 => bindings.mirror.swap = !bindings.mirror.swap,
+                _ => (),
+            }
+        }
+        if *bindings != previous {
+            settings.settings.controls = bindings.scheme;
+            settings.settings.custom_keys = bindings.custom;
+            settings.settings.mirror = bindings.mirror;
+            settings.save();
+            changed = true;
+        }
+    }
+
+    if !changed {
+        return;
+    }
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+    if !*open {
+        return;
+    }
+
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 20.0, color: Color::WHITE };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { right: Val::Px(40.0), top: Val::Px(40.0), ..default() },
+                padding: Rect::all(Val::Px(16.0)),
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..default()
+        })
+        .insert(ControlsMenu)
+        .insert(UiLayer(MENU_Z))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(controls_menu_text(&bindings), text_style, default()),
+                ..default()
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirroring_inverts_the_axes() {
+        let up_left = PlayerInput { up: true, left: true, ..default() };
+        assert_eq!(AxisMirror::default().apply(up_left), up_left);
+
+        let mirror = AxisMirror { x: true, ..default() };
+        let expected = PlayerInput { up: true, right: true, ..default() };
+        assert_eq!(mirror.apply(up_left), expected);
+
+        let mirror = AxisMirror { x: true, y: true, ..default() };
+        let expected = PlayerInput { down: true, right: true, ..default() };
+        assert_eq!(mirror.apply(up_left), expected);
+    }
+
+    #[test]
+    fn swapping_exchanges_the_axes() {
+        let mirror = AxisMirror { swap: true, ..default() };
+        let right = PlayerInput { right: true, ..default() };
+        assert_eq!(mirror.apply(right), PlayerInput { up: true, ..default() });
+        let down = PlayerInput { down: true, ..default() };
+        assert_eq!(mirror.apply(down), PlayerInput { left: true, ..default() });
+
+        // Swapped first, then mirrored.
+        let mirror = AxisMirror { swap: true, x: true, y: false };
+        assert_eq!(mirror.apply(down), PlayerInput { right: true, ..default() });
+    }
+}
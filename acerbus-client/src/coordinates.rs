@@ -0,0 +1,46 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+/// Where the coordinates shown to the player start from, the simulation always
+/// uses the center of the arena, this only changes how positions are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinateOrigin {
+    /// The center of the arena, like the physics.
+    Center,
+    /// The bottom-left corner of the arena, handier to build maps.
+    Corner,
+}
+
+impl Default for CoordinateOrigin {
+    fn default() -> CoordinateOrigin {
+        CoordinateOrigin::Center
+    }
+}
+
+impl CoordinateOrigin {
+    /// Converts a position of the simulation into the one to display.
+    pub fn to_display(self, position: Vec2, arena: Arena) -> Vec2 {
+        match self {
+            CoordinateOrigin::Center => position,
+            CoordinateOrigin::Corner => center_to_corner(position, arena),
+        }
+    }
+}
+
+pub fn center_to_corner(position: Vec2, arena: Arena) -> Vec2 {
+    position + Vec2::new(arena.width, arena.height) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corner_coordinates_start_from_the_bottom_left() {
+        let arena = Arena { width: 800., height: 600. };
+        let corner = CoordinateOrigin::Corner;
+        assert_eq!(corner.to_display(Vec2::new(-400., -300.), arena), Vec2::ZERO);
+        assert_eq!(corner.to_display(Vec2::ZERO, arena), Vec2::new(400., 300.));
+        assert_eq!(CoordinateOrigin::Center.to_display(Vec2::ONE, arena), Vec2::ONE);
+    }
+}
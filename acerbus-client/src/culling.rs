@@ -0,0 +1,65 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::camera_bounds::visible_half_size;
+use crate::MainCamera;
+
+/// How far beyond the edges of the view a player is still considered visible,
+/// its overlays are drawn above it and must not pop in when it enters the view.
+const CULLING_MARGIN: f32 = PLAYER_SQUARE_HEIGHT;
+
+/// Returns whether something at this position can be seen through the camera.
+pub fn is_on_screen(
+    camera_transform: &GlobalTransform,
+    projection: &OrthographicProjection,
+    position: Vec2,
+) -> bool {
+    let half_view = visible_half_size(projection) + Vec2::splat(CULLING_MARGIN);
+    let offset = (position - camera_transform.translation.truncate()).abs();
+    offset.x <= half_view.x && offset.y <= half_view.y
+}
+
+/// Hides the overlays drawn above the players, like the quality bars, when they are off-screen.
+pub fn cull_player_overlays_system(
+    cameras: Query<(&GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    players: Query<(&GlobalTransform, &Children), With<Player>>,
+    mut overlays: Query<&mut Visibility, Without<Player>>,
+) {
+    let (camera_transform, projection) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    for (transform, children) in players.iter() {
+        let visible = is_on_screen(camera_transform, projection, transform.translation.truncate());
+        for child in children.iter() {
+            if let Ok(mut visibility) = overlays.get_mut(*child) {
+                // Only touched when it changes so that the change detection stays meaningful.
+                if visibility.is_visible != visible {
+                    visibility.is_visible = visible;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players_just_beyond_the_edges_are_still_on_screen() {
+        let projection = OrthographicProjection {
+            left: -400.,
+            right: 400.,
+            bottom: -300.,
+            top: 300.,
+            ..default()
+        };
+        let camera = GlobalTransform::from_xyz(100., 0., 0.);
+        assert!(is_on_screen(&camera, &projection, Vec2::new(100., 0.)));
+        assert!(is_on_screen(&camera, &projection, Vec2::new(500. + CULLING_MARGIN, 0.)));
+        assert!(!is_on_screen(&camera, &projection, Vec2::new(501. + CULLING_MARGIN, 0.)));
+        assert!(!is_on_screen(&camera, &projection, Vec2::new(100., -301. - CULLING_MARGIN)));
+    }
+}
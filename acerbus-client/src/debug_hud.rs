@@ -0,0 +1,147 @@
+use std::fmt::Write;
+
+use acerbus_common::*;
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::entity::Entities;
+use bevy::prelude::*;
+
+use crate::connecting::LocalPlayer;
+use crate::coordinates::CoordinateOrigin;
+use crate::layers::{UiLayer, HUD_Z};
+use crate::GameAssets;
+
+pub const DEBUG_HUD_TOGGLE_KEY: KeyCode = KeyCode::F3;
+
+/// The state of the F3 debug overlay.
+#[derive(Debug, Default)]
+pub struct DebugHud {
+    pub visible: bool,
+    last_position: Option<Vec2>,
+    pub velocity: Vec2,
+}
+
+/// How many ticks per second the server simulates, as it told us when we joined.
+#[derive(Debug, Clone, Copy)]
+pub struct ServerTickRate(pub f64);
+
+#[derive(Component)]
+pub struct DebugHudText;
+
+/// Returns whether we render fewer frames than the server simulates ticks,
+/// we then send fewer inputs than the server applies and some ticks miss one.
+pub fn is_below_tick_rate(fps: f64, tick_rate: f64) -> bool {
+    fps < tick_rate
+}
+
+/// Returns the velocity of something that moved from `previous` to `current` in `delta` seconds.
+pub fn derive_velocity(previous: Vec2, current: Vec2, delta: f32) -> Vec2 {
+    if delta <= 0.0 {
+        Vec2::ZERO
+    } else {
+        (current - previous) / delta
+    }
+}
+
+pub fn setup_debug_hud(mut commands: Commands, game_assets: Res<GameAssets>) {
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 16.0, color: Color::WHITE };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { right: Val::Px(10.0), top: Val::Px(10.0), ..default() },
+                ..default()
+            },
+            text: Text::with_section(String::with_capacity(128), text_style, default()),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(DebugHudText)
+        .insert(UiLayer(HUD_Z));
+}
+
+pub fn toggle_debug_hud_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut hud: ResMut<DebugHud>,
+    mut texts: Query<&mut Visibility, With<DebugHudText>>,
+) {
+    if keyboard_input.just_pressed(DEBUG_HUD_TOGGLE_KEY) {
+        hud.visible = !hud.visible;
+        for mut visibility in texts.iter_mut() {
+            visibility.is_visible = hud.visible;
+        }
+    }
+}
+
+/// Updates the overlay in place, the text buffer is reused from one frame to the other.
+#[allow(clippy::too_many_arguments)]
+pub fn debug_hud_system(
+    time: Res<Time>,
+    diagnostics: Res<Diagnostics>,
+    entities: &Entities,
+    local_player: Res<LocalPlayer>,
+    lobby: Res<Lobby>,
+    origin: Res<CoordinateOrigin>,
+    arena: Option<Res<Arena>>,
+    tick_rate: Option<Res<ServerTickRate>>,
+    mut hud: ResMut<DebugHud>,
+    transforms: Query<&Transform, With<Player>>,
+    mut texts: Query<&mut Text, With<DebugHudText>>,
+) {
+    let position = local_player
+        .player
+        .and_then(|player| lobby.players.get(&player))
+        .and_then(|entity| transforms.get(*entity).ok())
+        .map(|transform| transform.translation.truncate());
+
+    if let (Some(previous), Some(current)) = (hud.last_position, position) {
+        hud.velocity = derive_velocity(previous, current, time.delta_seconds());
+    }
+    hud.last_position = position;
+
+    if !hud.visible {
+        return;
+    }
+
+    let arena = arena.map_or_else(Arena::default, |arena| *arena);
+    let displayed = position.map(|position| origin.to_display(position, arena));
+    let fps = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS).and_then(|fps| fps.average());
+    for mut text in texts.iter_mut() {
+        let value = &mut text.sections[0].value;
+        value.clear();
+        match displayed {
+            Some(position) => writeln!(value, "position: {:.1} {:.1}", position.x, position.y),
+            None => writeln!(value, "position: -"),
+        }
+        .unwrap();
+        writeln!(value, "velocity: {:.1} {:.1}", hud.velocity.x, hud.velocity.y).unwrap();
+        writeln!(value, "entities: {}", entities.len()).unwrap();
+        write!(value, "fps: {:.0}", fps.unwrap_or(0.0)).unwrap();
+        if let Some(tick_rate) = tick_rate.as_deref() {
+            write!(value, "\nserver tick rate: {:.0}", tick_rate.0).unwrap();
+            if fps.map_or(false, |fps| is_below_tick_rate(fps, tick_rate.0)) {
+                write!(value, "\nwarning: fewer frames than server ticks").unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn velocity_is_the_distance_over_the_frame() {
+        let velocity = derive_velocity(Vec2::new(10., 0.), Vec2::new(12., -1.), 0.5);
+        assert_eq!(velocity, Vec2::new(4., -2.));
+        // A frame that took no time doesn't give an infinite velocity.
+        assert_eq!(derive_velocity(Vec2::ZERO, Vec2::ONE, 0.), Vec2::ZERO);
+    }
+
+    #[test]
+    fn warns_when_rendering_slower_than_the_server_ticks() {
+        assert!(is_below_tick_rate(45., 60.));
+        assert!(!is_below_tick_rate(60., 60.));
+        assert!(!is_below_tick_rate(144., 60.));
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+
+/// The transparency of the players that are about to be despawned.
+const DISCONNECTED_ALPHA: f32 = 0.3;
+
+/// How long, in seconds, the cube of a disconnected player stays around, dimmed.
+pub struct DespawnGrace(pub f64);
+
+/// The cubes of the disconnected players, with the time at which they must be despawned.
+#[derive(Debug, Default)]
+pub struct PendingDespawns {
+    players: BTreeMap<Player, (Entity, f64)>,
+}
+
+impl PendingDespawns {
+    pub fn schedule(&mut self, player: Player, entity: Entity, at: f64) {
+        self.players.insert(player, (entity, at));
+    }
+
+    /// Returns the entity of the player if it was about to be despawned.
+    pub fn cancel(&mut self, player: Player) -> Option<Entity> {
+        self.players.remove(&player).map(|(entity, _)| entity)
+    }
+
+    /// Removes and returns the entities that must be despawned now.
+    pub fn take_expired(&mut self, now: f64) -> Vec<Entity> {
+        let mut expired = Vec::new();
+        self.players.retain(|_, (entity, at)| {
+            let keep = *at > now;
+            if !keep {
+                expired.push(*entity);
+            }
+            keep
+        });
+        expired
+    }
+}
+
+/// Dims the cube of a disconnected player, or brings its color back.
+pub fn set_disconnected_look(
+    materials: &mut Assets<ColorMaterial>,
+    handle: &Handle<ColorMaterial>,
+    disconnected: bool,
+) {
+    if let Some(material) = materials.get_mut(handle) {
+        material.color.set_a(if disconnected { DISCONNECTED_ALPHA } else { 1.0 });
+    }
+}
+
+pub fn despawn_disconnected_players_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pending: ResMut<PendingDespawns>,
+) {
+    for entity in pending.take_expired(time.seconds_since_startup()) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disconnected_players_are_despawned_after_the_grace() {
+        let mut pending = PendingDespawns::default();
+        pending.schedule(Player { id: 1 }, Entity::from_raw(1), 5.0);
+        pending.schedule(Player { id: 2 }, Entity::from_raw(2), 8.0);
+
+        assert!(pending.take_expired(4.0).is_empty());
+        assert_eq!(pending.take_expired(5.0), vec![Entity::from_raw(1)]);
+        assert!(pending.take_expired(6.0).is_empty());
+        assert_eq!(pending.take_expired(10.0), vec![Entity::from_raw(2)]);
+    }
+
+    #[test]
+    fn reconnected_players_are_kept() {
+        let mut pending = PendingDespawns::default();
+        pending.schedule(Player { id: 1 }, Entity::from_raw(1), 5.0);
+
+        assert_eq!(pending.cancel(Player { id: 1 }), Some(Entity::from_raw(1)));
+        assert_eq!(pending.cancel(Player { id: 1 }), None);
+        assert!(pending.take_expired(10.0).is_empty());
+    }
+}
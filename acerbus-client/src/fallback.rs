@@ -0,0 +1,47 @@
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::GameAssets;
+
+const FALLBACK_IMAGE_SIZE: u32 = 32;
+
+/// Generates an image of a single color to replace an image that couldn't be loaded.
+pub fn solid_color_image(color: Color, size: u32) -> Image {
+    let [r, g, b, a] = color.as_rgba_f32();
+    let pixel = [r, g, b, a].map(|component| (component * 255.0).round() as u8);
+    Image::new_fill(
+        Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &pixel,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// Replaces the images that failed to load with solid color ones, for the people
+/// that run the client without the assets folder.
+pub fn asset_fallback_system(
+    asset_server: Res<AssetServer>,
+    mut game_assets: ResMut<GameAssets>,
+    mut images: ResMut<Assets<Image>>,
+    mut font_warned: Local<bool>,
+) {
+    let game_assets = &mut *game_assets;
+    let fallbacks = [
+        ("images/icon-green.png", &mut game_assets.icon_green, Color::GREEN),
+        ("images/icon-purple.png", &mut game_assets.icon_purple, Color::PURPLE),
+    ];
+
+    for (path, handle, color) in fallbacks {
+        if asset_server.get_load_state(&*handle) == LoadState::Failed {
+            warn!("Could not load {:?}, using a solid color image instead.", path);
+            *handle = images.add(solid_color_image(color, FALLBACK_IMAGE_SIZE));
+        }
+    }
+
+    // We can't generate a font, texts will be missing.
+    if !*font_warned && asset_server.get_load_state(&game_assets.font) == LoadState::Failed {
+        warn!("Could not load the font, the texts will not be displayed.");
+        *font_warned = true;
+    }
+}
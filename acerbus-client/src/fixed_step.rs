@@ -0,0 +1,144 @@
+use bevy::prelude::*;
+
+/// The tick rate used until the server tells us its own.
+pub const DEFAULT_STEP_RATE: f64 = 60.0;
+/// The steps left over are dropped after a long frame instead of being caught up.
+pub const MAX_STEPS_PER_FRAME: u32 = 5;
+
+/// Returns how far we are between the last two fixed steps, 0 right at the last step and
+/// almost 1 just before the next one.
+pub fn render_alpha(accumulator: f64, step: f64) -> f32 {
+    if step > 0. {
+        (accumulator / step).clamp(0., 1.) as f32
+    } else {
+        1.
+    }
+}
+
+/// Our player is moved by fixed steps, at the tick rate of the server, and is rendered
+/// between its last two positions so that it doesn't stutter when we render faster.
+pub struct FixedStepClock {
+    /// The time that elapsed since the last step, in seconds.
+    pub accumulator: f64,
+    /// Render our player between its last two positions, instead of at the last one.
+    pub interpolate: bool,
+}
+
+impl FixedStepClock {
+    pub fn new(interpolate: bool) -> FixedStepClock {
+        FixedStepClock { accumulator: 0., interpolate }
+    }
+
+    /// Adds the duration of the frame and returns the number of steps to simulate.
+    pub fn advance(&mut self, delta: f64, step: f64) -> u32 {
+        self.accumulator += delta;
+        let mut steps = 0;
+        while self.accumulator >= step && steps < MAX_STEPS_PER_FRAME {
+            self.accumulator -= step;
+            steps += 1;
+        }
+        if steps == MAX_STEPS_PER_FRAME {
+            self.accumulator = self.accumulator.min(step);
+        }
+        steps
+    }
+
+    /// The progress between the last two steps, used to render our player.
+    pub fn alpha(&self, step: f64) -> f32 {
+        if self.interpolate {
+            render_alpha(self.accumulator, step)
+        } else {
+            1.
+        }
+    }
+}
+
+/// The tick the server is at, counted by the client from the one it was told on connect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientTick {
+    synced_tick: u64,
+    /// When we were told about `synced_tick`, in seconds.
+    synced_at: f64,
+    tick_rate: f64,
+}
+
+impl ClientTick {
+    pub fn new(tick: u64, now: f64, tick_rate: f64) -> ClientTick {
+        ClientTick { synced_tick: tick, synced_at: now, tick_rate }
+    }
+
+    /// Returns the tick we are at, at `now`.
+    pub fn at(&self, now: f64) -> u64 {
+        let elapsed = ((now - self.synced_at) * self.tick_rate).max(0.);
+        self.synced_tick + elapsed as u64
+    }
+}
+
+/// The last two simulated positions of our player and the one it was rendered at,
+/// the simulation restarts from the rendered one when something else moved the player.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct SimulatedPosition {
+    pub previous: Vec2,
+    pub current: Vec2,
+    pub rendered: Vec2,
+}
+
+impl SimulatedPosition {
+    pub fn new(position: Vec2) -> SimulatedPosition {
+        SimulatedPosition { previous: position, current: position, rendered: position }
+    }
+
+    pub fn step(&mut self, position: Vec2) {
+        self.previous = self.current;
+        self.current = position;
+    }
+
+    /// Returns where to render the player at this progress between the last two steps.
+    pub fn render(&mut self, alpha: f32) -> Vec2 {
+        self.rendered = self.previous.lerp(self.current, alpha);
+        self.rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alpha_is_how_far_we_are_into_the_step() {
+        assert_eq!(render_alpha(0., 0.1), 0.);
+        assert!((render_alpha(0.025, 0.1) - 0.25).abs() < 1e-6);
+        assert_eq!(render_alpha(0.3, 0.1), 1.);
+        assert_eq!(render_alpha(0.05, 0.), 1.);
+    }
+
+    #[test]
+    fn long_frames_drop_the_steps_left_over() {
+        let mut clock = FixedStepClock::new(true);
+        assert_eq!(clock.advance(0.25, 0.5), 0);
+        assert_eq!(clock.advance(0.5, 0.5), 1);
+        assert!((clock.alpha(0.5) - 0.5).abs() < 1e-6);
+
+        assert_eq!(clock.advance(10., 0.5), MAX_STEPS_PER_FRAME);
+        assert_eq!(clock.accumulator, 0.5);
+        assert_eq!(FixedStepClock::new(false).alpha(0.5), 1.);
+    }
+
+    #[test]
+    fn the_player_is_rendered_between_its_last_two_steps() {
+        let mut position = SimulatedPosition::new(Vec2::ZERO);
+        position.step(Vec2::new(10., 0.));
+        position.step(Vec2::new(20., 0.));
+        assert_eq!(position.render(0.5), Vec2::new(15., 0.));
+        assert_eq!(position.rendered, Vec2::new(15., 0.));
+    }
+
+    #[test]
+    fn client_tick_counts_from_the_server_tick() {
+        let tick = ClientTick::new(100, 10., 60.);
+        assert_eq!(tick.at(5.), 100);
+        assert_eq!(tick.at(10.), 100);
+        assert_eq!(tick.at(10.5), 130);
+        assert_eq!(tick.at(11.), 160);
+    }
+}
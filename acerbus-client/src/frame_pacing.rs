@@ -0,0 +1,59 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::freeze::{FreezeWhenHidden, FROZEN_FPS};
+
+/// Limits the number of frames rendered per second by sleeping at the end of the frames.
+pub struct FramePacing {
+    pub target_fps: f64,
+    frame_start: Option<Instant>,
+}
+
+impl FramePacing {
+    pub fn new(target_fps: f64) -> FramePacing {
+        FramePacing { target_fps, frame_start: None }
+    }
+}
+
+/// Returns how long to sleep after a frame that took `frame_time` to reach the target FPS.
+pub fn frame_pacing_sleep(target_fps: f64, frame_time: Duration) -> Duration {
+    if target_fps <= 0. {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(1. / target_fps).saturating_sub(frame_time)
+}
+
+/// Runs last and sleeps for the rest of the frame budget, the network
+/// messages are still sent and received once per frame.
+pub fn frame_pacing_system(mut pacing: ResMut<FramePacing>, freeze: Option<Res<FreezeWhenHidden>>) {
+    let target_fps = match freeze {
+        Some(freeze) if freeze.frozen => FROZEN_FPS,
+        _ => pacing.target_fps,
+    };
+    if let Some(frame_start) = pacing.frame_start {
+        let sleep = frame_pacing_sleep(target_fps, frame_start.elapsed());
+        if !sleep.is_zero() {
+            thread::sleep(sleep);
+        }
+    }
+    pacing.frame_start = Some(Instant::now());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_frames_sleep_for_the_rest_of_the_budget() {
+        let sleep = frame_pacing_sleep(50., Duration::from_millis(5));
+        assert_eq!(sleep, Duration::from_millis(15));
+    }
+
+    #[test]
+    fn slow_frames_do_not_sleep() {
+        assert_eq!(frame_pacing_sleep(50., Duration::from_millis(30)), Duration::ZERO);
+        assert_eq!(frame_pacing_sleep(0., Duration::ZERO), Duration::ZERO);
+    }
+}
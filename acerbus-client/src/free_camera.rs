@@ -0,0 +1,109 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+
+use crate::MainCamera;
+
+pub const FREE_CAMERA_TOGGLE_KEY: KeyCode = KeyCode::F2;
+const FREE_CAMERA_PAN_SPEED: f32 = 500.0;
+const FREE_CAMERA_ZOOM_STEP: f32 = 0.1;
+const FREE_CAMERA_MIN_SCALE: f32 = 0.1;
+
+/// Whether the camera follows the local player or is freely moved around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    Follow,
+    Free,
+}
+
+impl Default for CameraMode {
+    fn default() -> CameraMode {
+        CameraMode::Follow
+    }
+}
+
+impl CameraMode {
+    pub fn toggle(self) -> CameraMode {
+        match self {
+            CameraMode::Follow => CameraMode::Free,
+            CameraMode::Free => CameraMode::Follow,
+        }
+    }
+}
+
+/// Whether the local player stops moving while the camera is free.
+pub struct FreezePlayerInFreeCamera(pub bool);
+
+/// Returns the direction in which to pan the camera, it is zero when the camera follows the player.
+pub fn pan_direction(mode: CameraMode, keyboard_input: &Input<KeyCode>) -> Vec2 {
+    if mode == CameraMode::Follow {
+        return Vec2::ZERO;
+    }
+
+    let pressed = |a, b| keyboard_input.pressed(a) || keyboard_input.pressed(b);
+    let x = pressed(KeyCode::D, KeyCode::Right) as i8 - pressed(KeyCode::A, KeyCode::Left) as i8;
+    let y = pressed(KeyCode::W, KeyCode::Up) as i8 - pressed(KeyCode::S, KeyCode::Down) as i8;
+    Vec2::new(x as f32, y as f32)
+}
+
+/// Switch between the follow and the free camera, the zoom is reset when we follow again.
+pub fn toggle_camera_mode_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mode: ResMut<CameraMode>,
+    mut projections: Query<&mut OrthographicProjection, With<MainCamera>>,
+) {
+    if keyboard_input.just_pressed(FREE_CAMERA_TOGGLE_KEY) {
+        *mode = mode.toggle();
+        if *mode == CameraMode::Follow {
+            for mut projection in projections.iter_mut() {
+                projection.scale = 1.0;
+            }
+        }
+    }
+}
+
+/// Pan the free camera with the arrows or WASD and zoom with the mouse wheel.
+pub fn free_camera_system(
+    time: Res<Time>,
+    mode: Res<CameraMode>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let zoom: f32 = mouse_wheel.iter().map(|event| event.y).sum();
+    if *mode == CameraMode::Follow {
+        return;
+    }
+
+    let direction = pan_direction(*mode, &keyboard_input);
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        let pan = direction * FREE_CAMERA_PAN_SPEED * projection.scale * time.delta_seconds();
+        transform.translation += pan.extend(0.);
+        projection.scale =
+            (projection.scale - zoom * FREE_CAMERA_ZOOM_STEP).max(FREE_CAMERA_MIN_SCALE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_free_camera_pans() {
+        let mut keyboard_input = Input::<KeyCode>::default();
+        keyboard_input.press(KeyCode::D);
+        keyboard_input.press(KeyCode::Up);
+        assert_eq!(pan_direction(CameraMode::Follow, &keyboard_input), Vec2::ZERO);
+        assert_eq!(pan_direction(CameraMode::Free, &keyboard_input), Vec2::new(1., 1.));
+
+        // Opposite keys cancel each other.
+        keyboard_input.press(KeyCode::Left);
+        assert_eq!(pan_direction(CameraMode::Free, &keyboard_input), Vec2::new(0., 1.));
+    }
+
+    #[test]
+    fn toggling_twice_follows_again() {
+        let mode = CameraMode::default();
+        assert_eq!(mode.toggle(), CameraMode::Free);
+        assert_eq!(mode.toggle().toggle(), CameraMode::Follow);
+    }
+}
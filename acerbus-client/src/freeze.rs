@@ -0,0 +1,90 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy::window::WindowResized;
+use bevy_renet::renet::RenetClient;
+
+use crate::connecting::{run_if_in_game, ConnectionStatus};
+
+/// The frames per second while the window is minimized, the connection is kept alive
+/// as the network messages are still sent and received once per frame.
+pub const FROZEN_FPS: f64 = 5.0;
+
+/// Slows the client down to a few frames per second and pauses the heavy systems while
+/// its window is minimized, to save battery, the last frame stays on screen.
+#[derive(Debug, Default)]
+pub struct FreezeWhenHidden {
+    pub frozen: bool,
+}
+
+impl FreezeWhenHidden {
+    /// A window resized to nothing is minimized, it is restored once it has a size again.
+    pub fn resized(&mut self, width: f32, height: f32) {
+        self.frozen = width <= 0. || height <= 0.;
+    }
+}
+
+pub fn freeze_when_hidden_system(
+    mut freeze: ResMut<FreezeWhenHidden>,
+    mut resized_events: EventReader<WindowResized>,
+) {
+    let was_frozen = freeze.frozen;
+    for event in resized_events.iter() {
+        freeze.resized(event.width, event.height);
+    }
+    if freeze.frozen != was_frozen {
+        info!("The window is {}.", if freeze.frozen { "minimized, freezing" } else { "back" });
+    }
+}
+
+/// Runs the heavy systems, the ones rendering and moving the players, unless frozen.
+pub fn run_unless_frozen(freeze: Option<Res<FreezeWhenHidden>>) -> ShouldRun {
+    match freeze {
+        Some(freeze) if freeze.frozen => ShouldRun::No,
+        _ => ShouldRun::Yes,
+    }
+}
+
+/// Runs the heavy systems that need the world and our player to exist, unless frozen.
+pub fn run_if_playing(
+    status: Res<ConnectionStatus>,
+    client: Res<RenetClient>,
+    freeze: Option<Res<FreezeWhenHidden>>,
+) -> ShouldRun {
+    match run_unless_frozen(freeze) {
+        ShouldRun::Yes => run_if_in_game(status, client),
+        _ => ShouldRun::No,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Runs(u32);
+
+    fn count_runs(mut runs: ResMut<Runs>) {
+        runs.0 += 1;
+    }
+
+    #[test]
+    fn minimizing_pauses_the_gated_systems() {
+        let mut world = World::new();
+        world.insert_resource(FreezeWhenHidden::default());
+        world.insert_resource(Runs::default());
+        let mut stage = SystemStage::single(count_runs.with_run_criteria(run_unless_frozen));
+
+        stage.run(&mut world);
+        assert_eq!(world.get_resource::<Runs>().unwrap().0, 1);
+
+        world.get_resource_mut::<FreezeWhenHidden>().unwrap().resized(0., 0.);
+        assert!(world.get_resource::<FreezeWhenHidden>().unwrap().frozen);
+        stage.run(&mut world);
+        assert_eq!(world.get_resource::<Runs>().unwrap().0, 1);
+
+        world.get_resource_mut::<FreezeWhenHidden>().unwrap().resized(1280., 720.);
+        assert!(!world.get_resource::<FreezeWhenHidden>().unwrap().frozen);
+        stage.run(&mut world);
+        assert_eq!(world.get_resource::<Runs>().unwrap().0, 2);
+    }
+}
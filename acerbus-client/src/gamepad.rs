@@ -0,0 +1,91 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::notification::spawn_notification;
+use crate::GameAssets;
+
+/// How far the left stick must be pushed to move.
+const GAMEPAD_STICK_THRESHOLD: f32 = 0.5;
+
+/// The gamepad the player moves with, the keyboard is always usable too.
+#[derive(Debug, Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+/// Returns the directions pushed on the gamepad, with the left stick or the D-pad.
+pub fn gamepad_input(
+    gamepad: Gamepad,
+    buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+) -> PlayerInput {
+    let pressed = |button_type| buttons.pressed(GamepadButton(gamepad, button_type));
+    let axis = |axis_type| axes.get(GamepadAxis(gamepad, axis_type)).unwrap_or(0.);
+    let x = axis(GamepadAxisType::LeftStickX);
+    let y = axis(GamepadAxisType::LeftStickY);
+    PlayerInput {
+        up: pressed(GamepadButtonType::DPadUp) || y >= GAMEPAD_STICK_THRESHOLD,
+        down: pressed(GamepadButtonType::DPadDown) || y <= -GAMEPAD_STICK_THRESHOLD,
+        left: pressed(GamepadButtonType::DPadLeft) || x <= -GAMEPAD_STICK_THRESHOLD,
+        right: pressed(GamepadButtonType::DPadRight) || x >= GAMEPAD_STICK_THRESHOLD,
+        dash: pressed(GamepadButtonType::South),
+    }
+}
+
+/// Uses the first gamepad connected and goes back to the keyboard when it is disconnected,
+/// the input is zeroed so that the player doesn't keep moving in the last stick direction.
+pub fn gamepad_connection_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    time: Res<Time>,
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut active: ResMut<ActiveGamepad>,
+    mut player_input: ResMut<PlayerInput>,
+) {
+    for GamepadEvent(gamepad, event_type) in gamepad_events.iter() {
+        match event_type {
+            GamepadEventType::Connected => {
+                if active.0.is_none() {
+                    active.0 = Some(*gamepad);
+                }
+                let text = format!("Gamepad {} connected", gamepad.0);
+                spawn_notification(&mut commands, &game_assets, &time, text);
+            }
+            GamepadEventType::Disconnected => {
+                if active.0 == Some(*gamepad) {
+                    active.0 = None;
+                    *player_input = PlayerInput::default();
+                }
+                let text = format!("Gamepad {} disconnected, back to the keyboard", gamepad.0);
+                spawn_notification(&mut commands, &game_assets, &time, text);
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_stick_and_the_dpad_move_the_player() {
+        let gamepad = Gamepad(0);
+        let mut buttons = Input::<GamepadButton>::default();
+        let mut axes = Axis::<GamepadAxis>::default();
+        assert_eq!(gamepad_input(gamepad, &buttons, &axes), PlayerInput::default());
+
+        buttons.press(GamepadButton(gamepad, GamepadButtonType::DPadUp));
+        axes.set(GamepadAxis(gamepad, GamepadAxisType::LeftStickX), -0.8);
+        // A slightly pushed stick is ignored.
+        axes.set(GamepadAxis(gamepad, GamepadAxisType::LeftStickY), -0.2);
+        let input = gamepad_input(gamepad, &buttons, &axes);
+        assert_eq!(input, PlayerInput { up: true, left: true, ..default() });
+    }
+
+    #[test]
+    fn other_gamepads_are_ignored() {
+        let mut buttons = Input::<GamepadButton>::default();
+        buttons.press(GamepadButton(Gamepad(1), GamepadButtonType::DPadDown));
+        let input = gamepad_input(Gamepad(0), &buttons, &Axis::default());
+        assert_eq!(input, PlayerInput::default());
+    }
+}
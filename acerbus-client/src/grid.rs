@@ -0,0 +1,84 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::layers::GRID_Z;
+
+const GRID_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const GRID_LINE_THICKNESS: f32 = 1.0;
+
+/// The distance between two lines of the background grid.
+pub struct GridSpacing(pub f32);
+
+#[derive(Component)]
+pub struct GridLine;
+
+/// Returns the x positions of the vertical lines and the y positions of the horizontal lines,
+/// the first and last lines of each axis are on the arena edges.
+pub fn grid_lines(arena: Arena, spacing: f32) -> (Vec<f32>, Vec<f32>) {
+    let axis = |length: f32| -> Vec<f32> {
+        if spacing <= 0. {
+            return Vec::new();
+        }
+        let count = (length / spacing).floor() as usize;
+        (0..=count).map(|i| -length / 2. + i as f32 * spacing).collect()
+    };
+    (axis(arena.width), axis(arena.height))
+}
+
+/// (Re)draw the grid under the players every time the arena changes.
+pub fn spawn_grid_system(
+    mut commands: Commands,
+    spacing: Res<GridSpacing>,
+    arena: Option<Res<Arena>>,
+    lines: Query<Entity, With<GridLine>>,
+) {
+    let arena = match arena {
+        Some(arena) if arena.is_changed() => *arena,
+        _ => return,
+    };
+
+    for entity in lines.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let (xs, ys) = grid_lines(arena, spacing.0);
+    let vertical =
+        xs.into_iter().map(|x| (Vec2::new(x, 0.), Vec2::new(GRID_LINE_THICKNESS, arena.height)));
+    let horizontal =
+        ys.into_iter().map(|y| (Vec2::new(0., y), Vec2::new(arena.width, GRID_LINE_THICKNESS)));
+    for (position, size) in vertical.chain(horizontal) {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite { color: GRID_COLOR, custom_size: Some(size), ..default() },
+                transform: Transform::from_translation(position.extend(GRID_Z)),
+                ..default()
+            })
+            .insert(GridLine);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_go_from_edge_to_edge() {
+        let arena = Arena { width: 400., height: 200. };
+        let (xs, ys) = grid_lines(arena, 100.);
+        assert_eq!(xs, vec![-200., -100., 0., 100., 200.]);
+        assert_eq!(ys, vec![-100., 0., 100.]);
+    }
+
+    #[test]
+    fn lines_stop_inside_the_arena() {
+        let arena = Arena { width: 250., height: 250. };
+        let (xs, _) = grid_lines(arena, 100.);
+        assert_eq!(xs, vec![-125., -25., 75.]);
+    }
+
+    #[test]
+    fn no_lines_without_spacing() {
+        let (xs, ys) = grid_lines(Arena::default(), 0.);
+        assert!(xs.is_empty() && ys.is_empty());
+    }
+}
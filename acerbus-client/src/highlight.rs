@@ -0,0 +1,120 @@
+use std::str::FromStr;
+
+use acerbus_common::*;
+use bevy::prelude::shape::{Circle, Quad};
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use serde::{Serialize, Serializer};
+
+use crate::connecting::LocalPlayer;
+use crate::layers::HIGHLIGHT_Z_OFFSET;
+
+/// How far the highlight goes beyond the cube of our player, in pixels.
+const HIGHLIGHT_WIDTH: f32 = 3.0;
+
+/// The outline drawn around our own player so that we find it at a glance,
+/// whatever the color of its team.
+pub struct LocalHighlight {
+    pub color: Color,
+}
+
+/// A color written in hexadecimal, like `ffd700` or `#ffd700`.
+#[derive(Debug, Clone, Copy)]
+pub struct HexColor(pub Color);
+
+impl FromStr for HexColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<HexColor, String> {
+        let hex = s.trim_start_matches('#');
+        Color::hex(hex).map(HexColor).map_err(|e| format!("invalid color {:?}: {:?}", s, e))
+    }
+}
+
+/// Written in hexadecimal, as the flags take it.
+impl Serialize for HexColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, _] = self.0.as_rgba_f32();
+        let byte = |channel: f32| (channel * 255.).round() as u8;
+        serializer.collect_str(&format_args!("{:02x}{:02x}{:02x}", byte(r), byte(g), byte(b)))
+    }
+}
+
+#[derive(Component)]
+pub struct LocalHighlightOutline;
+
+/// Returns which of the players is ours, we are told our player in the `InitState`
+/// as it is not our client id once we reconnected.
+pub fn local_entity(local_player: &LocalPlayer, lobby: &Lobby) -> Option<Entity> {
+    local_player.player.and_then(|player| lobby.players.get(&player)).copied()
+}
+
+/// The mesh of the highlight, the shape of the players but a little bigger.
+pub fn highlight_mesh(shape: BodyShape) -> Mesh {
+    match shape {
+        BodyShape::Square => {
+            let size = Vec2::new(PLAYER_SQUARE_WIDTH, PLAYER_SQUARE_HEIGHT);
+            Quad::new(size + HIGHLIGHT_WIDTH * 2.).into()
+        }
+        BodyShape::Circle => {
+            Circle::new(BodyShape::radius(SizeClass::Normal) + HIGHLIGHT_WIDTH).into()
+        }
+    }
+}
+
+/// Moves the highlight to our player, when it spawns or when we join another server,
+/// and draws it again when the players of the new server have another shape.
+pub fn local_highlight_system(
+    mut commands: Commands,
+    highlight: Res<LocalHighlight>,
+    local_player: Res<LocalPlayer>,
+    lobby: Res<Lobby>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut highlighted: Local<Option<(Entity, BodyShape)>>,
+    outlines: Query<Entity, With<LocalHighlightOutline>>,
+) {
+    let local = local_entity(&local_player, &lobby).map(|local| (local, local_player.body_shape));
+    if local == *highlighted {
+        return;
+    }
+
+    for outline in outlines.iter() {
+        commands.entity(outline).despawn_recursive();
+    }
+    if let Some((local, shape)) = local {
+        let mesh = Mesh2dHandle(meshes.add(highlight_mesh(shape)));
+        let material = materials.add(ColorMaterial::from(highlight.color));
+        commands.entity(local).with_children(|parent| {
+            parent
+                .spawn_bundle(MaterialMesh2dBundle {
+                    mesh,
+                    material,
+                    transform: Transform::from_xyz(0., 0., HIGHLIGHT_Z_OFFSET),
+                    ..default()
+                })
+                .insert(LocalHighlightOutline);
+        });
+    }
+    *highlighted = local;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_our_player_is_highlighted() {
+        let mut world = World::new();
+        let ours = world.spawn().id();
+        let theirs = world.spawn().id();
+        let mut lobby = Lobby::default();
+        lobby.players.insert(Player { id: 1 }, ours);
+        lobby.players.insert(Player { id: 2 }, theirs);
+
+        let mut local_player = LocalPlayer::default();
+        assert_eq!(local_entity(&local_player, &lobby), None);
+        local_player.player = Some(Player { id: 1 });
+        assert_eq!(local_entity(&local_player, &lobby), Some(ours));
+    }
+}
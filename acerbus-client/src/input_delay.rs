@@ -0,0 +1,58 @@
+use std::collections::VecDeque;
+
+use acerbus_common::PlayerInput;
+
+/// The inputs we sent, tagged with the tick the server applies them at, which our player
+/// moves with only once this tick comes, like on the server.
+#[derive(Debug, Default)]
+pub struct DelayedInputs {
+    /// How many ticks ahead the inputs are applied, as told by the server.
+    pub delay: u64,
+    pending: VecDeque<(u64, PlayerInput)>,
+    current: PlayerInput,
+}
+
+impl DelayedInputs {
+    pub fn new(delay: u64) -> DelayedInputs {
+        DelayedInputs { delay, ..DelayedInputs::default() }
+    }
+
+    /// Returns the tick to tag an input sent at the tick `tick` with and remembers it.
+    pub fn push(&mut self, tick: u64, input: PlayerInput) -> u64 {
+        let target = tick + self.delay;
+        self.pending.push_back((target, input));
+        target
+    }
+
+    /// Returns the input to move our player with at the tick `tick`,
+    /// the last one whose tick came.
+    pub fn input_at(&mut self, tick: u64) -> PlayerInput {
+        while let Some((target, input)) = self.pending.front().copied() {
+            if target > tick {
+                break;
+            }
+            self.current = input;
+            self.pending.pop_front();
+        }
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_are_applied_after_the_delay() {
+        let up = PlayerInput { up: true, ..PlayerInput::default() };
+        let down = PlayerInput { down: true, ..PlayerInput::default() };
+        let mut inputs = DelayedInputs::new(3);
+        assert_eq!(inputs.push(10, up), 13);
+        assert_eq!(inputs.push(11, down), 14);
+
+        assert_eq!(inputs.input_at(12), PlayerInput::default());
+        assert_eq!(inputs.input_at(13), up);
+        assert_eq!(inputs.input_at(20), down);
+        assert_eq!(inputs.input_at(21), down);
+    }
+}
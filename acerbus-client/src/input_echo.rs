@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::layers::{UiLayer, HUD_Z};
+use crate::GameAssets;
+
+/// How many latencies the graph shows, the oldest ones scroll out on the left.
+pub const INPUT_ECHO_SAMPLES: usize = 120;
+
+/// The latency drawn at the top of the graph, in ticks, the slower inputs are cut.
+const GRAPH_MAX_LATENCY: u64 = 15;
+const GRAPH_HEIGHT: f32 = 60.0;
+const GRAPH_BAR_WIDTH: f32 = 2.0;
+
+/// The inputs we sent tagged with a sequence number and how many ticks the server took to
+/// apply the ones it echoed, from the tick we were at when sending them.
+#[derive(Debug, Default)]
+pub struct InputEcho {
+    next_sequence: u32,
+    in_flight: VecDeque<(u32, u64)>,
+    pub latencies: VecDeque<u64>,
+    /// The server tick the last echoed input was applied on.
+    pub last_applied_tick: Option<u64>,
+}
+
+impl InputEcho {
+    /// Returns the sequence number to tag the input sent at the client tick `tick` with.
+    /// A server that never echoes doesn't make the inputs in flight pile up.
+    pub fn tag(&mut self, tick: u64) -> u32 {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.in_flight.push_back((sequence, tick));
+        while self.in_flight.len() > INPUT_ECHO_SAMPLES {
+            self.in_flight.pop_front();
+        }
+        sequence
+    }
+
+    /// Matches an echo with the input it was sent for and returns the latency of this input,
+    /// in ticks. The inputs sent before it and never echoed were dropped by the server,
+    /// they are forgotten.
+    pub fn echoed(&mut self, sequence: u32, tick: u64) -> Option<u64> {
+        let position = self.in_flight.iter().position(|(sent, _)| *sent == sequence)?;
+        let (_, sent_at) = self.in_flight.drain(..=position).last()?;
+        let latency = tick.saturating_sub(sent_at);
+        self.latencies.push_back(latency);
+        while self.latencies.len() > INPUT_ECHO_SAMPLES {
+            self.latencies.pop_front();
+        }
+        self.last_applied_tick = Some(tick);
+        Some(latency)
+    }
+}
+
+/// An echo received from the server, sent by the system reading the server messages.
+#[derive(Debug, Clone, Copy)]
+pub struct InputApplied {
+    pub sequence: u32,
+    pub tick: u64,
+}
+
+#[derive(Component)]
+pub struct InputEchoBar(usize);
+
+#[derive(Component)]
+pub struct InputEchoText;
+
+/// Returns the height of the bar of a latency in ticks, in pixels.
+pub fn bar_height(latency: u64) -> f32 {
+    latency.min(GRAPH_MAX_LATENCY) as f32 / GRAPH_MAX_LATENCY as f32 * GRAPH_HEIGHT
+}
+
+fn bar_color(latency: u64) -> Color {
+    match latency {
+        l if l < GRAPH_MAX_LATENCY / 3 => Color::GREEN,
+        l if l < GRAPH_MAX_LATENCY * 2 / 3 => Color::YELLOW,
+        _ => Color::RED,
+    }
+}
+
+pub fn setup_input_echo_graph(mut commands: Commands, game_assets: Res<GameAssets>) {
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 14.0, color: Color::WHITE };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(10.0), bottom: Val::Px(10.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..default()
+        })
+        .insert(UiLayer(HUD_Z))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(String::new(), text_style, default()),
+                    ..default()
+                })
+                .insert(InputEchoText);
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(
+                            Val::Px(GRAPH_BAR_WIDTH * INPUT_ECHO_SAMPLES as f32),
+                            Val::Px(GRAPH_HEIGHT),
+                        ),
+                        align_items: AlignItems::FlexEnd,
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|graph| {
+                    for i in 0..INPUT_ECHO_SAMPLES {
+                        graph
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(GRAPH_BAR_WIDTH), Val::Px(0.0)),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .insert(InputEchoBar(i));
+                    }
+                });
+        });
+}
+
+/// Matches the echoes with the inputs sent and redraws the graph, the newest latency on the right.
+pub fn input_echo_system(
+    mut echo: ResMut<InputEcho>,
+    mut applied: EventReader<InputApplied>,
+    mut bars: Query<(&InputEchoBar, &mut Style, &mut UiColor)>,
+    mut texts: Query<&mut Text, With<InputEchoText>>,
+) {
+    let mut applied: Vec<_> = applied.iter().copied().collect();
+    if applied.is_empty() {
+        return;
+    }
+    applied.sort_by_key(|applied| applied.sequence);
+    for applied in applied {
+        echo.echoed(applied.sequence, applied.tick);
+    }
+
+    let offset = INPUT_ECHO_SAMPLES - echo.latencies.len();
+    for (InputEchoBar(i), mut style, mut color) in bars.iter_mut() {
+        let latency = i.checked_sub(offset).and_then(|i| echo.latencies.get(i)).copied();
+        style.size.height = Val::Px(latency.map_or(0.0, bar_height));
+        *color = latency.map_or(Color::NONE, bar_color).into();
+    }
+
+    let count = echo.latencies.len().max(1) as f64;
+    let average = echo.latencies.iter().sum::<u64>() as f64 / count;
+    let max = echo.latencies.iter().copied().max().unwrap_or(0);
+    for mut text in texts.iter_mut() {
+        let value = &mut text.sections[0].value;
+        value.clear();
+        write!(value, "input latency: {:.1} ticks avg, {} ticks max", average, max).unwrap();
+        if let Some(tick) = echo.last_applied_tick {
+            write!(value, "\nlast applied on tick {}", tick).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echoes_give_the_latency_in_ticks() {
+        let mut echo = InputEcho::default();
+        let first = echo.tag(100);
+        let second = echo.tag(101);
+        let third = echo.tag(102);
+
+        assert_eq!(echo.echoed(second, 105), Some(4));
+        // The first input was dropped by the server, its late echo is ignored.
+        assert_eq!(echo.echoed(first, 106), None);
+        assert_eq!(echo.echoed(third, 104), Some(2));
+        assert_eq!(echo.latencies, [4, 2]);
+        assert_eq!(echo.last_applied_tick, Some(104));
+    }
+}
@@ -0,0 +1,55 @@
+use acerbus_common::PlayerInput;
+
+/// Whether the input is only sent when it changes, the server keeps applying the last one
+/// it received. It is still sent every `keepalive` seconds to replace a lost one.
+#[derive(Debug, Clone, Copy)]
+pub struct InputSendPolicy {
+    pub on_change_only: bool,
+    pub keepalive: f64,
+}
+
+/// Returns whether to send the input now given the last one sent and when it was sent.
+pub fn should_send_input(
+    policy: InputSendPolicy,
+    last_sent: Option<(PlayerInput, f64)>,
+    input: PlayerInput,
+    now: f64,
+) -> bool {
+    match last_sent {
+        _ if !policy.on_change_only => true,
+        Some((last_input, sent_at)) => last_input != input || now - sent_at >= policy.keepalive,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ON_CHANGE: InputSendPolicy = InputSendPolicy { on_change_only: true, keepalive: 1.0 };
+
+    fn moving_up() -> PlayerInput {
+        PlayerInput { up: true, ..PlayerInput::default() }
+    }
+
+    #[test]
+    fn the_input_is_sent_when_it_changes() {
+        let still = PlayerInput::default();
+        assert!(should_send_input(ON_CHANGE, None, still, 0.0));
+        assert!(!should_send_input(ON_CHANGE, Some((still, 0.0)), still, 0.5));
+        assert!(should_send_input(ON_CHANGE, Some((still, 0.0)), moving_up(), 0.5));
+    }
+
+    #[test]
+    fn the_same_input_is_sent_again_after_the_keepalive() {
+        let last_sent = Some((moving_up(), 2.0));
+        assert!(!should_send_input(ON_CHANGE, last_sent, moving_up(), 2.9));
+        assert!(should_send_input(ON_CHANGE, last_sent, moving_up(), 3.0));
+    }
+
+    #[test]
+    fn every_input_is_sent_by_default() {
+        let policy = InputSendPolicy { on_change_only: false, ..ON_CHANGE };
+        assert!(should_send_input(policy, Some((moving_up(), 2.0)), moving_up(), 2.0));
+    }
+}
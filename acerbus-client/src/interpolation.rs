@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+
+use acerbus_common::InterpolationParams;
+use bevy::prelude::*;
+
+use crate::layers::PLAYER_Z;
+
+/// How fast the jitter estimations follow the new arrival intervals.
+const JITTER_SMOOTHING: f64 = 1.0 / 16.0;
+/// The number of jitters added to the mean interval to get the interpolation delay.
+const JITTER_MULTIPLIER: f64 = 2.0;
+const MAX_SNAPSHOTS: usize = 32;
+
+/// Whether the remote players are interpolated or snapped to the last positions received.
+pub struct InterpolationEnabled(pub bool);
+
+/// The bounds, in seconds, of the delay with which the remote players are rendered.
+pub struct InterpolationDelay {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl InterpolationDelay {
+    /// The bounds advertised by the server, unless the player chose others.
+    pub fn resolve(
+        advertised: InterpolationParams,
+        overrides: &InterpolationOverrides,
+    ) -> InterpolationDelay {
+        InterpolationDelay {
+            min: overrides.min.unwrap_or(advertised.min_delay),
+            max: overrides.max.unwrap_or(advertised.max_delay),
+        }
+    }
+}
+
+/// The bounds given on the command line, they win over the ones advertised by the server.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InterpolationOverrides {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// The interpolation recommended by the server in its `InitState`.
+pub struct AdvertisedInterpolation(pub InterpolationParams);
+
+pub fn adopt_advertised_interpolation_system(
+    advertised: Option<Res<AdvertisedInterpolation>>,
+    overrides: Res<InterpolationOverrides>,
+    mut bounds: ResMut<InterpolationDelay>,
+) {
+    if let Some(advertised) = advertised.filter(|advertised| advertised.is_changed()) {
+        *bounds = InterpolationDelay::resolve(advertised.0, &overrides);
+    }
+}
+
+/// Estimates the interval between the snapshots of a player and the variation of this interval.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JitterEstimator {
+    last_arrival: Option<f64>,
+    pub mean_interval: f64,
+    pub jitter: f64,
+}
+
+impl JitterEstimator {
+    pub fn record(&mut self, arrival: f64) {
+        if let Some(last_arrival) = self.last_arrival {
+            let interval = arrival - last_arrival;
+            if self.mean_interval == 0.0 {
+                self.mean_interval = interval;
+            } else {
+                self.mean_interval += (interval - self.mean_interval) * JITTER_SMOOTHING;
+            }
+            let deviation = (interval - self.mean_interval).abs();
+            self.jitter += (deviation - self.jitter) * JITTER_SMOOTHING;
+        }
+        self.last_arrival = Some(arrival);
+    }
+
+    /// The delay that gives enough time for the next snapshot to arrive.
+    pub fn delay(&self, bounds: &InterpolationDelay) -> f64 {
+        (self.mean_interval + JITTER_MULTIPLIER * self.jitter).clamp(bounds.min, bounds.max)
+    }
+}
+
+/// The last positions received for a remote player, it is rendered in the past
+/// in between two of them, with a delay adapted to the jitter of its snapshots.
+#[derive(Debug, Default, Component)]
+pub struct Interpolated {
+    snapshots: VecDeque<(f64, Vec2)>,
+    pub jitter: JitterEstimator,
+    /// The number of teleports of the player, as last sent by the server.
+    teleports: u8,
+}
+
+impl Interpolated {
+    /// Forgets the previous snapshots if the player was teleported since the last one,
+    /// it jumps to its new position instead of sliding across the arena.
+    pub fn snap_if_teleported(&mut self, teleports: u8) {
+        if self.teleports != teleports {
+            self.teleports = teleports;
+            self.snapshots.clear();
+        }
+    }
+
+    pub fn push(&mut self, arrival: f64, position: Vec2) {
+        self.jitter.record(arrival);
+        self.snapshots.push_back((arrival, position));
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Records that the player is still at its last position.
+    pub fn hold(&mut self, arrival: f64) {
+        if let Some((_, position)) = self.snapshots.back().copied() {
+            self.push(arrival, position);
+        }
+    }
+
+    /// Returns the position at `time`, clamped to the known snapshots.
+    pub fn sample(&self, time: f64) -> Option<Vec2> {
+        let (first, last) = (self.snapshots.front()?, self.snapshots.back()?);
+        if time <= first.0 {
+            return Some(first.1);
+        }
+
+        let mut pairs = self.snapshots.iter().zip(self.snapshots.iter().skip(1));
+        match pairs.find(|(_, (b_time, _))| time <= *b_time) {
+            Some(((a_time, a), (b_time, b))) => {
+                let t = ((time - a_time) / (b_time - a_time)) as f32;
+                Some(a.lerp(*b, t))
+            }
+            None => Some(last.1),
+        }
+    }
+
+    /// Forgets the snapshots that are no more needed to sample after `time`.
+    pub fn prune(&mut self, time: f64) {
+        while self.snapshots.get(1).map_or(false, |(arrival, _)| *arrival <= time) {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+pub fn interpolate_players_system(
+    time: Res<Time>,
+    bounds: Res<InterpolationDelay>,
+    mut query: Query<(&mut Interpolated, &mut Transform)>,
+) {
+    let now = time.seconds_since_startup();
+    for (mut interpolated, mut transform) in query.iter_mut() {
+        let render_time = now - interpolated.jitter.delay(&bounds);
+        if let Some(position) = interpolated.sample(render_time) {
+            transform.translation = position.extend(PLAYER_Z);
+        }
+        interpolated.prune(render_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_players_stay_in_place() {
+        let mut interpolated = Interpolated::default();
+        interpolated.hold(0.5);
+        assert_eq!(interpolated.sample(0.5), None);
+
+        interpolated.push(1.0, Vec2::ZERO);
+        interpolated.push(2.0, Vec2::new(10., 0.));
+        interpolated.hold(3.0);
+        assert_eq!(interpolated.sample(1.5), Some(Vec2::new(5., 0.)));
+        assert_eq!(interpolated.sample(2.5), Some(Vec2::new(10., 0.)));
+        // The keepalive counts as a snapshot for the jitter estimation.
+        assert_eq!(interpolated.jitter.mean_interval, 1.0);
+    }
+
+    #[test]
+    fn the_player_flags_win_over_the_advertised_delays() {
+        let advertised = InterpolationParams { min_delay: 0.1, max_delay: 0.5 };
+        let delay = InterpolationDelay::resolve(advertised, &InterpolationOverrides::default());
+        assert_eq!((delay.min, delay.max), (0.1, 0.5));
+
+        let overrides = InterpolationOverrides { min: None, max: Some(0.2) };
+        let delay = InterpolationDelay::resolve(advertised, &overrides);
+        assert_eq!((delay.min, delay.max), (0.1, 0.2));
+    }
+
+    #[test]
+    fn the_advertised_delays_are_adopted_once_received() {
+        let mut app = App::new();
+        let overrides = InterpolationOverrides { min: Some(0.05), max: None };
+        app.insert_resource(InterpolationDelay::resolve(default(), &overrides));
+        app.insert_resource(overrides);
+        app.add_system(adopt_advertised_interpolation_system);
+        app.update();
+        let delay = app.world.resource::<InterpolationDelay>();
+        assert_eq!((delay.min, delay.max), (0.05, 0.25));
+
+        let advertised = InterpolationParams { min_delay: 0.1, max_delay: 0.4 };
+        app.insert_resource(AdvertisedInterpolation(advertised));
+        app.update();
+        let delay = app.world.resource::<InterpolationDelay>();
+        assert_eq!((delay.min, delay.max), (0.05, 0.4));
+    }
+
+    #[test]
+    fn interpolates_between_snapshots() {
+        let mut interpolated = Interpolated::default();
+        interpolated.push(0., Vec2::ZERO);
+        interpolated.push(1., Vec2::new(10., 0.));
+        assert_eq!(interpolated.sample(0.5), Some(Vec2::new(5., 0.)));
+    }
+
+    #[test]
+    fn teleports_are_not_interpolated() {
+        let mut interpolated = Interpolated::default();
+        interpolated.push(0., Vec2::ZERO);
+        interpolated.snap_if_teleported(1);
+        interpolated.push(1., Vec2::new(100., 0.));
+        assert_eq!(interpolated.sample(0.5), Some(Vec2::new(100., 0.)));
+
+        // The same count sent again is not another teleport.
+        interpolated.snap_if_teleported(1);
+        interpolated.push(2., Vec2::new(110., 0.));
+        assert_eq!(interpolated.sample(1.5), Some(Vec2::new(105., 0.)));
+    }
+}
@@ -0,0 +1,73 @@
+//! The depth at which everything is drawn in the world, from the back to the front.
+//! The UI (panels, HUD and menus) is drawn by its own camera, above the whole world,
+//! and stacked in layers.
+
+use bevy::prelude::*;
+
+pub const GRID_Z: f32 = 0.0;
+pub const GOAL_Z: f32 = 0.4;
+pub const TELEPORT_PAD_Z: f32 = 0.5;
+/// The outlines drawn around some players, behind their cube.
+pub const OUTLINE_Z: f32 = 0.9;
+/// The highlight of our own player, above the other outlines so that they never mix.
+pub const HIGHLIGHT_Z: f32 = 0.95;
+pub const PLAYER_Z: f32 = 1.0;
+/// The bars and texts drawn above the players, they must never be hidden by a cube.
+pub const LABEL_Z: f32 = 2.0;
+
+/// The depth of a label relative to the player it is a child of.
+pub const LABEL_Z_OFFSET: f32 = LABEL_Z - PLAYER_Z;
+/// The depth of an outline relative to the player it is a child of.
+pub const OUTLINE_Z_OFFSET: f32 = OUTLINE_Z - PLAYER_Z;
+/// The depth of the highlight relative to our player, it is a child of.
+pub const HIGHLIGHT_Z_OFFSET: f32 = HIGHLIGHT_Z - PLAYER_Z;
+
+/// The depth added to the UI of the HUD, the texts always shown during the game.
+pub const HUD_Z: f32 = 0.0;
+/// The depth added to the UI of the menus and panels, they open above the HUD.
+pub const MENU_Z: f32 = 100.0;
+
+/// Stacks a UI tree at this depth, Bevy gives the UI trees depths in no particular order.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct UiLayer(pub f32);
+
+/// Moves the UI trees in their layer, once Bevy computed the depth of their nodes.
+pub fn ui_layers_system(
+    layers: Query<(Entity, &UiLayer)>,
+    transforms: Query<&Transform>,
+    children: Query<&Children>,
+    mut global_transforms: Query<&mut GlobalTransform>,
+) {
+    fn set_depth(
+        entity: Entity,
+        depth: f32,
+        transforms: &Query<&Transform>,
+        children: &Query<&Children>,
+        global_transforms: &mut Query<&mut GlobalTransform>,
+    ) {
+        if let Ok(mut global_transform) = global_transforms.get_mut(entity) {
+            global_transform.translation.z = depth;
+        }
+        for child in children.get(entity).into_iter().flat_map(|children| children.iter()) {
+            let child_z = transforms.get(*child).map_or(0., |t| t.translation.z);
+            set_depth(*child, depth + child_z, transforms, children, global_transforms);
+        }
+    }
+
+    for (entity, UiLayer(layer)) in layers.iter() {
+        let z = transforms.get(entity).map_or(0., |t| t.translation.z);
+        set_depth(entity, layer + z, &transforms, &children, &mut global_transforms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_are_ordered() {
+        let world = [GRID_Z, GOAL_Z, TELEPORT_PAD_Z, OUTLINE_Z, HIGHLIGHT_Z, PLAYER_Z, LABEL_Z];
+        assert!(world.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(HUD_Z < MENU_Z);
+    }
+}
@@ -1,49 +1,645 @@
-use std::net::{SocketAddr, UdpSocket};
-use std::time::{Duration, SystemTime};
+use std::env;
+use std::fs;
+use std::iter;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 
 use acerbus_common::*;
 use bevy::app::AppExit;
+use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
 use bevy::ecs::schedule::ShouldRun;
-use bevy::prelude::shape::Quad;
+use bevy::prelude::shape::{Circle, Quad};
 use bevy::prelude::*;
 use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::window::WindowFocused;
 use bevy_asset_loader::{AssetCollection, AssetCollectionApp};
-use bevy_renet::renet::{ClientAuthentication, RenetClient, RenetConnectionConfig};
+use bevy_renet::renet::{ClientAuthentication, RenetClient};
 use bevy_renet::{run_if_client_conected, RenetClientPlugin};
+use bot_tag::{bot_color, spawn_bot_tag};
+use browser::{
+    browser_input_system, browser_panel_system, probe_servers_system, ServerBrowser, ServerProber,
+};
+use camera_bounds::{clamp_to_arena, visible_half_size, ClampCameraToArena};
+use camera_lead::{camera_lead_system, CameraLead};
+use chat::{chat_scroll_system, chat_text_system, setup_chat, ChatHistory};
 use clap::Parser;
+use collisions::ClientCollisions;
+use connecting::{
+    connection_lost_system, connection_status_system, disconnect_reason_system, run_if_in_game,
+    unreachable_input_system, ConnectParams, ConnectionStatus, DisconnectReason, LocalPlayer,
+};
+use controls::{controls_menu_system, AxisMirror, ControlScheme, DirectionKeys, KeyBindings};
+use coordinates::CoordinateOrigin;
+use culling::cull_player_overlays_system;
+use debug_hud::{
+    debug_hud_system, setup_debug_hud, toggle_debug_hud_system, DebugHud, ServerTickRate,
+};
+use despawn_grace::{
+    despawn_disconnected_players_system, set_disconnected_look, DespawnGrace, PendingDespawns,
+};
+use fallback::asset_fallback_system;
+use fixed_step::{ClientTick, FixedStepClock};
+use frame_pacing::{frame_pacing_system, FramePacing};
+use free_camera::{
+    free_camera_system, toggle_camera_mode_system, CameraMode, FreezePlayerInFreeCamera,
+};
+use freeze::{freeze_when_hidden_system, run_if_playing, run_unless_frozen, FreezeWhenHidden};
+use gamepad::{gamepad_connection_system, gamepad_input, ActiveGamepad};
+use grid::{spawn_grid_system, GridSpacing};
+use highlight::{local_highlight_system, HexColor, LocalHighlight};
+use input_delay::DelayedInputs;
+use input_echo::{input_echo_system, setup_input_echo_graph, InputApplied, InputEcho};
+use input_send::{should_send_input, InputSendPolicy};
+use interpolation::{
+    adopt_advertised_interpolation_system, interpolate_players_system, AdvertisedInterpolation,
+    Interpolated, InterpolationDelay, InterpolationEnabled, InterpolationOverrides,
+};
+use layers::{ui_layers_system, PLAYER_Z};
+use notification::{expire_notifications_system, spawn_announcement};
+use objects::{sync_objects_system, SyncedObjects};
+use protection::{protection_outline_system, spawn_protection_outline};
+use quality::{quality_bars_system, spawn_quality_bars};
+use ready::{ready_button_system, ready_panel_system, ready_text_system, ReadyCheck};
+use reconnect::{auto_reconnect_system, ReconnectState};
+#[cfg(feature = "record-frames")]
+use record::FrameRecordPlugin;
+use resize::{resize_players_system, SizeTransition};
+use scores::{leaderboard_system, Score};
+use serde::Serialize;
+use settings::{ClientSettings, SettingsFile};
+use shake::{detect_impacts_system, CameraShake};
+use smoothing::{smooth_correction_system, CorrectionOffset, CorrectionSmoothing, LogicalPosition};
+use soccer::{draw_goals_system, scoreboard_system, TeamScores};
+use teleport_pads::{draw_teleport_pads_system, TeleportPads};
+use trusted::trusted_movement_system;
+use tutorial::{dismiss_tutorial_system, setup_tutorial, Tutorial};
+use view_radius::{send_view_radius_system, ViewRadius};
+use watch::watch_replay;
+use window_title::window_title_system;
 
-#[derive(Parser)]
+mod bot_tag;
+mod browser;
+mod camera_bounds;
+mod camera_lead;
+mod chat;
+mod collisions;
+mod connecting;
+mod controls;
+mod coordinates;
+mod culling;
+mod debug_hud;
+mod despawn_grace;
+mod fallback;
+mod fixed_step;
+mod frame_pacing;
+mod free_camera;
+mod freeze;
+mod gamepad;
+mod grid;
+mod highlight;
+mod input_delay;
+mod input_echo;
+mod input_send;
+mod interpolation;
+mod layers;
+mod notification;
+mod objects;
+mod protection;
+mod quality;
+mod ready;
+mod reconnect;
+#[cfg(feature = "record-frames")]
+mod record;
+mod resize;
+mod scores;
+mod settings;
+mod shake;
+mod smoothing;
+mod soccer;
+mod teleport_pads;
+mod trusted;
+mod tutorial;
+mod view_radius;
+mod watch;
+mod window_title;
+
+/// How long to wait for the coordinator to tell us which server to join.
+const COORDINATOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Parser, Serialize)]
+#[clap(args_override_self = true)]
 struct Opt {
+    /// Read the options from this JSON file, like `{ "name": "Kero", "skin": "red" }`,
+    /// the flags given on the command line win over it.
+    #[clap(long)]
+    config: Option<PathBuf>,
+
     #[clap(long, default_value = "127.0.0.1:5000")]
     server_addr: SocketAddr,
+
+    /// Ask this coordinator which server to join instead of using `--server-addr`.
+    #[clap(long)]
+    coordinator: Option<SocketAddr>,
+
+    /// Add this server to the server browser (F4) and open it on startup, can be repeated.
+    #[clap(long)]
+    browse: Vec<SocketAddr>,
+
+    /// How long, in seconds, to wait for the server to answer before giving up.
+    #[clap(long, default_value = "10", value_parser = parse_positive)]
+    connect_timeout: f64,
+
+    /// Connect again to the server once the connection is lost, instead of exiting,
+    /// waiting longer and longer between the attempts.
+    #[clap(long)]
+    auto_reconnect: bool,
+
+    /// Give up reconnecting after this many attempts, there is no limit by default.
+    /// The player can still retry by hand. Implies `--auto-reconnect`.
+    #[clap(long)]
+    max_reconnect_attempts: Option<u32>,
+
+    /// The token to present to the server, reuse it to get your cube back after a disconnection.
+    #[clap(long)]
+    session_token: Option<u64>,
+
+    /// The team we would like to join, the server may put us in another one to keep them even.
+    #[clap(long)]
+    team: Option<u8>,
+
+    /// Draw our cube with a texture, `green` or `purple`, instead of the color of our team.
+    #[clap(long)]
+    skin: Option<Skin>,
+
+    /// The color of the outline drawn around our own player, in hexadecimal.
+    #[clap(long, default_value = "ffd700")]
+    highlight_color: HexColor,
+
+    /// Don't outline our own player.
+    #[clap(long)]
+    no_local_highlight: bool,
+
+    /// The name we are shown with in the chat, cut after 16 characters.
+    /// The server may refuse it and name us after our id instead.
+    #[clap(long)]
+    name: Option<String>,
+
+    /// Draw a grid under the players, sized to the arena.
+    #[clap(long)]
+    grid: bool,
+
+    #[clap(long, default_value = "50")]
+    grid_spacing: f32,
+
+    /// Stop moving the player while the free camera (F2) is used.
+    #[clap(long)]
+    freeze_in_free_camera: bool,
+
+    /// How far, in pixels, the camera looks ahead of our player in the direction it goes.
+    #[clap(long, default_value = "40")]
+    camera_lead: f32,
+
+    /// How far, in pixels, the camera is shaken when our player collides, 0 to disable.
+    #[clap(long, default_value = "6")]
+    shake_intensity: f32,
+
+    /// How long, in seconds, the camera shakes after a collision.
+    #[clap(long, default_value = "0.3")]
+    shake_duration: f32,
+
+    /// Let the camera follow the player beyond the edges of the arena.
+    #[clap(long)]
+    no_camera_clamp: bool,
+
+    /// Over how many frames the corrections of our position are smoothed, 0 to snap.
+    #[clap(long, default_value = "6")]
+    correction_frames: u32,
+
+    /// Snap our player to the position the server corrects it to, without smoothing,
+    /// the same as `--correction-frames 0`.
+    #[clap(long)]
+    raw_correction: bool,
+
+    /// Ask the server to send us the players within this distance of ours, when it only sends
+    /// the nearby players. It may send less than asked.
+    #[clap(long)]
+    view_radius: Option<f32>,
+
+    /// Tag the inputs sent so that the server echoes the tick it applied them on,
+    /// and draw a graph of how many ticks each input took to be applied.
+    #[clap(long)]
+    input_echo: bool,
+
+    /// The size above which the messages sent are reported as too big, in debug builds.
+    #[clap(long, default_value_t = DEFAULT_SAFE_MESSAGE_BYTES)]
+    safe_message_bytes: usize,
+
+    /// The minimum delay, in seconds, with which the other players are rendered,
+    /// instead of the one advertised by the server.
+    #[clap(long)]
+    interp_min_delay: Option<f64>,
+
+    /// The maximum delay, in seconds, with which the other players are rendered,
+    /// instead of the one advertised by the server.
+    #[clap(long)]
+    interp_max_delay: Option<f64>,
+
+    /// Only send our input when it changes, and regularly to replace a lost one, instead of
+    /// every frame. This saves bandwidth when idle.
+    #[clap(long)]
+    send_input_on_change: bool,
+
+    /// How often, in seconds, the unchanged input is sent again with `--send-input-on-change`.
+    #[clap(long, default_value = "0.25")]
+    input_keepalive: f64,
+
+    /// Render the other players at the last positions received, without delay, for LAN play.
+    #[clap(long)]
+    no_interp: bool,
+
+    /// Limit the number of frames rendered per second, to save power.
+    #[clap(long, value_parser = parse_positive)]
+    fps_cap: Option<f64>,
+
+    /// Render only a few frames per second and pause the players while the window is
+    /// minimized, to save battery, we stay connected.
+    #[clap(long)]
+    freeze_when_hidden: bool,
+
+    /// How long, in seconds, the cube of a disconnected player stays dimmed before disappearing.
+    #[clap(long, default_value = "1")]
+    despawn_grace: f64,
+
+    /// Don't stop our player at the walls when we move it ourselves, for servers without walls.
+    #[clap(long)]
+    no_client_collisions: bool,
+
+    /// Render our player at its last simulated position instead of between the last two,
+    /// when the server trusts us to move it.
+    #[clap(long)]
+    no_render_interpolation: bool,
+
+    /// Where the settings remembered from one run to the other are stored.
+    #[clap(long, default_value = "acerbus-client.settings")]
+    settings: PathBuf,
+
+    /// The keys that move our player: wasd (with the arrows), arrows, ijkl or custom.
+    /// They can also be changed in game with F5.
+    #[clap(long)]
+    controls: Option<ControlScheme>,
+
+    /// The custom keys moving our player up, down, left and right, like `W,S,A,D`.
+    #[clap(long)]
+    keys: Option<DirectionKeys>,
+
+    /// Invert left and right.
+    #[clap(long)]
+    mirror_x: bool,
+
+    /// Invert up and down.
+    #[clap(long)]
+    mirror_y: bool,
+
+    /// Move vertically with the horizontal keys and horizontally with the vertical ones.
+    #[clap(long)]
+    swap_axes: bool,
+
+    /// Show the tutorial even if it has already been dismissed.
+    #[clap(long)]
+    tutorial: bool,
+
+    /// Write the rendered frames as numbered PNG images into this directory, the recording
+    /// is started and stopped with F9. The frames have the initial size of the window.
+    #[cfg(feature = "record-frames")]
+    #[clap(long)]
+    record_frames: Option<PathBuf>,
+
+    /// Watch a replay recorded by the server with `--record-replay`, without connecting.
+    #[clap(long)]
+    watch_replay: Option<PathBuf>,
+
+    /// Print the configuration resolved from the flags, the config file, the settings and
+    /// the defaults on startup, as JSON.
+    #[clap(long)]
+    print_config: bool,
+
+    /// Exit once the configuration is read, use it with `--print-config` to check it.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Show the coordinates from the bottom-left corner of the arena instead of its center.
+    #[clap(long)]
+    corner_origin: bool,
+
+    /// How many lines of chat are kept, scroll back through them with Page Up and Page Down.
+    #[clap(long, default_value = "100")]
+    chat_history: usize,
+
+    /// Append the chat of the session to this file.
+    #[clap(long)]
+    chat_log: Option<PathBuf>,
+}
+
+/// Parses a finite number greater than zero, a duration or a rate.
+fn parse_positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0. => Ok(value),
+        Ok(_) => Err("must be a finite number greater than zero".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Everything the client starts with, the options, the settings and the values that can't be
+/// changed. The interpolation delays are the defaults until the server advertises its own.
+#[derive(Debug, Serialize)]
+struct Config<'a> {
+    protocol_id: u64,
+    channels: [(&'static str, u8); 4],
+    show_tutorial: bool,
+    interpolation_delay: (f64, f64),
+    settings: &'a ClientSettings,
+    options: &'a Opt,
+}
+
+impl<'a> Config<'a> {
+    fn resolve(opt: &'a Opt, settings: &'a ClientSettings) -> Config<'a> {
+        let overrides =
+            InterpolationOverrides { min: opt.interp_min_delay, max: opt.interp_max_delay };
+        let delay = InterpolationDelay::resolve(InterpolationParams::default(), &overrides);
+        Config {
+            protocol_id: PROTOCOL_ID,
+            channels: CHANNELS,
+            show_tutorial: show_tutorial(opt, settings),
+            interpolation_delay: (delay.min, delay.max),
+            settings,
+            options: opt,
+        }
+    }
+}
+
+/// The flags win over the controls chosen in the menu, which are remembered in the settings.
+fn key_bindings(opt: &Opt, settings: &ClientSettings) -> KeyBindings {
+    let custom = opt.keys.or(settings.custom_keys);
+    let scheme = match opt.controls {
+        Some(scheme) => scheme,
+        None if opt.keys.is_some() => ControlScheme::Custom,
+        None => settings.controls,
+    };
+    let mirror = AxisMirror {
+        x: opt.mirror_x || settings.mirror.x,
+        y: opt.mirror_y || settings.mirror.y,
+        swap: opt.swap_axes || settings.mirror.swap,
+    };
+    KeyBindings { scheme, custom, mirror }
+}
+
+/// The `--tutorial` flag wins over the settings, which remember whether it was already seen.
+fn show_tutorial(opt: &Opt, settings: &ClientSettings) -> bool {
+    opt.tutorial || !settings.tutorial_seen
+}
+
+/// The `--raw-correction` flag disables the smoothing, whatever `--correction-frames` says.
+fn correction_frames(opt: &Opt) -> u32 {
+    if opt.raw_correction {
+        0
+    } else {
+        opt.correction_frames
+    }
+}
+
+/// Parses the flags, the ones of the `--config` file are parsed first for the command line
+/// to override them.
+fn parse_opt(args: Vec<String>) -> Opt {
+    let opt = Opt::parse_from(&args);
+    let path = match &opt.config {
+        Some(path) => path,
+        None => return opt,
+    };
+    let content = fs::read_to_string(path).expect("failed to read the config file");
+    let file_args = config_file_args(&content).expect("invalid config file");
+    let (program, flags) = args.split_first().unwrap();
+    Opt::parse_from(iter::once(program).chain(&file_args).chain(flags))
 }
 
 fn main() {
-    let opt = Opt::parse();
+    let opt = parse_opt(env::args().collect());
+    let settings = ClientSettings::load(&opt.settings);
+
+    if opt.print_config {
+        let config = Config::resolve(&opt, &settings);
+        println!("{}", serde_json::to_string_pretty(&config).unwrap());
+    }
+    if opt.dry_run {
+        return;
+    }
+    if let Some(path) = &opt.watch_replay {
+        watch_replay(path);
+        return;
+    }
+    set_safe_message_bytes(opt.safe_message_bytes);
+
+    let server_addr = match opt.coordinator {
+        Some(coordinator) => match find_server(coordinator, COORDINATOR_TIMEOUT) {
+            Ok(Some(server_addr)) => server_addr,
+            Ok(None) => {
+                eprintln!("The coordinator {} has no server to join.", coordinator);
+                return;
+            }
+            Err(e) => {
+                eprintln!("Failed to ask the coordinator {} for a server: {}", coordinator, e);
+                return;
+            }
+        },
+        None => opt.server_addr,
+    };
+    println!("Joining the server {}.", server_addr);
 
     let mut app = App::new();
     app.add_plugins(DefaultPlugins);
     app.init_collection::<GameAssets>();
+    app.add_system(asset_fallback_system);
     app.insert_resource(Lobby::default());
+    app.insert_resource(WorldSyncAssembler::default());
 
     app.add_plugin(RenetClientPlugin);
-    app.insert_resource(new_renet_client(opt.server_addr));
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+    let session_token = SessionToken(opt.session_token.unwrap_or(current_time.as_nanos() as u64));
+    println!("Using the session token {}.", session_token.0);
+    let request = ConnectRequest {
+        session_token: Some(session_token),
+        team: opt.team.map(Team),
+        skin: opt.skin,
+        name: opt.name.as_ref().map(|name| name.chars().take(MAX_NAME_CHARS).collect()),
+        resume: false,
+    };
+    let user_data = request.to_user_data().unwrap();
+    let params = ConnectParams {
+        server_addr,
+        user_data,
+        timeout: Duration::from_secs_f64(opt.connect_timeout),
+    };
+    app.insert_resource(new_renet_client(&params));
+    app.insert_resource(params);
+    app.insert_resource(ConnectionStatus::Connecting { since: Instant::now() });
+    app.insert_resource(LocalPlayer::default());
+    app.add_system(connection_status_system.label("connection_status"));
+    app.add_system(window_title_system);
+    app.insert_resource(DisconnectReason::default());
+    app.add_system(disconnect_reason_system);
+    if opt.auto_reconnect || opt.max_reconnect_attempts.is_some() {
+        app.insert_resource(ReconnectState::new(opt.max_reconnect_attempts));
+        app.add_system(auto_reconnect_system.after("connection_status").after("player_input"));
+    } else {
+        app.add_system(connection_lost_system.after("player_input"));
+    }
+    app.add_system(unreachable_input_system);
+    let servers = std::iter::once(server_addr).chain(opt.browse.iter().copied());
+    app.insert_resource(ServerBrowser::new(servers, !opt.browse.is_empty()));
+    app.insert_resource(ServerProber::default());
+    app.add_system(probe_servers_system);
+    app.add_system(browser_input_system);
+    app.add_system(browser_panel_system);
     app.insert_resource(PlayerInput::default());
-    app.add_system(player_input);
+    app.insert_resource(ActiveGamepad::default());
+    app.add_system(gamepad_connection_system.before("player_input"));
+    app.add_system(player_input.label("player_input"));
+    app.add_system(expire_notifications_system);
+
+    app.insert_resource(CameraMode::default());
+    app.insert_resource(FreezePlayerInFreeCamera(opt.freeze_in_free_camera));
+    app.insert_resource(ClampCameraToArena(!opt.no_camera_clamp));
+    app.add_system(toggle_camera_mode_system);
+    app.add_system(free_camera_system);
+    app.insert_resource(CameraShake::new(opt.shake_intensity, opt.shake_duration));
+    app.add_system(
+        detect_impacts_system.with_run_criteria(run_unless_frozen).before("camera_follow_player"),
+    );
+    app.insert_resource(CameraLead::new(opt.camera_lead));
+    app.add_system(
+        camera_lead_system.with_run_criteria(run_unless_frozen).before("camera_follow_player"),
+    );
     app.add_system(
         camera_follow_player
-            .with_run_criteria(run_if_client_conected)
-            .with_run_criteria(run_if_player_exist),
+            .label("camera_follow_player")
+            .with_run_criteria(run_if_playing.chain(run_if_player_exist)),
     );
+    app.insert_resource(InputSendPolicy {
+        on_change_only: opt.send_input_on_change,
+        keepalive: opt.input_keepalive,
+    });
     app.add_system(client_send_input.with_run_criteria(run_if_client_conected));
     app.add_system(client_sync_players.with_run_criteria(run_if_client_conected));
+    app.insert_resource(DespawnGrace(opt.despawn_grace));
+    app.insert_resource(PendingDespawns::default());
+    app.add_system(despawn_disconnected_players_system);
+    app.insert_resource(CorrectionSmoothing { frames: correction_frames(&opt) });
+    app.add_event::<InputApplied>();
+    if opt.input_echo {
+        app.insert_resource(InputEcho::default());
+        app.add_startup_system(setup_input_echo_graph);
+        app.add_system(input_echo_system);
+    }
+    if let Some(view_radius) = opt.view_radius {
+        app.insert_resource(ViewRadius(view_radius));
+        app.add_system(send_view_radius_system);
+    }
+    // The heavy systems, rendering and moving the players, are paused while minimized.
+    app.add_system(smooth_correction_system.with_run_criteria(run_unless_frozen));
+    app.add_system(cull_player_overlays_system.with_run_criteria(run_unless_frozen));
+    app.add_system(resize_players_system.with_run_criteria(run_unless_frozen));
+    app.add_system(protection_outline_system.with_run_criteria(run_unless_frozen));
+    if !opt.no_local_highlight {
+        app.insert_resource(LocalHighlight { color: opt.highlight_color.0 });
+        app.add_system(local_highlight_system);
+    }
+    app.insert_resource(ClientCollisions(!opt.no_client_collisions));
+    app.insert_resource(FixedStepClock::new(!opt.no_render_interpolation));
+    app.add_system(trusted_movement_system.with_run_criteria(run_if_playing).after("player_input"));
+    app.insert_resource(InterpolationEnabled(!opt.no_interp));
+    app.insert_resource(SyncedObjects::default());
+    app.add_system(sync_objects_system);
+    app.add_system(draw_goals_system);
+    app.add_system(scoreboard_system);
+    // The depths of the UI are computed and propagated in the post update stage.
+    app.add_system_to_stage(CoreStage::Last, ui_layers_system);
+    app.add_system(leaderboard_system);
+    app.add_system(draw_teleport_pads_system);
+    let overrides = InterpolationOverrides { min: opt.interp_min_delay, max: opt.interp_max_delay };
+    app.insert_resource(InterpolationDelay::resolve(InterpolationParams::default(), &overrides));
+    app.insert_resource(overrides);
+    if !opt.no_interp {
+        app.add_system(adopt_advertised_interpolation_system.before("interpolate_players"));
+        app.add_system(
+            interpolate_players_system
+                .with_run_criteria(run_unless_frozen)
+                .label("interpolate_players"),
+        );
+    }
+    app.add_system(quality_bars_system.with_run_criteria(run_unless_frozen));
+
+    app.insert_resource(ReadyCheck::default());
+    app.add_system(ready_panel_system);
+    app.add_system(ready_button_system.with_run_criteria(run_if_in_game));
+    app.add_system(ready_text_system);
+
+    app.add_plugin(FrameTimeDiagnosticsPlugin::default());
+    let chat_history = ChatHistory::new(opt.chat_history);
+    let chat_history = match &opt.chat_log {
+        Some(path) => chat_history.with_log(path).unwrap_or_else(|e| {
+            warn!("Failed to open the chat log {}, the chat is not logged: {}", path.display(), e);
+            ChatHistory::new(opt.chat_history)
+        }),
+        None => chat_history,
+    };
+    app.insert_resource(chat_history);
+    app.add_startup_system(setup_chat);
+    app.add_system(chat_scroll_system);
+    app.add_system(chat_text_system);
+
+    app.insert_resource(Tutorial { show: show_tutorial(&opt, &settings) });
+    app.insert_resource(key_bindings(&opt, &settings));
+    app.add_system(controls_menu_system);
+    app.insert_resource(SettingsFile { path: opt.settings.clone(), settings });
+    app.add_startup_system(setup_tutorial);
+    app.add_system(dismiss_tutorial_system);
+
+    app.insert_resource(DebugHud::default());
+    app.insert_resource(if opt.corner_origin {
+        CoordinateOrigin::Corner
+    } else {
+        CoordinateOrigin::Center
+    });
+    app.add_startup_system(setup_debug_hud);
+    app.add_system(toggle_debug_hud_system);
+    app.add_system(debug_hud_system.with_run_criteria(run_if_playing));
 
     app.insert_resource(LogRttConfig { timer: Timer::new(Duration::from_secs(5), true) });
     app.add_system(log_rtt.with_run_criteria(run_if_client_conected));
 
+    if opt.grid {
+        app.insert_resource(GridSpacing(opt.grid_spacing));
+        app.add_system(spawn_grid_system);
+    }
+
     app.add_startup_system(setup);
     app.add_system_to_stage(CoreStage::PostUpdate, close_connection_exit_system);
-    app.add_system(panic_on_error_system);
+    if opt.freeze_when_hidden {
+        app.insert_resource(FreezeWhenHidden::default());
+        app.add_system(freeze_when_hidden_system);
+    }
+    if opt.fps_cap.is_some() || opt.freeze_when_hidden {
+        // Without a cap the frames are only paced while frozen.
+        app.insert_resource(FramePacing::new(opt.fps_cap.unwrap_or(0.)));
+        app.add_system_to_stage(CoreStage::Last, frame_pacing_system);
+    }
+    app.add_system(handle_renet_errors_system);
+    #[cfg(feature = "record-frames")]
+    if let Some(dir) = opt.record_frames.clone() {
+        let window = WindowDescriptor::default();
+        let size = UVec2::new(window.width as u32, window.height as u32);
+        app.add_plugin(FrameRecordPlugin { dir, size });
+    }
 
     app.run();
 }
@@ -54,113 +650,523 @@ struct GameAssets {
     icon_green: Handle<Image>,
     #[asset(path = "images/icon-purple.png")]
     icon_purple: Handle<Image>,
+    #[asset(path = "fonts/DejaVuSansMono.ttf")]
+    font: Handle<Font>,
+}
+
+impl GameAssets {
+    /// Returns the texture a player with this skin is drawn with.
+    fn skin_texture(&self, skin: Skin) -> Handle<Image> {
+        match skin {
+            Skin::Green => self.icon_green.clone(),
+            Skin::Purple => self.icon_purple.clone(),
+        }
+    }
 }
 
-fn new_renet_client(server_addr: SocketAddr) -> RenetClient {
-    let mut socket = server_addr.clone();
+/// The camera that looks at the world, as opposed to the UI one.
+#[derive(Component)]
+struct MainCamera;
+
+fn new_renet_client(params: &ConnectParams) -> RenetClient {
+    let server_addr = params.server_addr;
+    let mut socket = server_addr;
     socket.set_port(0);
-    let socket = UdpSocket::bind(socket).unwrap();
-    let connection_config = RenetConnectionConfig::default();
+    let socket = UdpTransport::bind(socket).unwrap().into_socket();
+    let connection_config = connection_config();
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
     let client_id = current_time.as_millis() as u64;
     let authentication = ClientAuthentication::Unsecure {
         client_id,
         protocol_id: PROTOCOL_ID,
         server_addr,
-        user_data: None,
+        user_data: Some(params.user_data),
     };
     RenetClient::new(current_time, socket, client_id, connection_config, authentication).unwrap()
 }
 
+#[allow(clippy::too_many_arguments)]
 fn client_sync_players(
     mut commands: Commands,
     game_assets: Res<GameAssets>,
     mut client: ResMut<RenetClient>,
     mut lobby: ResMut<Lobby>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut assembler: ResMut<WorldSyncAssembler>,
+    mut ready_check: ResMut<ReadyCheck>,
+    time: Res<Time>,
+    interpolation: Res<InterpolationEnabled>,
+    mut interpolated: Query<&mut Interpolated>,
+    mut local_player: ResMut<LocalPlayer>,
+    despawn_grace: Res<DespawnGrace>,
+    mut pending_despawns: ResMut<PendingDespawns>,
+    player_materials: Query<&Handle<ColorMaterial>>,
+    (mut materials, mut meshes): (ResMut<Assets<ColorMaterial>>, ResMut<Assets<Mesh>>),
+    mut chat_history: ResMut<ChatHistory>,
+    mut inputs_applied: EventWriter<InputApplied>,
 ) {
     while let Some(message) = client.receive_message(CONNECTION_EVENTS_CHANNEL) {
-        let server_message = bincode::deserialize(&message).unwrap();
+        let server_message = match try_decode(&message) {
+            Ok(server_message) => server_message,
+            Err(e) => {
+                warn!("Skipping a server message: {}", e);
+                continue;
+            }
+        };
         match server_message {
-            ServerMessage::PlayerConnected { player } => {
-                println!("{:?} connected.", player);
-
-                let player_entity = commands
-                    .spawn_bundle(MaterialMesh2dBundle {
-                        mesh: Mesh2dHandle(meshes.add(
-                            Quad::new(Vec2::new(PLAYER_SQUARE_WIDTH, PLAYER_SQUARE_HEIGHT)).into(),
-                        )),
-                        material: materials.add(ColorMaterial::from(Color::PURPLE)),
-                        ..default()
-                    })
-                    .insert(player)
-                    .id();
+            ServerMessage::InitState {
+                player,
+                tick_rate,
+                tick,
+                input_delay,
+                trusted_positions,
+                interpolation,
+                body_shape,
+                mode,
+                arena,
+                teleport_pads,
+                match_state,
+                resumed,
+            } => {
+                // We asked to resume but the server forgot us, it sends every player again.
+                if !resumed {
+                    for entity in std::mem::take(&mut lobby.players).into_values() {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+                local_player.player = Some(player);
+                local_player.trusted = trusted_positions;
+                local_player.body_shape = body_shape;
+                commands.insert_resource(mode);
+                commands.remove_resource::<TeamScores>();
+                commands.insert_resource(ServerTickRate(tick_rate));
+                let now = time.seconds_since_startup();
+                commands.insert_resource(ClientTick::new(tick, now, tick_rate));
+                // Without delay our player moves with the last input, as soon as it is pressed.
+                match input_delay {
+                    0 => commands.remove_resource::<DelayedInputs>(),
+                    delay => commands.insert_resource(DelayedInputs::new(delay)),
+                }
+                commands.insert_resource(AdvertisedInterpolation(interpolation));
+                commands.insert_resource(arena);
+                ready_check.match_state = match_state;
+                commands.insert_resource(TeleportPads(teleport_pads));
+            }
+            ServerMessage::Welcome { player } => {
+                println!("Welcomed as {:?}.", player);
+                local_player.welcomed = true;
+                let message = encode(&ClientMessage::WelcomeReceived);
+                client.send_message(CLIENT_MESSAGES_CHANNEL, message);
+            }
+            // We may already know this player if we asked for a full sync.
+            ServerMessage::PlayerConnected { player, .. }
+                if lobby.players.contains_key(&player) => {}
+            ServerMessage::PlayerConnected { player, team, skin, name, bot } => {
+                println!("{:?} connected as {:?}.", player, name.0);
+                chat_history.set_name(player, name.0.clone());
+
+                // The player came back before its dimmed cube was despawned.
+                if let Some(player_entity) = pending_despawns.cancel(player) {
+                    if let Ok(handle) = player_materials.get(player_entity) {
+                        set_disconnected_look(&mut materials, handle, false);
+                    }
+                    lobby.players.insert(player, player_entity);
+                    continue;
+                }
 
+                let look = PlayerLook { team, skin, bot, shape: local_player.body_shape };
+                let player_entity = spawn_player_cube(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    &game_assets,
+                    player,
+                    look,
+                );
+                let mut player_commands = commands.entity(player_entity);
+                player_commands.insert(CorrectionOffset::default()).insert(name);
+                if local_player.player != Some(player) {
+                    // Without interpolation the remote players are moved as soon as we know.
+                    if interpolation.0 {
+                        player_commands.insert(Interpolated::default());
+                    }
+                    player_commands.with_children(spawn_quality_bars);
+                }
                 lobby.players.insert(player, player_entity);
             }
+            ServerMessage::PlayerResized { player, size } => {
+                if let Some(player_entity) = lobby.players.get(&player) {
+                    commands
+                        .entity(*player_entity)
+                        .insert(size)
+                        .insert(SizeTransition::new(size.scale()));
+                }
+            }
+            ServerMessage::PlayerSpeedChanged { player, speed } => {
+                if let Some(player_entity) = lobby.players.get(&player) {
+                    commands.entity(*player_entity).insert(speed);
+                }
+            }
+            ServerMessage::SpawnProtected { player, duration } => {
+                if let Some(player_entity) = lobby.players.get(&player) {
+                    let until = time.seconds_since_startup() + duration.as_secs_f64();
+                    commands
+                        .entity(*player_entity)
+                        .with_children(|parent| spawn_protection_outline(parent, until));
+                }
+            }
             ServerMessage::PlayerDisconnected { player } => {
                 println!("{:?} disconnected.", player);
                 if let Some(player_entity) = lobby.players.remove(&player) {
-                    commands.entity(player_entity).despawn();
+                    if despawn_grace.0 > 0. {
+                        if let Ok(handle) = player_materials.get(player_entity) {
+                            set_disconnected_look(&mut materials, handle, true);
+                        }
+                        let at = time.seconds_since_startup() + despawn_grace.0;
+                        pending_despawns.schedule(player, player_entity, at);
+                    } else {
+                        commands.entity(player_entity).despawn_recursive();
+                    }
+                }
+            }
+            // The player keeps its id, we only bring its cube back if it was being despawned.
+            ServerMessage::PlayerReconnected { player } => {
+                println!("{:?} reconnected.", player);
+                if !lobby.players.contains_key(&player) {
+                    if let Some(player_entity) = pending_despawns.cancel(player) {
+                        if let Ok(handle) = player_materials.get(player_entity) {
+                            set_disconnected_look(&mut materials, handle, false);
+                        }
+                        lobby.players.insert(player, player_entity);
+                    }
+                }
+            }
+            ServerMessage::ReadyChanged { player, ready } => {
+                match ready {
+                    true => ready_check.ready.insert(player),
+                    false => ready_check.ready.remove(&player),
+                };
+            }
+            ServerMessage::ArenaChanged { arena, teleport_pads } => {
+                println!("The arena is now {}x{}.", arena.width, arena.height);
+                commands.insert_resource(arena);
+                commands.insert_resource(TeleportPads(teleport_pads));
+            }
+            ServerMessage::MatchStateChanged { state } => {
+                println!("The match state is now {:?}.", state);
+                ready_check.match_state = state;
+                ready_check.ready.clear();
+                ready_check.local_ready = false;
+            }
+            ServerMessage::PlayerEliminated { player, by: Some(by) } => {
+                println!("{:?} has been eliminated by {:?}.", player, by);
+            }
+            ServerMessage::PlayerEliminated { player, by: None } => {
+                println!("{:?} fell off the arena.", player);
+            }
+            ServerMessage::Chat { player, text } => {
+                chat_history.push(format!("{}: {}", chat_history.name(player), text));
+            }
+            ServerMessage::Whisper { from, text } => {
+                chat_history.push(format!("[whisper] {}: {}", chat_history.name(from), text));
+            }
+            ServerMessage::WhisperFailed { reason } => {
+                chat_history.push(format!("[whisper] Your message was not delivered, {}.", reason));
+            }
+            ServerMessage::ScoreChanged { player, score } => {
+                if let Some(player_entity) = lobby.players.get(&player) {
+                    commands.entity(*player_entity).insert(Score(score));
+                }
+            }
+            ServerMessage::TeamScores { scores } => {
+                commands.insert_resource(TeamScores(scores));
+            }
+            ServerMessage::Announcement { text } => {
+                chat_history.push(format!("[server] {}", text));
+                spawn_announcement(&mut commands, &game_assets, &time, text);
+            }
+            ServerMessage::ChatThrottled { retry_after } => {
+                let line = format!("You are sending too many messages, wait {:.0?}.", retry_after);
+                chat_history.push(line);
+            }
+            ServerMessage::Disconnecting { reason } => {
+                println!("The server is disconnecting us: {}.", reason);
+                commands.insert_resource(DisconnectReason(Some(reason)));
+            }
+            ServerMessage::Pong(value) => {
+                debug!("Received a pong for {}.", value);
+            }
+            ServerMessage::InputsApplied { inputs } => {
+                for (sequence, tick) in inputs {
+                    inputs_applied.send(InputApplied { sequence, tick });
                 }
             }
+            ServerMessage::GameEvent(event) => {
+                info!("{:?}", event);
+            }
         }
     }
 
     while let Some(message) = client.receive_message(WORLD_SYNC_CHANNEL) {
-        let world: WorldSync = bincode::deserialize(&message).unwrap();
+        let mut part: WorldSyncPart = match try_decode(&message) {
+            Ok(part) => part,
+            Err(e) => {
+                warn!("Skipping a world sync: {}", e);
+                continue;
+            }
+        };
+        // The players didn't move, they must not be seen as lagging behind meanwhile.
+        if part.unchanged {
+            for mut interpolated in interpolated.iter_mut() {
+                interpolated.hold(time.seconds_since_startup());
+            }
+            continue;
+        }
+        part.dequantize();
+        let world = match assembler.push(part) {
+            Some(world) => world,
+            None => continue,
+        };
         for (player, translation) in world.players_positions.iter() {
             if let Some(player_entity) = lobby.players.get(player) {
-                let transform = Transform { translation: translation.extend(0.), ..default() };
-                commands.entity(*player_entity).insert(transform);
+                let teleports = world.players_teleports.get(player).copied().unwrap_or(0);
+                if local_player.player == Some(*player) {
+                    // Our own position is smoothed when corrected, unless we move it ourselves.
+                    if !local_player.trusted {
+                        // Adding the position again, instead of changing it, skips the smoothing.
+                        if local_player.teleports != teleports {
+                            local_player.teleports = teleports;
+                            commands.entity(*player_entity).remove::<LogicalPosition>();
+                        }
+                        commands.entity(*player_entity).insert(LogicalPosition(*translation));
+                    }
+                } else {
+                    let interpolated = interpolated.get_mut(*player_entity).ok();
+                    let now = time.seconds_since_startup();
+                    let position = remote_position(interpolated, now, *translation, teleports);
+                    if let Some(transform) = position {
+                        commands.entity(*player_entity).insert(transform);
+                    }
+                }
             }
         }
+        for (player, stats) in world.players_connections.iter() {
+            if let Some(player_entity) = lobby.players.get(player) {
+                commands.entity(*player_entity).insert(*stats);
+            }
+        }
+        let arrival = time.seconds_since_startup();
+        commands.insert_resource(SyncedObjects { arrival, positions: world.objects });
+    }
+}
+
+/// Buffers the position of a remote player to be interpolated, or returns
+/// the transform to snap it to when it is not interpolated.
+fn remote_position(
+    interpolated: Option<Mut<Interpolated>>,
+    now: f64,
+    translation: Vec2,
+    teleports: u8,
+) -> Option<Transform> {
+    match interpolated {
+        Some(mut interpolated) => {
+            interpolated.snap_if_teleported(teleports);
+            interpolated.push(now, translation);
+            None
+        }
+        None => Some(Transform { translation: translation.extend(PLAYER_Z), ..default() }),
+    }
+}
+
+/// How a player is drawn, as told by the server when it connected.
+#[derive(Debug, Default, Clone, Copy)]
+struct PlayerLook {
+    team: Option<Team>,
+    skin: Option<Skin>,
+    bot: bool,
+    shape: BodyShape,
+}
+
+/// The mesh of a player of this shape, at its normal size.
+fn player_mesh(shape: BodyShape) -> Mesh {
+    match shape {
+        BodyShape::Square => Quad::new(SizeClass::Normal.half_extents() * 2.).into(),
+        BodyShape::Circle => Circle::new(BodyShape::radius(SizeClass::Normal)).into(),
+    }
+}
+
+/// Spawns the cube of a player, textured with its skin or colored like its team.
+fn spawn_player_cube(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    game_assets: &GameAssets,
+    player: Player,
+    look: PlayerLook,
+) -> Entity {
+    let size = Vec2::new(PLAYER_SQUARE_WIDTH, PLAYER_SQUARE_HEIGHT);
+    let color = team_color(look.team);
+    let color = if look.bot { bot_color(color) } else { color };
+    let mut player_commands = match (look.skin, look.shape) {
+        (Some(skin), BodyShape::Square) => commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite { custom_size: Some(size), ..default() },
+            texture: game_assets.skin_texture(skin),
+            ..default()
+        }),
+        // The skins of the round players are cut out of their square texture.
+        (skin, shape) => {
+            let material = match skin {
+                Some(skin) => ColorMaterial::from(game_assets.skin_texture(skin)),
+                None => ColorMaterial::from(color),
+            };
+            commands.spawn_bundle(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(player_mesh(shape))),
+                material: materials.add(material),
+                ..default()
+            })
+        }
+    };
+    player_commands.insert(player);
+    if look.bot {
+        let font = game_assets.font.clone();
+        player_commands.with_children(|parent| spawn_bot_tag(parent, font));
+    }
+    player_commands.id()
+}
+
+/// The players without a team are all purple.
+fn team_color(team: Option<Team>) -> Color {
+    const TEAM_COLORS: [Color; 4] = [Color::RED, Color::BLUE, Color::GREEN, Color::YELLOW];
+    match team {
+        Some(Team(team)) => TEAM_COLORS[team as usize % TEAM_COLORS.len()],
+        None => Color::PURPLE,
     }
 }
 
 /// set up a simple 2D scene
 fn setup(mut commands: Commands) {
     // camera
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d()).insert(MainCamera);
+    commands.spawn_bundle(UiCameraBundle::default());
 }
 
-fn player_input(keyboard_input: Res<Input<KeyCode>>, mut player_input: ResMut<PlayerInput>) {
-    player_input.left = keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left);
-    player_input.right =
-        keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right);
-    player_input.up = keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up);
-    player_input.down = keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down);
+#[allow(clippy::too_many_arguments)]
+fn player_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    camera_mode: Res<CameraMode>,
+    freeze: Res<FreezePlayerInFreeCamera>,
+    mut focus_events: EventReader<WindowFocused>,
+    mut unfocused: Local<bool>,
+    gamepad: Res<ActiveGamepad>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    bindings: Res<KeyBindings>,
+    browser: Res<ServerBrowser>,
+    mut player_input: ResMut<PlayerInput>,
+) {
+    // The keys pressed while the window is unfocused are meant for another application.
+    for event in focus_events.iter() {
+        *unfocused = !event.focused;
+    }
+
+    // The arrows select a server while the browser is open, they don't move the player.
+    if *unfocused || browser.visible || (*camera_mode == CameraMode::Free && freeze.0) {
+        *player_input = PlayerInput::default();
+        return;
+    }
+
+    let pad = gamepad.0.map(|g| gamepad_input(g, &gamepad_buttons, &gamepad_axes));
+    let pad = pad.unwrap_or_default();
+    let keys = bindings.input(&keyboard_input);
+    *player_input = bindings.mirror.apply(PlayerInput {
+        left: keys.left || pad.left,
+        right: keys.right || pad.right,
+        up: keys.up || pad.up,
+        down: keys.down || pad.down,
+        dash: keys.dash || pad.dash,
+    });
 }
 
-fn client_send_input(player_input: Res<PlayerInput>, mut client: ResMut<RenetClient>) {
-    let input_message = bincode::serialize(&*player_input).unwrap();
-    client.send_message(PLAYER_POSITION_CHANNEL, input_message);
+fn client_send_input(
+    time: Res<Time>,
+    policy: Res<InputSendPolicy>,
+    player_input: Res<PlayerInput>,
+    mut client: ResMut<RenetClient>,
+    echo: Option<ResMut<InputEcho>>,
+    client_tick: Option<Res<ClientTick>>,
+    delayed_inputs: Option<ResMut<DelayedInputs>>,
+    mut last_sent: Local<Option<(PlayerInput, f64)>>,
+) {
+    let now = time.seconds_since_startup();
+    // We can't tag the inputs before the server told us its tick.
+    let client_tick = match client_tick {
+        Some(client_tick) => client_tick.at(now),
+        None => return,
+    };
+    if should_send_input(*policy, *last_sent, *player_input, now) {
+        let input = *player_input;
+        let tick = match delayed_inputs {
+            Some(mut delayed_inputs) => delayed_inputs.push(client_tick, input),
+            None => client_tick,
+        };
+        let message = match echo {
+            Some(mut echo) => {
+                let sequence = echo.tag(client_tick);
+                ClientMessage::TaggedInput { input, tick, sequence }
+            }
+            None => ClientMessage::Input { input, tick },
+        };
+        let input_message = encode(&message);
+        client.send_message(CLIENT_INPUT_CHANNEL, input_message);
+        *last_sent = Some((*player_input, now));
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn camera_follow_player(
-    client: Res<RenetClient>,
+    time: Res<Time>,
+    shake: Res<CameraShake>,
+    lead: Res<CameraLead>,
+    local_player: Res<LocalPlayer>,
     lobby: Res<Lobby>,
-    transforms: Query<&Transform, (With<Player>, Without<Camera>)>,
-    mut cameras: Query<&mut Transform, (With<Camera>, Without<Player>)>,
+    camera_mode: Res<CameraMode>,
+    clamp: Res<ClampCameraToArena>,
+    arena: Option<Res<Arena>>,
+    transforms: Query<&Transform, (With<Player>, Without<MainCamera>)>,
+    mut cameras: Query<
+        (&mut Transform, &OrthographicProjection),
+        (With<MainCamera>, Without<Player>),
+    >,
 ) {
-    let player = Player { id: client.client_id() };
-    let entity = lobby.players.get(&player).unwrap();
-    for mut cam_transform in cameras.iter_mut() {
-        cam_transform.translation = transforms.get(*entity).unwrap().translation;
+    if *camera_mode == CameraMode::Free {
+        return;
+    }
+
+    let entity = local_player.player.and_then(|player| lobby.players.get(&player)).unwrap();
+    let target = transforms.get(*entity).unwrap().translation.truncate() + lead.offset;
+    for (mut cam_transform, projection) in cameras.iter_mut() {
+        let center = match arena.as_deref() {
+            Some(&arena) if clamp.0 => clamp_to_arena(target, visible_half_size(projection), arena),
+            _ => target,
+        };
+        // The camera keeps its depth to see everything drawn under the players.
+        let center = center + shake.offset(time.seconds_since_startup());
+        cam_transform.translation = center.extend(cam_transform.translation.z);
     }
 }
 
+/// Chained after another run criteria, runs when it does and our player exists.
 fn run_if_player_exist(
-    client: Res<RenetClient>,
+    In(should_run): In<ShouldRun>,
+    local_player: Res<LocalPlayer>,
     lobby: Res<Lobby>,
     transforms: Query<&Transform, With<Player>>,
 ) -> ShouldRun {
-    let player = Player { id: client.client_id() };
-    if lobby.players.get(&player).map_or(false, |id| transforms.get(*id).is_ok()) {
-        ShouldRun::Yes
-    } else {
-        ShouldRun::No
+    let entity = local_player.player.and_then(|player| lobby.players.get(&player));
+    match should_run {
+        ShouldRun::Yes if entity.map_or(false, |entity| transforms.get(*entity).is_ok()) => {
+            ShouldRun::Yes
+        }
+        _ => ShouldRun::No,
     }
 }
 
@@ -186,3 +1192,142 @@ fn log_rtt(time: Res<Time>, client: Res<RenetClient>, mut config: ResMut<LogRttC
         eprintln!("UDP Round-trip time: {:0.02?}ms", rtt);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_team_has_its_color() {
+        assert_eq!(team_color(None), Color::PURPLE);
+        assert_eq!(team_color(Some(Team(0))), Color::RED);
+        assert_eq!(team_color(Some(Team(1))), Color::BLUE);
+        assert_ne!(team_color(Some(Team(0))), team_color(Some(Team(1))));
+        // There are more teams than colors, they are reused.
+        assert_eq!(team_color(Some(Team(4))), team_color(Some(Team(0))));
+    }
+
+    #[test]
+    fn remote_players_are_snapped_without_interpolation() {
+        let transform = remote_position(None, 1.0, Vec2::new(3., 4.), 0).unwrap();
+        assert_eq!(transform.translation, Vec3::new(3., 4., PLAYER_Z));
+    }
+
+    #[test]
+    fn interpolated_remote_players_are_buffered() {
+        let mut world = World::new();
+        let entity = world.spawn().insert(Interpolated::default()).id();
+        let interpolated = world.get_mut::<Interpolated>(entity);
+        assert_eq!(remote_position(interpolated, 1.0, Vec2::new(3., 4.), 0), None);
+        let interpolated = world.get::<Interpolated>(entity).unwrap();
+        assert_eq!(interpolated.sample(1.0), Some(Vec2::new(3., 4.)));
+    }
+
+    #[test]
+    fn the_input_is_released_while_unfocused() {
+        let mut app = App::new();
+        app.add_event::<WindowFocused>();
+        app.insert_resource(CameraMode::Follow);
+        app.insert_resource(FreezePlayerInFreeCamera(true));
+        app.insert_resource(PlayerInput::default());
+        app.insert_resource(ActiveGamepad::default());
+        app.insert_resource(Input::<GamepadButton>::default());
+        app.insert_resource(Axis::<GamepadAxis>::default());
+        let mut keyboard_input = Input::<KeyCode>::default();
+        keyboard_input.press(KeyCode::A);
+        app.insert_resource(keyboard_input);
+        app.add_system(player_input);
+
+        app.update();
+        assert!(app.world.resource::<PlayerInput>().left);
+
+        let focused = |focused| WindowFocused { id: bevy::window::WindowId::primary(), focused };
+        app.world.send_event(focused(false));
+        app.update();
+        assert_eq!(*app.world.resource::<PlayerInput>(), PlayerInput::default());
+
+        app.world.send_event(focused(true));
+        app.update();
+        assert!(app.world.resource::<PlayerInput>().left);
+    }
+
+    #[derive(Default)]
+    struct Runs(u32);
+
+    fn count_runs(mut runs: ResMut<Runs>) {
+        runs.0 += 1;
+    }
+
+    #[test]
+    fn the_camera_follows_our_player_once_it_exists() {
+        let mut world = World::new();
+        world.insert_resource(LocalPlayer::default());
+        world.insert_resource(Lobby::default());
+        world.insert_resource(Runs::default());
+        let playing = (|| ShouldRun::Yes).chain(run_if_player_exist);
+        let mut stage = SystemStage::single(count_runs.with_run_criteria(playing));
+
+        stage.run(&mut world);
+        assert_eq!(world.resource::<Runs>().0, 0);
+
+        let player = Player { id: 1 };
+        let entity = world.spawn().insert(player).insert(Transform::default()).id();
+        world.resource_mut::<LocalPlayer>().player = Some(player);
+        world.resource_mut::<Lobby>().players.insert(player, entity);
+        stage.run(&mut world);
+        assert_eq!(world.resource::<Runs>().0, 1);
+
+        // It doesn't follow it while not playing, the window minimized for example.
+        let not_playing = (|| ShouldRun::No).chain(run_if_player_exist);
+        let mut stage = SystemStage::single(count_runs.with_run_criteria(not_playing));
+        stage.run(&mut world);
+        assert_eq!(world.resource::<Runs>().0, 1);
+    }
+
+    #[test]
+    fn raw_corrections_are_not_smoothed() {
+        let opt = Opt::parse_from(["acerbus-client", "--correction-frames", "4"]);
+        assert_eq!(correction_frames(&opt), 4);
+        let opt =
+            Opt::parse_from(["acerbus-client", "--correction-frames", "4", "--raw-correction"]);
+        assert_eq!(correction_frames(&opt), 0);
+    }
+
+    #[test]
+    fn the_flags_win_over_the_config_file_which_wins_over_the_defaults() {
+        let path = env::temp_dir().join(format!("acerbus-client-config-{}", std::process::id()));
+        let content = r#"{ "name": "Kero", "correction_frames": 12, "grid": true }"#;
+        fs::write(&path, content).unwrap();
+        let args = ["acerbus-client", "--config", path.to_str().unwrap(), "--name", "Mops"];
+        let opt = parse_opt(args.iter().map(|arg| arg.to_string()).collect());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(opt.name.as_deref(), Some("Mops"));
+        assert_eq!(opt.correction_frames, 12);
+        assert!(opt.grid);
+        assert_eq!(opt.chat_history, 100);
+
+        let settings = ClientSettings::default();
+        let config = serde_json::to_value(Config::resolve(&opt, &settings)).unwrap();
+        assert_eq!(config["options"]["name"], "Mops");
+        assert_eq!(config["settings"]["controls"], "wasd");
+    }
+
+    #[test]
+    fn the_connect_timeout_must_be_positive() {
+        let parse = |timeout| Opt::try_parse_from(["acerbus-client", "--connect-timeout", timeout]);
+        assert_eq!(parse("2.5").unwrap().connect_timeout, 2.5);
+        for timeout in ["0", "-1", "inf", "NaN", "soon"] {
+            assert!(parse(timeout).is_err(), "{} was accepted", timeout);
+        }
+    }
+
+    #[test]
+    fn the_fps_cap_must_be_positive() {
+        let parse = |fps| Opt::try_parse_from(["acerbus-client", "--fps-cap", fps]);
+        assert_eq!(parse("30").unwrap().fps_cap, Some(30.));
+        for fps in ["0", "inf", "NaN", "fast"] {
+            assert!(parse(fps).is_err(), "{} was accepted", fps);
+        }
+    }
+}
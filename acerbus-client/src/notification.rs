@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+
+use crate::layers::{UiLayer, HUD_Z};
+use crate::GameAssets;
+
+/// How long, in seconds, a notification stays on screen.
+const NOTIFICATION_DURATION: f64 = 3.0;
+/// The announcements of the server are shown longer, bigger and on top of the screen.
+const ANNOUNCEMENT_DURATION: f64 = 6.0;
+const ANNOUNCEMENT_COLOR: Color = Color::rgb(1.0, 0.85, 0.2);
+
+/// A short message shown at the bottom of the screen, it disappears by itself.
+#[derive(Component)]
+pub struct Notification {
+    expires_at: f64,
+}
+
+pub fn spawn_notification(
+    commands: &mut Commands,
+    game_assets: &GameAssets,
+    time: &Time,
+    text: impl Into<String>,
+) {
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 18.0, color: Color::WHITE };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { right: Val::Px(10.0), bottom: Val::Px(10.0), ..default() },
+                ..default()
+            },
+            text: Text::with_section(text, text_style, default()),
+            ..default()
+        })
+        .insert(Notification { expires_at: time.seconds_since_startup() + NOTIFICATION_DURATION })
+        .insert(UiLayer(HUD_Z));
+}
+
+/// Shows a message of the server administrator prominently, on top of the screen.
+pub fn spawn_announcement(
+    commands: &mut Commands,
+    game_assets: &GameAssets,
+    time: &Time,
+    text: impl Into<String>,
+) {
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 28.0, color: ANNOUNCEMENT_COLOR };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Percent(30.0), top: Val::Px(10.0), ..default() },
+                ..default()
+            },
+            text: Text::with_section(text, text_style, default()),
+            ..default()
+        })
+        .insert(Notification { expires_at: time.seconds_since_startup() + ANNOUNCEMENT_DURATION })
+        .insert(UiLayer(HUD_Z));
+}
+
+pub fn expire_notifications_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    notifications: Query<(Entity, &Notification)>,
+) {
+    let now = time.seconds_since_startup();
+    for (entity, notification) in notifications.iter() {
+        if notification.expires_at <= now {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn announcements_outlast_the_notifications() {
+        let mut world = World::new();
+        let start = Instant::now();
+        world.insert_resource(Time::default());
+        let notification =
+            world.spawn().insert(Notification { expires_at: NOTIFICATION_DURATION }).id();
+        let announcement =
+            world.spawn().insert(Notification { expires_at: ANNOUNCEMENT_DURATION }).id();
+        let mut stage = SystemStage::single_threaded().with_system(expire_notifications_system);
+
+        let mut run_at = |world: &mut World, secs| {
+            world.resource_mut::<Time>().update_with_instant(start + Duration::from_secs(secs));
+            stage.run(world);
+        };
+        run_at(&mut world, 1);
+        assert!(world.get_entity(notification).is_some());
+        run_at(&mut world, 4);
+        assert!(world.get_entity(notification).is_none());
+        assert!(world.get_entity(announcement).is_some());
+        run_at(&mut world, 7);
+        assert!(world.get_entity(announcement).is_none());
+    }
+}
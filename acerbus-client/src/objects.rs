@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+
+use acerbus_common::*;
+use bevy::prelude::shape::Circle;
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+
+use crate::interpolation::{Interpolated, InterpolationEnabled};
+use crate::layers::PLAYER_Z;
+
+/// The objects of the last world received from the server and when it arrived.
+#[derive(Debug, Default)]
+pub struct SyncedObjects {
+    pub arrival: f64,
+    pub positions: BTreeMap<ObjectId, Vec2>,
+}
+
+/// Spawns the objects that appeared in the world, moves the others and despawns the ones
+/// that are gone. They are interpolated like the other players.
+pub fn sync_objects_system(
+    mut commands: Commands,
+    synced: Res<SyncedObjects>,
+    interpolation: Res<InterpolationEnabled>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut objects: Query<(Entity, &ObjectId, &mut Transform, Option<&mut Interpolated>)>,
+) {
+    if !synced.is_changed() {
+        return;
+    }
+
+    let mut missing = synced.positions.clone();
+    for (entity, object, mut transform, interpolated) in objects.iter_mut() {
+        match missing.remove(object) {
+            Some(position) => match interpolated {
+                Some(mut interpolated) => interpolated.push(synced.arrival, position),
+                None => transform.translation = position.extend(PLAYER_Z),
+            },
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for (object, position) in missing {
+        let mut object_commands = commands.spawn_bundle(MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Circle::new(BALL_RADIUS).into())),
+            material: materials.add(ColorMaterial::from(Color::WHITE)),
+            transform: Transform::from_translation(position.extend(PLAYER_Z)),
+            ..default()
+        });
+        object_commands.insert(object);
+        if interpolation.0 {
+            let mut interpolated = Interpolated::default();
+            interpolated.push(synced.arrival, position);
+            object_commands.insert(interpolated);
+        }
+    }
+}
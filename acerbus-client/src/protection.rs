@@ -0,0 +1,48 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::layers::OUTLINE_Z_OFFSET;
+
+/// How far the outline goes beyond the cube of the player, in pixels.
+const OUTLINE_WIDTH: f32 = 4.0;
+/// How many times per second the outline pulses.
+const OUTLINE_PULSE_FREQUENCY: f64 = 2.0;
+
+/// The pulsing outline of a player that can't be pushed since it just spawned.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct ProtectionOutline {
+    pub until: f64,
+}
+
+/// Returns the opacity of the outline at `time`, it pulses between half and fully opaque.
+pub fn outline_alpha(time: f64) -> f32 {
+    let pulse = (time * OUTLINE_PULSE_FREQUENCY * std::f64::consts::TAU).sin() as f32;
+    0.75 + pulse * 0.25
+}
+
+/// Spawns the outline as a child of a player cube, it is removed once the protection ends.
+pub fn spawn_protection_outline(parent: &mut ChildBuilder, until: f64) {
+    let size = Vec2::new(PLAYER_SQUARE_WIDTH, PLAYER_SQUARE_HEIGHT) + OUTLINE_WIDTH * 2.;
+    parent
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite { color: Color::WHITE, custom_size: Some(size), ..default() },
+            transform: Transform::from_xyz(0., 0., OUTLINE_Z_OFFSET),
+            ..default()
+        })
+        .insert(ProtectionOutline { until });
+}
+
+pub fn protection_outline_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut outlines: Query<(Entity, &ProtectionOutline, &mut Sprite)>,
+) {
+    let now = time.seconds_since_startup();
+    for (entity, outline, mut sprite) in outlines.iter_mut() {
+        if now >= outline.until {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            sprite.color.set_a(outline_alpha(now));
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::layers::LABEL_Z_OFFSET;
+
+const QUALITY_BAR_WIDTH: f32 = 4.0;
+const QUALITY_BAR_SPACING: f32 = 2.0;
+const QUALITY_BAR_MARGIN: f32 = 8.0;
+const QUALITY_BAR_OFF_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+
+/// One of the three bars drawn above a remote player to show its connection quality.
+#[derive(Component)]
+pub struct QualityBar(pub usize);
+
+pub fn quality_color(quality: ConnectionQuality) -> Color {
+    match quality {
+        ConnectionQuality::Good => Color::GREEN,
+        ConnectionQuality::Medium => Color::YELLOW,
+        ConnectionQuality::Poor => Color::RED,
+    }
+}
+
+/// Spawns the bars as children of a player cube, they are lit by `quality_bars_system`.
+pub fn spawn_quality_bars(parent: &mut ChildBuilder) {
+    for index in 0..3 {
+        let height = QUALITY_BAR_WIDTH * (index + 1) as f32;
+        let x = (index as f32 - 1.0) * (QUALITY_BAR_WIDTH + QUALITY_BAR_SPACING);
+        let y = PLAYER_SQUARE_HEIGHT / 2. + QUALITY_BAR_MARGIN + height / 2.;
+        parent
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: QUALITY_BAR_OFF_COLOR,
+                    custom_size: Some(Vec2::new(QUALITY_BAR_WIDTH, height)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(x, y, LABEL_Z_OFFSET),
+                ..default()
+            })
+            .insert(QualityBar(index));
+    }
+}
+
+pub fn quality_bars_system(
+    players: Query<(&ConnectionStats, &Children), Changed<ConnectionStats>>,
+    mut bars: Query<(&QualityBar, &mut Sprite)>,
+) {
+    for (stats, children) in players.iter() {
+        let quality = ConnectionQuality::from_stats(*stats);
+        for child in children.iter() {
+            if let Ok((bar, mut sprite)) = bars.get_mut(*child) {
+                sprite.color = match bar.0 < quality.bars() {
+                    true => quality_color(quality),
+                    false => QUALITY_BAR_OFF_COLOR,
+                };
+            }
+        }
+    }
+}
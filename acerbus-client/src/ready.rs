@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetClient;
+
+use crate::layers::{UiLayer, MENU_Z};
+use crate::GameAssets;
+
+const READY_BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const READY_BUTTON_PRESSED_COLOR: Color = Color::rgb(0.25, 0.55, 0.25);
+
+/// What the client knows about the players that are ready to play.
+#[derive(Debug, Default)]
+pub struct ReadyCheck {
+    pub match_state: MatchState,
+    pub ready: HashSet<Player>,
+    pub local_ready: bool,
+}
+
+/// The panel shown before the match starts.
+#[derive(Component)]
+pub struct ReadyPanel;
+
+#[derive(Component)]
+pub struct ReadyButton;
+
+#[derive(Component)]
+pub struct ReadyButtonText;
+
+#[derive(Component)]
+pub struct ReadyPlayersText;
+
+/// Show the ready panel while in the lobby and remove it when the match starts.
+pub fn ready_panel_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    ready_check: Res<ReadyCheck>,
+    panels: Query<Entity, With<ReadyPanel>>,
+) {
+    match (ready_check.match_state, panels.get_single()) {
+        (MatchState::Lobby, Err(_)) => spawn_ready_panel(&mut commands, &game_assets),
+        (MatchState::Playing, Ok(panel)) => commands.entity(panel).despawn_recursive(),
+        _ => (),
+    }
+}
+
+fn spawn_ready_panel(commands: &mut Commands, game_assets: &GameAssets) {
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 20.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(10.0), top: Val::Px(10.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(ReadyPanel)
+        .insert(UiLayer(MENU_Z))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(150.0), Val::Px(40.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: READY_BUTTON_COLOR.into(),
+                    ..default()
+                })
+                .insert(ReadyButton)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section("Ready", text_style.clone(), default()),
+                            ..default()
+                        })
+                        .insert(ReadyButtonText);
+                });
+
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section("", text_style, default()),
+                    ..default()
+                })
+                .insert(ReadyPlayersText);
+        });
+}
+
+/// Tell the server that we are (or are no more) ready when the button is clicked.
+pub fn ready_button_system(
+    mut client: ResMut<RenetClient>,
+    mut ready_check: ResMut<ReadyCheck>,
+    mut interactions: Query<
+        (&Interaction, &mut UiColor),
+        (Changed<Interaction>, With<ReadyButton>),
+    >,
+) {
+    for (interaction, mut color) in interactions.iter_mut() {
+        if *interaction == Interaction::Clicked {
+            ready_check.local_ready = !ready_check.local_ready;
+            let message = encode(&ClientMessage::Ready(ready_check.local_ready));
+            client.send_message(CLIENT_MESSAGES_CHANNEL, message);
+        }
+
+        *color = match ready_check.local_ready {
+            true => READY_BUTTON_PRESSED_COLOR.into(),
+            false => READY_BUTTON_COLOR.into(),
+        };
+    }
+}
+
+/// Keep the button label and the list of ready players up to date.
+pub fn ready_text_system(
+    ready_check: Res<ReadyCheck>,
+    mut button_texts: Query<&mut Text, (With<ReadyButtonText>, Without<ReadyPlayersText>)>,
+    mut players_texts: Query<&mut Text, (With<ReadyPlayersText>, Without<ReadyButtonText>)>,
+) {
+    for mut text in button_texts.iter_mut() {
+        text.sections[0].value = match ready_check.local_ready {
+            true => "Not ready".to_string(),
+            false => "Ready".to_string(),
+        };
+    }
+
+    let mut ready: Vec<_> = ready_check.ready.iter().map(|p| p.id).collect();
+    ready.sort_unstable();
+    let lines: Vec<_> = ready.iter().map(|id| format!("Player {} is ready", id)).collect();
+    for mut text in players_texts.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
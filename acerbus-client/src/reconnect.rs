@@ -0,0 +1,237 @@
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::connecting::{
+    ConnectParams, ConnectionLostPanel, ConnectionStatus, DisconnectReason, LocalPlayer,
+    UnreachablePanel,
+};
+use crate::ready::ReadyCheck;
+use crate::teleport_pads::TeleportPads;
+use crate::{new_renet_client, GameAssets};
+
+/// How long we wait before the first attempt, the delay doubles after every failed attempt.
+pub const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+pub const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Returns how long to wait before this attempt, the first one being the attempt `1`.
+pub fn reconnect_delay(attempt: u32) -> Duration {
+    let doublings = attempt.saturating_sub(1).min(16);
+    (RECONNECT_BASE_DELAY * 2u32.pow(doublings)).min(RECONNECT_MAX_DELAY)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconnectPhase {
+    /// We are connected, or never were.
+    Idle,
+    /// The connection is lost, the next attempt starts at this instant.
+    Waiting { at: Instant },
+    /// We are connecting again.
+    Attempting,
+    /// Every attempt failed, we wait for the player to retry by hand.
+    GaveUp,
+}
+
+/// Connects again to the server we lost, waiting longer and longer between the attempts.
+#[derive(Debug, Clone)]
+pub struct ReconnectState {
+    /// How many attempts are made before giving up, no limit when `None`.
+    pub max_attempts: Option<u32>,
+    /// The attempts made since we lost the connection.
+    pub attempts: u32,
+    pub phase: ReconnectPhase,
+}
+
+impl ReconnectState {
+    pub fn new(max_attempts: Option<u32>) -> ReconnectState {
+        ReconnectState { max_attempts, attempts: 0, phase: ReconnectPhase::Idle }
+    }
+
+    /// The connection was lost or the last attempt failed,
+    /// schedules the next attempt or gives up if there were enough of them.
+    pub fn failed(&mut self, now: Instant) -> ReconnectPhase {
+        self.phase = match self.max_attempts {
+            Some(max) if self.attempts >= max => ReconnectPhase::GaveUp,
+            _ => ReconnectPhase::Waiting { at: now + reconnect_delay(self.attempts + 1) },
+        };
+        self.phase
+    }
+
+    /// Returns whether it is time for the next attempt, which is then counted.
+    pub fn start_attempt(&mut self, now: Instant) -> bool {
+        match self.phase {
+            ReconnectPhase::Waiting { at } if now >= at => {
+                self.attempts += 1;
+                self.phase = ReconnectPhase::Attempting;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The player retried by hand after we gave up, the attempts are counted from zero.
+    pub fn retry_by_hand(&mut self) {
+        self.attempts = 0;
+        self.phase = ReconnectPhase::Attempting;
+    }
+
+    pub fn connected(&mut self) {
+        self.attempts = 0;
+        self.phase = ReconnectPhase::Idle;
+    }
+
+    fn attempts_label(&self) -> String {
+        match self.max_attempts {
+            Some(max) => format!("attempt {} of {}", self.attempts + 1, max),
+            None => format!("attempt {}", self.attempts + 1),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ReconnectPanel;
+
+/// Replaces the countdown before exiting: once the connection is lost we connect again,
+/// keeping the world we had, until it works or the attempts run out.
+#[allow(clippy::too_many_arguments)]
+pub fn auto_reconnect_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    mut params: ResMut<ConnectParams>,
+    mut status: ResMut<ConnectionStatus>,
+    mut state: ResMut<ReconnectState>,
+    mut local_player: ResMut<LocalPlayer>,
+    mut reason: ResMut<DisconnectReason>,
+    mut player_input: ResMut<PlayerInput>,
+    mut assembler: ResMut<WorldSyncAssembler>,
+    mut ready_check: ResMut<ReadyCheck>,
+    panels: Query<
+        Entity,
+        Or<(With<ConnectionLostPanel>, With<UnreachablePanel>, With<ReconnectPanel>)>,
+    >,
+    mut texts: Query<&mut Text, With<ReconnectPanel>>,
+) {
+    let now = Instant::now();
+    match (*status, state.phase) {
+        (ConnectionStatus::Connected, ReconnectPhase::Idle) => (),
+        (ConnectionStatus::Connected, _) => state.connected(),
+        (ConnectionStatus::Connecting { .. }, ReconnectPhase::GaveUp) => state.retry_by_hand(),
+        (ConnectionStatus::Lost { .. }, ReconnectPhase::Idle)
+        | (ConnectionStatus::Unreachable, ReconnectPhase::Attempting) => {
+            for panel in panels.iter() {
+                commands.entity(panel).despawn_recursive();
+            }
+            let text_style =
+                TextStyle { font: game_assets.font.clone(), font_size: 30.0, color: Color::WHITE };
+            let text_bundle = |message: String| TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect { left: Val::Px(10.0), bottom: Val::Px(10.0), ..default() },
+                    ..default()
+                },
+                text: Text::with_section(message, text_style.clone(), default()),
+                ..default()
+            };
+
+            match state.failed(now) {
+                ReconnectPhase::GaveUp => {
+                    *status = ConnectionStatus::Unreachable;
+                    let message = format!(
+                        "Could not reconnect to {} after {} attempts{}\n\
+                         Press R to retry or Escape to quit",
+                        params.server_addr,
+                        state.attempts,
+                        reason.suffix(),
+                    );
+                    commands.spawn_bundle(text_bundle(message)).insert(UnreachablePanel);
+                }
+                _ => {
+                    // The world stays frozen while we wait, as if the connection was just lost.
+                    *status = ConnectionStatus::Lost { since: now };
+                    let message = String::with_capacity(64);
+                    commands.spawn_bundle(text_bundle(message)).insert(ReconnectPanel);
+                }
+            }
+        }
+        (ConnectionStatus::Lost { .. }, ReconnectPhase::Waiting { at }) => {
+            *player_input = PlayerInput::default();
+            if !state.start_attempt(now) {
+                let seconds = at.saturating_duration_since(now).as_secs() + 1;
+                for mut text in texts.iter_mut() {
+                    let value = &mut text.sections[0].value;
+                    value.clear();
+                    value.push_str(&format!(
+                        "Connection lost{}\nReconnecting in {}s, {}",
+                        reason.suffix(),
+                        seconds,
+                        state.attempts_label(),
+                    ));
+                }
+                return;
+            }
+
+            // We come back to the same server, we keep its players and only hear about
+            // the changes, everything else is sent again.
+            for entity in panels.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            *assembler = WorldSyncAssembler::default();
+            *ready_check = ReadyCheck::default();
+            commands.remove_resource::<Arena>();
+            commands.remove_resource::<TeleportPads>();
+            if let Ok(mut request) = ConnectRequest::from_user_data(&params.user_data) {
+                request.resume = true;
+                if let Ok(user_data) = request.to_user_data() {
+                    params.user_data = user_data;
+                }
+            }
+            commands.insert_resource(new_renet_client(&params));
+            *local_player = LocalPlayer::default();
+            reason.0 = None;
+            *status = ConnectionStatus::Connecting { since: now };
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_doubles_up_to_the_max() {
+        assert_eq!(reconnect_delay(1), RECONNECT_BASE_DELAY);
+        assert_eq!(reconnect_delay(2), RECONNECT_BASE_DELAY * 2);
+        assert_eq!(reconnect_delay(3), RECONNECT_BASE_DELAY * 4);
+        assert_eq!(reconnect_delay(100), RECONNECT_MAX_DELAY);
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let now = Instant::now();
+        let mut state = ReconnectState::new(Some(2));
+        assert_eq!(state.failed(now), ReconnectPhase::Waiting { at: now + reconnect_delay(1) });
+        assert!(!state.start_attempt(now));
+        assert!(state.start_attempt(now + reconnect_delay(1)));
+
+        assert_eq!(state.failed(now), ReconnectPhase::Waiting { at: now + reconnect_delay(2) });
+        assert!(state.start_attempt(now + reconnect_delay(2)));
+        assert_eq!(state.failed(now), ReconnectPhase::GaveUp);
+
+        state.retry_by_hand();
+        assert_eq!(state.attempts, 0);
+        assert_eq!(state.phase, ReconnectPhase::Attempting);
+    }
+
+    #[test]
+    fn never_gives_up_without_a_cap() {
+        let now = Instant::now();
+        let mut state = ReconnectState::new(None);
+        for attempt in 1..=20 {
+            let at = now + reconnect_delay(attempt);
+            assert_eq!(state.failed(now), ReconnectPhase::Waiting { at });
+            assert!(state.start_attempt(at));
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Captures the rendered frames to numbered PNG images, to be assembled into a video.
+//!
+//! A second camera follows the main one and renders the world into an image, which is
+//! copied to a buffer the CPU reads back once the frame is rendered.
+
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+use bevy::core_pipeline::{self, draw_2d_graph, Transparent2d};
+use bevy::prelude::*;
+use bevy::render::camera::{ActiveCamera, Camera, CameraTypePlugin, RenderTarget};
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotValue};
+use bevy::render::render_phase::RenderPhase;
+use bevy::render::render_resource::{
+    Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout, MapMode,
+    TextureDimension, TextureFormat, TextureUsages, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::texture::BevyDefault;
+use bevy::render::{RenderApp, RenderStage};
+
+use crate::MainCamera;
+
+/// Starts and stops the recording of the frames.
+pub const RECORD_TOGGLE_KEY: KeyCode = KeyCode::F9;
+
+const RECORD_DRIVER: &str = "record_frames_driver";
+const RECORD_COPY: &str = "record_frames_copy";
+
+/// Where the frames are written and the number of the next one, the numbers keep
+/// increasing when the recording is stopped and started again.
+pub struct FrameRecorder {
+    pub dir: PathBuf,
+    pub recording: bool,
+    next_frame: u32,
+    image: Handle<Image>,
+    size: UVec2,
+    frames: Mutex<Receiver<Vec<u8>>>,
+}
+
+/// Returns the path of the frame numbered `index`, the numbers are padded so that
+/// the frames are sorted by name in the order they were recorded.
+pub fn frame_path(dir: &Path, index: u32) -> PathBuf {
+    dir.join(format!("frame-{:06}.png", index))
+}
+
+/// The camera rendering the world into the recorded image.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct RecordCamera;
+
+/// Renders the frames into an image of `size`, the initial size of the window,
+/// and writes them into `dir` while recording.
+pub struct FrameRecordPlugin {
+    pub dir: PathBuf,
+    pub size: UVec2,
+}
+
+impl Plugin for FrameRecordPlugin {
+    fn build(&self, app: &mut App) {
+        let size = Extent3d { width: self.size.x, height: self.size.y, depth_or_array_layers: 1 };
+        let mut image = Image::new_fill(
+            size,
+            TextureDimension::D2,
+            &[0, 0, 0, 255],
+            TextureFormat::bevy_default(),
+        );
+        image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_SRC
+            | TextureUsages::RENDER_ATTACHMENT;
+        let image = app.world.get_resource_mut::<Assets<Image>>().unwrap().add(image);
+
+        let (sender, frames) = channel();
+        app.insert_resource(FrameRecorder {
+            dir: self.dir.clone(),
+            recording: false,
+            next_frame: 0,
+            image: image.clone(),
+            size: self.size,
+            frames: Mutex::new(frames),
+        });
+        app.add_plugin(CameraTypePlugin::<RecordCamera>::default());
+        app.add_startup_system(setup_record_camera);
+        app.add_system(toggle_recording_system);
+        app.add_system_to_stage(CoreStage::PostUpdate, follow_main_camera_system);
+        app.add_system_to_stage(CoreStage::Last, save_frames_system);
+
+        let render_app = app.sub_app_mut(RenderApp);
+        let device = render_app.world.get_resource::<RenderDevice>().unwrap();
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("record_frames_buffer"),
+            size: (padded_bytes_per_row(self.size.x) * self.size.y) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        render_app.insert_resource(RecordingFrames(false));
+        render_app.insert_resource(FrameReadback {
+            image,
+            size: self.size,
+            buffer,
+            copied: AtomicBool::new(false),
+            sender: Mutex::new(sender),
+        });
+        render_app.add_system_to_stage(RenderStage::Extract, extract_recording_system);
+        render_app.add_system_to_stage(RenderStage::Cleanup, read_back_frame_system);
+
+        let mut graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+        graph.add_node(RECORD_DRIVER, RecordCameraDriver);
+        graph.add_node(RECORD_COPY, FrameCopyNode);
+        graph.add_node_edge(core_pipeline::node::CLEAR_PASS_DRIVER, RECORD_DRIVER).unwrap();
+        graph.add_node_edge(RECORD_DRIVER, RECORD_COPY).unwrap();
+        graph.add_node_edge(RECORD_COPY, core_pipeline::node::MAIN_PASS_DRIVER).unwrap();
+    }
+}
+
+/// The rows of the buffer the image is copied to are aligned, they are longer than the image.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    let bytes_per_row = width * 4;
+    let align = COPY_BYTES_PER_ROW_ALIGNMENT;
+    (bytes_per_row + align - 1) / align * align
+}
+
+fn setup_record_camera(mut commands: Commands, recorder: Res<FrameRecorder>) {
+    let bundle = OrthographicCameraBundle::new_2d();
+    commands.spawn_bundle(OrthographicCameraBundle {
+        camera: Camera { target: RenderTarget::Image(recorder.image.clone()), ..bundle.camera },
+        orthographic_projection: bundle.orthographic_projection,
+        visible_entities: bundle.visible_entities,
+        frustum: bundle.frustum,
+        transform: bundle.transform,
+        global_transform: bundle.global_transform,
+        marker: RecordCamera,
+    });
+}
+
+fn toggle_recording_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut recorder: ResMut<FrameRecorder>,
+) {
+    if !keyboard_input.just_pressed(RECORD_TOGGLE_KEY) {
+        return;
+    }
+
+    if !recorder.recording {
+        if let Err(e) = std::fs::create_dir_all(&recorder.dir) {
+            warn!("Failed to create {}, not recording: {}", recorder.dir.display(), e);
+            return;
+        }
+    }
+    recorder.recording = !recorder.recording;
+    match recorder.recording {
+        true => info!("Recording the frames into {}.", recorder.dir.display()),
+        false => info!("Stopped recording, {} frames recorded.", recorder.next_frame),
+    }
+}
+
+/// The recorded image shows what the main camera sees.
+fn follow_main_camera_system(
+    main_cameras: Query<&Transform, (With<MainCamera>, Without<RecordCamera>)>,
+    mut record_cameras: Query<&mut Transform, With<RecordCamera>>,
+) {
+    if let Some(main) = main_cameras.iter().next() {
+        for mut transform in record_cameras.iter_mut() {
+            *transform = *main;
+        }
+    }
+}
+
+/// Writes the frames read back from the GPU, in the order they were rendered.
+fn save_frames_system(mut recorder: ResMut<FrameRecorder>) {
+    let frames: Vec<_> = recorder.frames.lock().unwrap().try_iter().collect();
+    for frame in frames {
+        let path = frame_path(&recorder.dir, recorder.next_frame);
+        let UVec2 { x: width, y: height } = recorder.size;
+        match image::save_buffer(&path, &frame, width, height, image::ColorType::Rgba8) {
+            Ok(()) => recorder.next_frame += 1,
+            Err(e) => warn!("Failed to write the frame {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Whether the frames are recorded, extracted every frame into the render world.
+struct RecordingFrames(bool);
+
+/// The buffer the recorded image is copied to, in the render world.
+struct FrameReadback {
+    image: Handle<Image>,
+    size: UVec2,
+    buffer: Buffer,
+    /// The image was copied to the buffer during this frame, it isn't before it is prepared.
+    copied: AtomicBool,
+    sender: Mutex<Sender<Vec<u8>>>,
+}
+
+fn extract_recording_system(
+    mut commands: Commands,
+    recorder: Res<FrameRecorder>,
+    active: Res<ActiveCamera<RecordCamera>>,
+) {
+    commands.insert_resource(RecordingFrames(recorder.recording));
+    if let Some(camera) = active.get() {
+        commands.get_or_spawn(camera).insert(RenderPhase::<Transparent2d>::default());
+    }
+}
+
+/// Renders the world from the record camera, before the main pass.
+struct RecordCameraDriver;
+
+impl Node for RecordCameraDriver {
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        _render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let camera = world.get_resource::<ActiveCamera<RecordCamera>>().and_then(|a| a.get());
+        if let Some(camera) = camera {
+            graph.run_sub_graph(draw_2d_graph::NAME, vec![SlotValue::Entity(camera)])?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies the recorded image to the buffer read back once the frame is rendered.
+struct FrameCopyNode;
+
+impl Node for FrameCopyNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let recording = world.get_resource::<RecordingFrames>().map_or(false, |r| r.0);
+        let readback = world.get_resource::<FrameReadback>().unwrap();
+        let images = world.get_resource::<RenderAssets<Image>>().unwrap();
+        let image = match images.get(&readback.image) {
+            Some(image) if recording => image,
+            _ => return Ok(()),
+        };
+
+        let UVec2 { x: width, y: height } = readback.size;
+        render_context.command_encoder.copy_texture_to_buffer(
+            image.texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &readback.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row(width)),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+        readback.copied.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Waits for the frame to be rendered and sends its pixels to the main world.
+fn read_back_frame_system(readback: Res<FrameReadback>, device: Res<RenderDevice>) {
+    if !readback.copied.swap(false, Ordering::Relaxed) {
+        return;
+    }
+
+    let UVec2 { x: width, y: height } = readback.size;
+    let padded = padded_bytes_per_row(width) as usize;
+    let slice = readback.buffer.slice(..);
+    device.map_buffer(&slice, MapMode::Read);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for row in slice.get_mapped_range().chunks(padded) {
+        // The image is in BGRA, the PNG in RGBA.
+        for bgra in row[..width as usize * 4].chunks(4) {
+            pixels.extend_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+        }
+    }
+    readback.buffer.unmap();
+    let _ = readback.sender.lock().unwrap().send(pixels);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_numbered_in_order() {
+        let dir = Path::new("clip");
+        assert_eq!(frame_path(dir, 0), Path::new("clip/frame-000000.png"));
+        assert_eq!(frame_path(dir, 42), Path::new("clip/frame-000042.png"));
+
+        let names: Vec<_> = [9, 10, 100, 1000].iter().map(|i| frame_path(dir, *i)).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn buffer_rows_are_aligned() {
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+        assert_eq!(padded_bytes_per_row(1280) % COPY_BYTES_PER_ROW_ALIGNMENT, 0);
+    }
+}
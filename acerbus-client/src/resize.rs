@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+
+/// How long, in seconds, a player takes to grow or shrink to its new size.
+const RESIZE_DURATION: f32 = 0.3;
+
+/// A player changing size, its scale goes from the current one to the target one.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct SizeTransition {
+    from: Option<f32>,
+    pub target: f32,
+    elapsed: f32,
+}
+
+impl SizeTransition {
+    pub fn new(target: f32) -> SizeTransition {
+        SizeTransition { from: None, target, elapsed: 0.0 }
+    }
+
+    /// Advances the transition, returns the scale to draw the player with
+    /// and whether the transition is over.
+    pub fn advance(&mut self, current: f32, delta: f32) -> (f32, bool) {
+        let from = *self.from.get_or_insert(current);
+        self.elapsed += delta;
+        let t = (self.elapsed / RESIZE_DURATION).min(1.0);
+        let eased = t * t * (3.0 - 2.0 * t);
+        (from + (self.target - from) * eased, t >= 1.0)
+    }
+}
+
+pub fn resize_players_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut players: Query<(Entity, &mut SizeTransition, &mut Transform)>,
+) {
+    for (entity, mut transition, mut transform) in players.iter_mut() {
+        let (scale, done) = transition.advance(transform.scale.x, time.delta_seconds());
+        transform.scale = Vec3::new(scale, scale, 1.0);
+        if done {
+            commands.entity(entity).remove::<SizeTransition>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players_grow_smoothly_to_their_new_size() {
+        let mut transition = SizeTransition::new(2.0);
+        let (scale, done) = transition.advance(1.0, RESIZE_DURATION / 2.);
+        assert_eq!((scale, done), (1.5, false));
+        // The scale it started from is kept, not the one drawn meanwhile.
+        let (scale, done) = transition.advance(scale, RESIZE_DURATION);
+        assert_eq!((scale, done), (2.0, true));
+    }
+
+    #[test]
+    fn players_shrink_back() {
+        let mut transition = SizeTransition::new(0.5);
+        let (scale, done) = transition.advance(2.0, RESIZE_DURATION / 4.);
+        assert!(scale < 2.0 && scale > 1.25 && !done, "{}", scale);
+    }
+}
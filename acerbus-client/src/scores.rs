@@ -0,0 +1,83 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::chat::ChatHistory;
+use crate::layers::{UiLayer, HUD_Z};
+use crate::GameAssets;
+
+/// The number of players shown on the leaderboard.
+const LEADERBOARD_LEN: usize = 3;
+
+/// The number of players this player eliminated, as last sent by the server.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct Score(pub u32);
+
+#[derive(Component)]
+pub struct Leaderboard;
+
+/// Returns the text of the leaderboard, the best players first and one per line.
+pub fn leaderboard_text(mut scores: Vec<(String, u32)>, len: usize) -> String {
+    scores.sort_by(|(name_a, a), (name_b, b)| b.cmp(a).then_with(|| name_a.cmp(name_b)));
+    let lines: Vec<_> =
+        scores.iter().take(len).map(|(name, score)| format!("{} {}", score, name)).collect();
+    lines.join("\n")
+}
+
+/// Shows the best players in the top right corner while the server sends their scores.
+pub fn leaderboard_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    chat_history: Res<ChatHistory>,
+    scores: Query<(&Player, &Score)>,
+    mut boards: Query<(Entity, &mut Text), With<Leaderboard>>,
+) {
+    if scores.is_empty() {
+        for (entity, _) in boards.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let scores = scores.iter().map(|(player, score)| (chat_history.name(*player), score.0));
+    let text = leaderboard_text(scores.collect(), LEADERBOARD_LEN);
+    match boards.iter_mut().next() {
+        Some((_, mut board)) => {
+            if board.sections[0].value != text {
+                board.sections[0].value = text;
+            }
+        }
+        None => {
+            let text_style =
+                TextStyle { font: game_assets.font.clone(), font_size: 20.0, color: Color::WHITE };
+            commands
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: Rect { top: Val::Px(10.0), right: Val::Px(10.0), ..default() },
+                        ..default()
+                    },
+                    text: Text::with_section(text, text_style, default()),
+                    ..default()
+                })
+                .insert(Leaderboard)
+                .insert(UiLayer(HUD_Z));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_players_first() {
+        let scores = vec![("b".to_string(), 1), ("a".to_string(), 3), ("c".to_string(), 2)];
+        assert_eq!(leaderboard_text(scores, 2), "3 a\n2 c");
+    }
+
+    #[test]
+    fn ties_are_sorted_by_name() {
+        let scores = vec![("b".to_string(), 1), ("a".to_string(), 1)];
+        assert_eq!(leaderboard_text(scores, 3), "1 a\n1 b");
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::controls::{AxisMirror, ControlScheme, DirectionKeys};
+
+/// What the client remembers from one run to the other, stored as `key = value` lines.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct ClientSettings {
+    /// The tutorial has already been shown and dismissed.
+    pub tutorial_seen: bool,
+    /// The keys chosen in the controls menu.
+    pub controls: ControlScheme,
+    pub custom_keys: Option<DirectionKeys>,
+    pub mirror: AxisMirror,
+}
+
+impl ClientSettings {
+    /// Reads the settings, the missing file and the unknown or invalid lines are ignored.
+    pub fn load(path: &Path) -> ClientSettings {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        ClientSettings::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> ClientSettings {
+        let mut settings = ClientSettings::default();
+        for line in content.lines() {
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim();
+                match key.trim() {
+                    "tutorial_seen" => parse_into(value, &mut settings.tutorial_seen),
+                    "controls" => parse_into(value, &mut settings.controls),
+                    "custom_keys" => settings.custom_keys = value.parse().ok(),
+                    "mirror_x" => parse_into(value, &mut settings.mirror.x),
+                    "mirror_y" => parse_into(value, &mut settings.mirror.y),
+                    "swap_axes" => parse_into(value, &mut settings.mirror.swap),
+                    _ => (),
+                }
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut content = String::new();
+        writeln!(content, "tutorial_seen = {}", self.tutorial_seen).unwrap();
+        writeln!(content, "controls = {}", self.controls).unwrap();
+        if let Some(keys) = self.custom_keys {
+            writeln!(content, "custom_keys = {}", keys).unwrap();
+        }
+        writeln!(content, "mirror_x = {}", self.mirror.x).unwrap();
+        writeln!(content, "mirror_y = {}", self.mirror.y).unwrap();
+        writeln!(content, "swap_axes = {}", self.mirror.swap).unwrap();
+        fs::write(path, content)
+    }
+}
+
+/// Keeps the value as it is if the setting is invalid.
+fn parse_into<T: std::str::FromStr>(value: &str, setting: &mut T) {
+    if let Ok(value) = value.parse() {
+        *setting = value;
+    }
+}
+
+/// The settings of this run and where they are saved when they change.
+pub struct SettingsFile {
+    pub path: PathBuf,
+    pub settings: ClientSettings,
+}
+
+impl SettingsFile {
+    pub fn save(&self) {
+        if let Err(e) = self.settings.save(&self.path) {
+            warn!("Failed to save the settings to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip() {
+        let path = std::env::temp_dir().join(format!("acerbus-settings-{}", std::process::id()));
+        assert_eq!(ClientSettings::load(&path), ClientSettings::default());
+
+        let settings = ClientSettings { tutorial_seen: true };
+        settings.save(&path).unwrap();
+        assert_eq!(ClientSettings::load(&path), settings);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_lines_are_ignored() {
+        let settings = ClientSettings::parse("garbage\nunknown = 3\n tutorial_seen=true \n");
+        assert!(settings.tutorial_seen);
+        assert!(!ClientSettings::parse("tutorial_seen = yes").tutorial_seen);
+    }
+}
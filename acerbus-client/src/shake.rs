@@ -0,0 +1,104 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::connecting::LocalPlayer;
+use crate::smoothing::LogicalPosition;
+
+/// How much the velocity of our player must change, without us changing our input,
+/// to be considered as a collision, as a ratio of the speed of the players.
+const IMPACT_VELOCITY_CHANGE: f32 = 0.75;
+/// How many times per second the camera goes back and forth while shaking.
+const SHAKE_FREQUENCY: f64 = 30.0;
+
+/// Shakes the camera for a short time when our player collides with something,
+/// the shake starts at `intensity` pixels and fades out over `duration` seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraShake {
+    pub intensity: f32,
+    pub duration: f32,
+    remaining: f32,
+}
+
+impl CameraShake {
+    pub fn new(intensity: f32, duration: f32) -> CameraShake {
+        CameraShake { intensity, duration, remaining: 0.0 }
+    }
+
+    pub fn start(&mut self) {
+        self.remaining = self.duration;
+    }
+
+    pub fn advance(&mut self, delta: f32) {
+        self.remaining = (self.remaining - delta).max(0.0);
+    }
+
+    /// The offset to add to the camera at `time`, it decays to zero at the end of the shake.
+    pub fn offset(&self, time: f64) -> Vec2 {
+        if self.duration <= 0.0 || self.remaining <= 0.0 {
+            return Vec2::ZERO;
+        }
+        let fade = self.remaining / self.duration;
+        let phase = time * SHAKE_FREQUENCY;
+        Vec2::new(phase.sin() as f32, (phase * 1.3).cos() as f32) * self.intensity * fade * fade
+    }
+}
+
+/// Starts a shake when the velocity of our player changes suddenly while our input didn't,
+/// which means that something stopped or pushed it.
+pub fn detect_impacts_system(
+    time: Res<Time>,
+    local_player: Res<LocalPlayer>,
+    lobby: Res<Lobby>,
+    player_input: Res<PlayerInput>,
+    mut shake: ResMut<CameraShake>,
+    positions: Query<&LogicalPosition, Changed<LogicalPosition>>,
+    mut last: Local<Option<(Vec2, f64, Vec2, PlayerInput)>>,
+) {
+    shake.advance(time.delta_seconds());
+
+    let entity = local_player.player.and_then(|player| lobby.players.get(&player));
+    let position = match entity.and_then(|entity| positions.get(*entity).ok()) {
+        Some(position) => position.0,
+        None => return,
+    };
+
+    let now = time.seconds_since_startup();
+    let (mut velocity, input) = (Vec2::ZERO, *player_input);
+    if let Some((last_position, last_time, last_velocity, last_input)) = *last {
+        if now > last_time {
+            velocity = (position - last_position) / (now - last_time) as f32;
+        }
+        let change = (velocity - last_velocity).length();
+        if input == last_input && change > IMPACT_VELOCITY_CHANGE * PLAYER_MOVE_SPEED {
+            shake.start();
+        }
+    }
+    *last = Some((position, now, velocity, input));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_shake_fades_out() {
+        let mut shake = CameraShake::new(10.0, 0.5);
+        assert_eq!(shake.offset(0.1), Vec2::ZERO);
+
+        shake.start();
+        let time = 0.05;
+        let strong = shake.offset(time);
+        assert!(strong != Vec2::ZERO && strong.abs().max_element() <= 10.0);
+        shake.advance(0.25);
+        assert_eq!(shake.offset(time), strong / 4.0);
+        shake.advance(0.25);
+        assert_eq!(shake.offset(time), Vec2::ZERO);
+    }
+
+    #[test]
+    fn a_disabled_shake_never_moves_the_camera() {
+        let mut shake = CameraShake::new(10.0, 0.0);
+        shake.start();
+        assert_eq!(shake.offset(0.05), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+use crate::layers::PLAYER_Z;
+
+/// The part of the correction still visible when the configured number of frames is over.
+const REMAINING_CORRECTION: f32 = 0.01;
+
+/// Over how many frames the corrections of the local player position are smoothed.
+pub struct CorrectionSmoothing {
+    pub frames: u32,
+}
+
+/// Where the server says the local player is, the rendered position catches up with it.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct LogicalPosition(pub Vec2);
+
+/// The visible error between the rendered and the logical positions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Component)]
+pub struct CorrectionOffset {
+    pub offset: Vec2,
+    /// The number of frames after which the offset is gone.
+    pub frames_left: u32,
+}
+
+/// Returns the offset that remains after a frame, it reaches zero after `frames` frames.
+pub fn decay_correction(correction: CorrectionOffset, frames: u32) -> CorrectionOffset {
+    match correction.frames_left {
+        0 | 1 => CorrectionOffset::default(),
+        frames_left => CorrectionOffset {
+            offset: correction.offset * REMAINING_CORRECTION.powf(1.0 / frames as f32),
+            frames_left: frames_left - 1,
+        },
+    }
+}
+
+/// Render the players at their logical position plus a decaying correction offset.
+pub fn smooth_correction_system(
+    smoothing: Res<CorrectionSmoothing>,
+    mut query: Query<(
+        ChangeTrackers<LogicalPosition>,
+        &LogicalPosition,
+        &mut CorrectionOffset,
+        &mut Transform,
+    )>,
+) {
+    for (tracker, logical, mut correction, mut transform) in query.iter_mut() {
+        if tracker.is_added() {
+            *correction = CorrectionOffset::default();
+        } else if tracker.is_changed() {
+            let offset = transform.translation.truncate() - logical.0;
+            *correction = CorrectionOffset { offset, frames_left: smoothing.frames };
+        }
+
+        *correction = decay_correction(*correction, smoothing.frames);
+        transform.translation = (logical.0 + correction.offset).extend(PLAYER_Z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrections_are_gone_after_the_frames() {
+        let frames = 5;
+        let mut correction =
+            CorrectionOffset { offset: Vec2::new(200., -80.), frames_left: frames };
+        for _ in 1..frames {
+            let previous = correction.offset;
+            correction = decay_correction(correction, frames);
+            assert!(correction.offset.length() < previous.length());
+            assert_ne!(correction.offset, Vec2::ZERO);
+        }
+        correction = decay_correction(correction, frames);
+        assert_eq!(correction, CorrectionOffset::default());
+    }
+
+    #[test]
+    fn corrections_snap_without_smoothing() {
+        let correction = CorrectionOffset { offset: Vec2::new(200., -80.), frames_left: 0 };
+        assert_eq!(decay_correction(correction, 0), CorrectionOffset::default());
+    }
+}
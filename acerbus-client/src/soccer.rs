@@ -0,0 +1,103 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::layers::{UiLayer, GOAL_Z, HUD_Z};
+use crate::{team_color, GameAssets};
+
+/// The score of every team, as last sent by the server in the soccer mode.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TeamScores(pub Vec<u32>);
+
+#[derive(Component)]
+pub struct Goal;
+
+#[derive(Component)]
+pub struct Scoreboard;
+
+/// Returns the text of the scoreboard, the scores of the teams from the first one.
+pub fn scoreboard_text(scores: &[u32]) -> String {
+    let scores: Vec<_> = scores.iter().map(u32::to_string).collect();
+    scores.join(" - ")
+}
+
+/// Draws the goals of the soccer mode, in the color of the team defending them,
+/// and draws them again when the arena is resized.
+pub fn draw_goals_system(
+    mut commands: Commands,
+    mode: Option<Res<GameMode>>,
+    arena: Option<Res<Arena>>,
+    goals: Query<Entity, With<Goal>>,
+    mut drawn: Local<Option<Arena>>,
+) {
+    let arena = match (mode.as_deref(), arena.as_deref()) {
+        (Some(GameMode::Soccer), Some(arena)) => Some(*arena),
+        _ => None,
+    };
+    if *drawn == arena {
+        return;
+    }
+
+    for entity in goals.iter() {
+        commands.entity(entity).despawn();
+    }
+    if let Some(arena) = arena {
+        for goal in goal_zones(arena) {
+            let mut color = team_color(Some(goal.defended_by));
+            color.set_a(0.3);
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite { color, custom_size: Some(goal.half_size * 2.), ..default() },
+                    transform: Transform::from_translation(goal.center.extend(GOAL_Z)),
+                    ..default()
+                })
+                .insert(Goal);
+        }
+    }
+    *drawn = arena;
+}
+
+/// Shows the scores on top of the screen while the server sends them.
+pub fn scoreboard_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    scores: Option<Res<TeamScores>>,
+    mut boards: Query<(Entity, &mut Text), With<Scoreboard>>,
+) {
+    let scores = match scores {
+        Some(scores) => scores,
+        None => {
+            for (entity, _) in boards.iter() {
+                commands.entity(entity).despawn();
+            }
+            return;
+        }
+    };
+    if !scores.is_changed() {
+        return;
+    }
+
+    let text = scoreboard_text(&scores.0);
+    match boards.iter_mut().next() {
+        Some((_, mut board)) => board.sections[0].value = text,
+        None => {
+            let text_style =
+                TextStyle { font: game_assets.font.clone(), font_size: 32.0, color: Color::WHITE };
+            commands
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            top: Val::Px(10.0),
+                            left: Val::Percent(48.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    text: Text::with_section(text, text_style, default()),
+                    ..default()
+                })
+                .insert(Scoreboard)
+                .insert(UiLayer(HUD_Z));
+        }
+    }
+}
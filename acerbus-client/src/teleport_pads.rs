@@ -0,0 +1,39 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::layers::TELEPORT_PAD_Z;
+
+/// The teleport pads of the arena, as last sent by the server.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TeleportPads(pub Vec<TeleportPad>);
+
+/// Draws the teleport pads of the arena and draws them again when the server moves them.
+pub fn draw_teleport_pads_system(
+    mut commands: Commands,
+    pads: Option<Res<TeleportPads>>,
+    drawn_pads: Query<Entity, With<TeleportPad>>,
+    mut drawn: Local<Option<TeleportPads>>,
+) {
+    let pads = pads.as_deref().cloned();
+    if *drawn == pads {
+        return;
+    }
+
+    for entity in drawn_pads.iter() {
+        commands.entity(entity).despawn();
+    }
+    for pad in pads.iter().flat_map(|pads| &pads.0) {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::TEAL,
+                    custom_size: Some(Vec2::splat(TELEPORT_PAD_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(pad.position.extend(TELEPORT_PAD_Z)),
+                ..default()
+            })
+            .insert(*pad);
+    }
+    *drawn = pads;
+}
@@ -0,0 +1,105 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetClient;
+
+use crate::collisions::{resolve_wall_collisions, ClientCollisions};
+use crate::connecting::LocalPlayer;
+use crate::debug_hud::ServerTickRate;
+use crate::fixed_step::{ClientTick, FixedStepClock, SimulatedPosition, DEFAULT_STEP_RATE};
+use crate::input_delay::DelayedInputs;
+
+/// Returns how far our player moves in a step of `step` seconds, only its movement is
+/// predicted, the actions like the dash are left to the server which may reject them.
+pub fn predicted_delta(input: PlayerInput, speed: Option<&MoveSpeed>, step: f32) -> Vec2 {
+    input_velocity(&input.movement(), speed) * step
+}
+
+/// Moves our player ourselves and sends its position to the server, only when the server
+/// trusts the positions of the clients, the positions it sends us back are then ignored.
+/// The player moves by fixed steps at the tick rate of the server and is rendered between them.
+#[allow(clippy::too_many_arguments)]
+pub fn trusted_movement_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    local_player: Res<LocalPlayer>,
+    lobby: Res<Lobby>,
+    player_input: Res<PlayerInput>,
+    client_tick: Option<Res<ClientTick>>,
+    delayed_inputs: Option<ResMut<DelayedInputs>>,
+    collisions: Res<ClientCollisions>,
+    tick_rate: Option<Res<ServerTickRate>>,
+    arena: Option<Res<Arena>>,
+    mut clock: ResMut<FixedStepClock>,
+    mut client: ResMut<RenetClient>,
+    mut transforms: Query<
+        (&mut Transform, Option<&MoveSpeed>, Option<&SimulatedPosition>),
+        With<Player>,
+    >,
+) {
+    if !local_player.trusted {
+        return;
+    }
+
+    let entity = match local_player.player.and_then(|player| lobby.players.get(&player)) {
+        Some(entity) => *entity,
+        None => return,
+    };
+    let (mut transform, speed, simulated) = match transforms.get_mut(entity) {
+        Ok(found) => found,
+        Err(_) => return,
+    };
+
+    let rendered = transform.translation.truncate();
+    let mut simulated = match simulated {
+        // Something else moved our player, a teleport or a correction, we restart from there.
+        Some(simulated) if simulated.rendered == rendered => *simulated,
+        _ => SimulatedPosition::new(rendered),
+    };
+
+    // Our player moves with our inputs once their tick comes, like on the server.
+    let input = match (delayed_inputs, client_tick) {
+        (Some(mut delayed_inputs), Some(client_tick)) => {
+            delayed_inputs.input_at(client_tick.at(time.seconds_since_startup()))
+        }
+        _ => *player_input,
+    };
+    let step = 1.0 / tick_rate.map_or(DEFAULT_STEP_RATE, |rate| rate.0);
+    let steps = clock.advance(time.delta_seconds_f64(), step);
+    for _ in 0..steps {
+        let delta = predicted_delta(input, speed, step as f32);
+        let mut position = simulated.current + delta;
+        if let (true, Some(arena)) = (collisions.0, arena.as_deref()) {
+            let scale = transform.scale.truncate();
+            position = resolve_wall_collisions(position, local_player.body_shape, scale, *arena);
+        }
+        simulated.step(position);
+    }
+
+    let position = simulated.render(clock.alpha(step));
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
+    commands.entity(entity).insert(simulated);
+
+    if steps > 0 {
+        let message = encode(&ClientMessage::Position(simulated.current));
+        client.send_message(CLIENT_INPUT_CHANNEL, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dashes_are_not_predicted() {
+        let moving = PlayerInput { right: true, ..PlayerInput::default() };
+        let dashing = PlayerInput { dash: true, ..moving };
+        let step = 1. / 60.;
+
+        // The server may reject the dash, we keep predicting the movement only.
+        assert_eq!(predicted_delta(dashing, None, step), predicted_delta(moving, None, step));
+        assert!(predicted_delta(dashing, None, step).x > 0.);
+        let standing = PlayerInput { dash: true, ..PlayerInput::default() };
+        assert_eq!(predicted_delta(standing, None, step), Vec2::ZERO);
+    }
+}
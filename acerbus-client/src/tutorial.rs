@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+
+use crate::browser::BROWSER_TOGGLE_KEY;
+use crate::controls::{CONTROLS_MENU_KEY, DASH_KEY};
+use crate::debug_hud::DEBUG_HUD_TOGGLE_KEY;
+use crate::free_camera::FREE_CAMERA_TOGGLE_KEY;
+use crate::layers::{UiLayer, MENU_Z};
+use crate::settings::SettingsFile;
+use crate::GameAssets;
+
+const TUTORIAL_DISMISS_KEY: KeyCode = KeyCode::Space;
+
+/// The overlay explaining the controls, shown on the first run only unless asked for.
+pub struct Tutorial {
+    pub show: bool,
+}
+
+#[derive(Component)]
+pub struct TutorialOverlay;
+
+/// Returns the text of the tutorial, with the keys the systems really listen to.
+pub fn tutorial_text() -> String {
+    format!(
+        "Welcome to acerbus!\n\
+         \n\
+         WASD or the arrows, or a gamepad, to move, or the keys of the controls\n\
+         {:?}, or the south button of the gamepad, to dash\n\
+         {:?} to look around with the free camera\n\
+         {:?} to show the debug overlay\n\
+         {:?} to browse the servers\n\
+         {:?} to change the controls\n\
+         Page Up and Page Down to scroll the chat\n\
+         \n\
+         Press {:?} to start playing",
+        DASH_KEY,
+        FREE_CAMERA_TOGGLE_KEY,
+        DEBUG_HUD_TOGGLE_KEY,
+        BROWSER_TOGGLE_KEY,
+        CONTROLS_MENU_KEY,
+        TUTORIAL_DISMISS_KEY,
+    )
+}
+
+pub fn setup_tutorial(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    tutorial: Res<Tutorial>,
+) {
+    if !tutorial.show {
+        return;
+    }
+
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 20.0, color: Color::WHITE };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(40.0), top: Val::Px(40.0), ..default() },
+                padding: Rect::all(Val::Px(16.0)),
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..default()
+        })
+        .insert(TutorialOverlay)
+        .insert(UiLayer(MENU_Z))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(tutorial_text(), text_style, default()),
+                ..default()
+            });
+        });
+}
+
+/// Closes the tutorial and remembers that it must not be shown again.
+pub fn dismiss_tutorial_system(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut tutorial: ResMut<Tutorial>,
+    mut settings: ResMut<SettingsFile>,
+    overlays: Query<Entity, With<TutorialOverlay>>,
+) {
+    if !tutorial.show || !keyboard_input.just_pressed(TUTORIAL_DISMISS_KEY) {
+        return;
+    }
+
+    for overlay in overlays.iter() {
+        commands.entity(overlay).despawn_recursive();
+    }
+    tutorial.show = false;
+    settings.settings.tutorial_seen = true;
+    settings.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_tutorial_names_the_real_keys() {
+        let text = tutorial_text();
+        for key in [FREE_CAMERA_TOGGLE_KEY, DEBUG_HUD_TOGGLE_KEY, BROWSER_TOGGLE_KEY] {
+            assert!(text.contains(&format!("{:?} to", key)), "{:?} is missing", key);
+        }
+        assert!(text.ends_with(&format!("Press {:?} to start playing", TUTORIAL_DISMISS_KEY)));
+    }
+
+    #[test]
+    fn the_dismissed_tutorial_is_not_shown_again() {
+        let path = std::env::temp_dir().join(format!("acerbus-tutorial-{}", std::process::id()));
+        let mut app = App::new();
+        let settings = ClientSettings::default();
+        app.insert_resource(Tutorial { show: true, settings, settings_path: path.clone() });
+        let mut keyboard_input = Input::<KeyCode>::default();
+        keyboard_input.press(TUTORIAL_DISMISS_KEY);
+        app.insert_resource(keyboard_input);
+        let overlay = app.world.spawn().insert(TutorialOverlay).id();
+        app.add_system(dismiss_tutorial_system);
+        app.update();
+
+        assert!(!app.world.resource::<Tutorial>().show);
+        assert!(app.world.get_entity(overlay).is_none());
+        assert!(ClientSettings::load(&path).tutorial_seen);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
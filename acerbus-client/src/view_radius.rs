@@ -0,0 +1,25 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetClient;
+
+use crate::connecting::LocalPlayer;
+
+/// How far around our player we want to see the others, the server decides how far we can.
+pub struct ViewRadius(pub f32);
+
+/// Asks for our view radius once welcomed, again after every reconnection.
+/// It runs even when disconnected to notice that we are no more welcomed.
+pub fn send_view_radius_system(
+    client: Option<ResMut<RenetClient>>,
+    view_radius: Res<ViewRadius>,
+    local_player: Res<LocalPlayer>,
+    mut sent: Local<bool>,
+) {
+    if !local_player.welcomed {
+        *sent = false;
+    } else if let (false, Some(mut client)) = (*sent, client) {
+        let message = encode(&ClientMessage::ViewRadius(view_radius.0));
+        client.send_message(CLIENT_MESSAGES_CHANNEL, message);
+        *sent = true;
+    }
+}
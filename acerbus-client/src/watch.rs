@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_asset_loader::AssetCollectionApp;
+
+use crate::free_camera::{free_camera_system, CameraMode};
+use crate::layers::PLAYER_Z;
+use crate::{setup, spawn_player_cube, GameAssets, PlayerLook};
+
+const REPLAY_PAUSE_KEY: KeyCode = KeyCode::Space;
+const REPLAY_BACKWARD_KEY: KeyCode = KeyCode::Comma;
+const REPLAY_FORWARD_KEY: KeyCode = KeyCode::Period;
+/// How far, in seconds, the scrub keys move in the replay.
+const REPLAY_SCRUB_SECONDS: f64 = 5.0;
+
+/// A recorded session played back locally, without any server.
+pub struct ReplayViewer {
+    pub replay: Replay,
+    /// Where we are in the replay, in ticks, it advances at the tick rate of the recording.
+    pub tick: f64,
+    pub paused: bool,
+}
+
+impl ReplayViewer {
+    pub fn tick_rate(&self) -> f64 {
+        if self.replay.tick_rate > 0. {
+            self.replay.tick_rate
+        } else {
+            60.0
+        }
+    }
+
+    /// Moves in the replay by this many seconds, staying within it.
+    pub fn advance(&mut self, seconds: f64) {
+        let last = self.replay.ticks.len().saturating_sub(1) as f64;
+        self.tick = (self.tick + seconds * self.tick_rate()).clamp(0.0, last);
+    }
+
+    /// The players and their positions at the current tick.
+    pub fn current(&self) -> Option<&ReplayTick> {
+        self.replay.ticks.get(self.tick as usize)
+    }
+}
+
+#[derive(Component)]
+pub struct ReplayText;
+
+/// Plays a replay back in a window, the camera is moved freely with the arrows and the wheel.
+pub fn watch_replay(path: &Path) {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("Failed to load the replay {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    app.init_collection::<GameAssets>();
+    app.insert_resource(Lobby::default());
+    app.insert_resource(ReplayViewer { replay, tick: 0.0, paused: false });
+    app.insert_resource(CameraMode::Free);
+    app.add_startup_system(setup);
+    app.add_startup_system(setup_replay_text);
+    app.add_system(free_camera_system);
+    app.add_system(replay_controls_system.label("replay_controls"));
+    app.add_system(replay_players_system.after("replay_controls"));
+    app.run();
+}
+
+/// Pause with Space, go backward and forward with the comma and the period.
+pub fn replay_controls_system(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut viewer: ResMut<ReplayViewer>,
+) {
+    if keyboard_input.just_pressed(REPLAY_PAUSE_KEY) {
+        viewer.paused = !viewer.paused;
+    }
+    if keyboard_input.just_pressed(REPLAY_BACKWARD_KEY) {
+        viewer.advance(-REPLAY_SCRUB_SECONDS);
+    } else if keyboard_input.just_pressed(REPLAY_FORWARD_KEY) {
+        viewer.advance(REPLAY_SCRUB_SECONDS);
+    }
+    if !viewer.paused {
+        viewer.advance(time.delta_seconds_f64());
+    }
+}
+
+/// Spawns the players of the current tick, despawns the ones that left and moves the others.
+#[allow(clippy::too_many_arguments)]
+pub fn replay_players_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    viewer: Res<ReplayViewer>,
+    mut lobby: ResMut<Lobby>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut transforms: Query<&mut Transform, With<Player>>,
+    mut texts: Query<&mut Text, With<ReplayText>>,
+) {
+    let tick = match viewer.current() {
+        Some(tick) => tick,
+        None => return,
+    };
+
+    lobby.players.retain(|player, entity| {
+        let stays = tick.positions.contains_key(player);
+        if !stays {
+            commands.entity(*entity).despawn_recursive();
+        }
+        stays
+    });
+
+    for (player, position) in &tick.positions {
+        let translation = position.extend(PLAYER_Z);
+        match lobby.players.get(player).and_then(|entity| transforms.get_mut(*entity).ok()) {
+            Some(mut transform) => transform.translation = translation,
+            None if !lobby.players.contains_key(player) => {
+                let entity = spawn_player_cube(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    &game_assets,
+                    *player,
+                    PlayerLook::default(),
+                );
+                commands.entity(entity).insert(Transform::from_translation(translation));
+                lobby.players.insert(*player, entity);
+            }
+            None => (),
+        }
+    }
+
+    let seconds = |ticks: f64| ticks / viewer.tick_rate();
+    let total = viewer.replay.ticks.len() as f64;
+    let state = if viewer.paused { "paused" } else { "playing" };
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!(
+            "{:.1}s / {:.1}s, {}, {} players - Space to pause, comma and period to scrub",
+            seconds(viewer.tick),
+            seconds(total),
+            state,
+            tick.positions.len(),
+        );
+    }
+}
+
+fn setup_replay_text(mut commands: Commands, game_assets: Res<GameAssets>) {
+    let text_style =
+        TextStyle { font: game_assets.font.clone(), font_size: 18.0, color: Color::WHITE };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(10.0), top: Val::Px(10.0), ..default() },
+                ..default()
+            },
+            text: Text::with_section(String::new(), text_style, default()),
+            ..default()
+        })
+        .insert(ReplayText);
+}
@@ -0,0 +1,48 @@
+use std::net::SocketAddr;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::connecting::{ConnectParams, ConnectionStatus};
+
+pub const GAME_NAME: &str = "Acerbus";
+
+/// Returns the title of the window, the server we play on and how many players are in it.
+pub fn window_title(server_addr: Option<SocketAddr>, players: usize) -> String {
+    match server_addr {
+        Some(addr) if players == 1 => format!("{} - {} - 1 player", GAME_NAME, addr),
+        Some(addr) => format!("{} - {} - {} players", GAME_NAME, addr, players),
+        None => format!("{} - Not connected", GAME_NAME),
+    }
+}
+
+/// Keeps the title of the window up to date as the players join and leave.
+pub fn window_title_system(
+    status: Res<ConnectionStatus>,
+    params: Res<ConnectParams>,
+    lobby: Res<Lobby>,
+    mut windows: ResMut<Windows>,
+    mut last_title: Local<String>,
+) {
+    let server_addr = (*status == ConnectionStatus::Connected).then(|| params.server_addr);
+    let title = window_title(server_addr, lobby.players.len());
+    if *last_title != title {
+        if let Some(window) = windows.get_primary_mut() {
+            window.set_title(title.clone());
+        }
+        *last_title = title;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_title_shows_the_server_and_the_players() {
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        assert_eq!(window_title(Some(addr), 1), "Acerbus - 127.0.0.1:5000 - 1 player");
+        assert_eq!(window_title(Some(addr), 3), "Acerbus - 127.0.0.1:5000 - 3 players");
+        assert_eq!(window_title(None, 3), "Acerbus - Not connected");
+    }
+}
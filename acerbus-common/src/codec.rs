@@ -0,0 +1,142 @@
+use std::any::type_name;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Prefixes every message, a receiver skips the messages encoded with another schema.
+pub const MESSAGE_SCHEMA_VERSION: u8 = 35;
+
+/// The biggest message that fits in a single UDP datagram without being fragmented.
+pub const DEFAULT_SAFE_MESSAGE_BYTES: usize = 1200;
+
+static SAFE_MESSAGE_BYTES: AtomicUsize = AtomicUsize::new(DEFAULT_SAFE_MESSAGE_BYTES);
+
+/// Changes the size above which the encoded messages are reported, in debug builds.
+pub fn set_safe_message_bytes(bytes: usize) {
+    SAFE_MESSAGE_BYTES.store(bytes, Ordering::Relaxed);
+}
+
+/// A message that was bigger than the safe size once encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OversizedMessage {
+    pub type_name: &'static str,
+    pub size: usize,
+    pub max_size: usize,
+}
+
+impl fmt::Display for OversizedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a {} message of {} bytes exceeds the safe size of {} bytes",
+            self.type_name, self.size, self.max_size
+        )
+    }
+}
+
+/// Returns the report of a `T` message of `size` bytes if it is bigger than `max_size`.
+pub fn check_message_size<T>(size: usize, max_size: usize) -> Option<OversizedMessage> {
+    (size > max_size).then(|| OversizedMessage { type_name: type_name::<T>(), size, max_size })
+}
+
+/// Why a received message was skipped.
+#[derive(Debug)]
+pub enum DecodeError {
+    Empty,
+    InvalidLength(usize),
+    UnknownSchema(u8),
+    Malformed(bincode::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Empty => write!(f, "the message is empty"),
+            DecodeError::InvalidLength(len) => write!(f, "the message length {} is invalid", len),
+            DecodeError::UnknownSchema(version) => {
+                write!(f, "the message schema {} is not {}", version, MESSAGE_SCHEMA_VERSION)
+            }
+            DecodeError::Malformed(error) => write!(f, "the message is malformed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Serializes a message to send it, in debug builds we warn when it is too big.
+pub fn encode<T: Serialize>(message: &T) -> Vec<u8> {
+    let mut bytes = vec![MESSAGE_SCHEMA_VERSION];
+    bincode::serialize_into(&mut bytes, message).unwrap();
+    if cfg!(debug_assertions) {
+        let max_size = SAFE_MESSAGE_BYTES.load(Ordering::Relaxed);
+        if let Some(oversized) = check_message_size::<T>(bytes.len(), max_size) {
+            warn!("{}", oversized);
+        }
+    }
+    bytes
+}
+
+/// Deserializes a received message, the messages we don't understand
+/// must be skipped, they may have been sent by a more recent peer.
+pub fn try_decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DecodeError> {
+    match bytes.split_first() {
+        None => Err(DecodeError::Empty),
+        Some((&MESSAGE_SCHEMA_VERSION, message)) => {
+            bincode::deserialize(message).map_err(DecodeError::Malformed)
+        }
+        Some((&version, _)) => Err(DecodeError::UnknownSchema(version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum OldMessage {
+        Ping,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum NewMessage {
+        Ping,
+        Pong(u32),
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let bytes = encode(&NewMessage::Pong(42));
+        assert_eq!(bytes[0], MESSAGE_SCHEMA_VERSION);
+        assert_eq!(try_decode::<NewMessage>(&bytes).unwrap(), NewMessage::Pong(42));
+    }
+
+    #[test]
+    fn unknown_messages_are_skipped() {
+        // A variant added by a more recent peer.
+        let bytes = encode(&NewMessage::Pong(42));
+        assert!(matches!(try_decode::<OldMessage>(&bytes), Err(DecodeError::Malformed(_))));
+        assert_eq!(try_decode::<OldMessage>(&encode(&NewMessage::Ping)).unwrap(), OldMessage::Ping);
+
+        let mut bytes = encode(&NewMessage::Ping);
+        bytes[0] = MESSAGE_SCHEMA_VERSION.wrapping_add(1);
+        assert!(matches!(try_decode::<NewMessage>(&bytes), Err(DecodeError::UnknownSchema(_))));
+        assert!(matches!(try_decode::<NewMessage>(&[]), Err(DecodeError::Empty)));
+    }
+
+    #[test]
+    fn oversized_messages_are_reported() {
+        let size = encode(&vec![0u8; DEFAULT_SAFE_MESSAGE_BYTES]).len();
+        let oversized = check_message_size::<Vec<u8>>(size, DEFAULT_SAFE_MESSAGE_BYTES).unwrap();
+        assert_eq!(oversized.size, size);
+        assert_eq!(oversized.type_name, type_name::<Vec<u8>>());
+        assert!(oversized.to_string().ends_with("exceeds the safe size of 1200 bytes"));
+
+        let size = encode(&vec![0u8; 16]).len();
+        assert_eq!(check_message_size::<Vec<u8>>(size, DEFAULT_SAFE_MESSAGE_BYTES), None);
+    }
+}
@@ -0,0 +1,63 @@
+use serde_json::{Map, Value};
+
+/// Reads a JSON object of options, like `{ "max_players": 16, "ball": true }`, and returns
+/// the flags it stands for. They are meant to be parsed before the ones of the command line,
+/// which override them, the defaults only apply to the options set nowhere.
+///
+/// A `true` gives a bare flag, a `false` or a `null` nothing and an array repeats the flag.
+pub fn config_file_args(content: &str) -> serde_json::Result<Vec<String>> {
+    let options: Map<String, Value> = serde_json::from_str(content)?;
+    let mut args = Vec::new();
+    for (name, value) in options {
+        let flag = format!("--{}", name.replace('_', "-"));
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Null | Value::Bool(false) => (),
+                Value::Bool(true) => args.push(flag.clone()),
+                Value::String(value) => args.extend([flag.clone(), value]),
+                value => args.extend([flag.clone(), value.to_string()]),
+            }
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_are_turned_into_flags() {
+        let content = r#"{
+            "arena_walls": false,
+            "ball": true,
+            "browse": ["10.0.0.1:5000", "10.0.0.2:5000"],
+            "listen_addr": "0.0.0.0:5000",
+            "max_players": 16,
+            "name": null
+        }"#;
+        let expected = [
+            "--ball",
+            "--browse",
+            "10.0.0.1:5000",
+            "--browse",
+            "10.0.0.2:5000",
+            "--listen-addr",
+            "0.0.0.0:5000",
+            "--max-players",
+            "16",
+        ];
+        assert_eq!(config_file_args(content).unwrap(), expected);
+    }
+
+    #[test]
+    fn only_objects_are_accepted() {
+        assert!(config_file_args("[\"--ball\"]").is_err());
+        assert!(config_file_args("{ \"ball\": true").is_err());
+        assert_eq!(config_file_args("{}").unwrap(), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,90 @@
+use bevy_renet::renet::NETCODE_USER_DATA_BYTES;
+use serde::{Deserialize, Serialize};
+
+use crate::{check_message_size, encode, try_decode, DecodeError, OversizedMessage};
+use crate::{SessionToken, Skin, Team};
+
+/// The number of bytes, at the start of the user data, that store the request length.
+const CONNECT_REQUEST_LEN_BYTES: usize = 2;
+
+/// Everything a client tells the server when it connects, sent in the netcode user data.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectRequest {
+    /// The token to present to get our cube back after a disconnection.
+    pub session_token: Option<SessionToken>,
+    /// The team we would like to join, the server may put us in another one.
+    pub team: Option<Team>,
+    /// The texture everyone draws our cube with.
+    pub skin: Option<Skin>,
+    /// The name we would like to be shown with, the server may refuse it.
+    pub name: Option<String>,
+    /// We kept the world from before our disconnection, the server only sends what changed.
+    pub resume: bool,
+}
+
+impl ConnectRequest {
+    pub fn to_user_data(&self) -> Result<[u8; NETCODE_USER_DATA_BYTES], OversizedMessage> {
+        let bytes = encode(self);
+        let max_size = NETCODE_USER_DATA_BYTES - CONNECT_REQUEST_LEN_BYTES;
+        if let Some(oversized) = check_message_size::<ConnectRequest>(bytes.len(), max_size) {
+            return Err(oversized);
+        }
+
+        let mut user_data = [0; NETCODE_USER_DATA_BYTES];
+        let (len, request) = user_data.split_at_mut(CONNECT_REQUEST_LEN_BYTES);
+        len.copy_from_slice(&(bytes.len() as u16).to_le_bytes());
+        request[..bytes.len()].copy_from_slice(&bytes);
+        Ok(user_data)
+    }
+
+    /// Fails if the client didn't send a request or if it is garbled.
+    pub fn from_user_data(
+        user_data: &[u8; NETCODE_USER_DATA_BYTES],
+    ) -> Result<ConnectRequest, DecodeError> {
+        let (len, request) = user_data.split_at(CONNECT_REQUEST_LEN_BYTES);
+        let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+        match request.get(..len) {
+            Some(request) => try_decode(request),
+            None => Err(DecodeError::InvalidLength(len)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connect_requests_round_trip() {
+        let request = ConnectRequest {
+            session_token: Some(SessionToken(42)),
+            team: Some(Team(u8::MAX)),
+            skin: Some(Skin::Purple),
+            name: Some("Ünïcode".to_string()),
+            resume: true,
+        };
+        let user_data = request.to_user_data().unwrap();
+        assert_eq!(ConnectRequest::from_user_data(&user_data).unwrap(), request);
+    }
+
+    #[test]
+    fn invalid_user_data_is_refused() {
+        let mut user_data = [0; NETCODE_USER_DATA_BYTES];
+        assert!(matches!(ConnectRequest::from_user_data(&user_data), Err(DecodeError::Empty)));
+
+        user_data[..CONNECT_REQUEST_LEN_BYTES].copy_from_slice(&u16::MAX.to_le_bytes());
+        let result = ConnectRequest::from_user_data(&user_data);
+        assert!(matches!(result, Err(DecodeError::InvalidLength(len)) if len == u16::MAX as usize));
+    }
+
+    #[test]
+    fn too_long_names_do_not_fit_in_the_user_data() {
+        let request = ConnectRequest {
+            name: Some("a".repeat(NETCODE_USER_DATA_BYTES)),
+            ..ConnectRequest::default()
+        };
+        let oversized = request.to_user_data().unwrap_err();
+        assert_eq!(oversized.max_size, NETCODE_USER_DATA_BYTES - CONNECT_REQUEST_LEN_BYTES);
+        assert!(oversized.size > oversized.max_size);
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{encode, try_decode, ServerInfo};
+
+/// How often the servers tell the coordinator they are still there.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What the servers and the clients send to the coordinator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CoordinatorRequest {
+    // A server is alive, the IP of its game address is taken from the packet when it is
+    // unspecified or a loopback one, which the clients couldn't reach.
+    Heartbeat { game_addr: SocketAddr, info: ServerInfo },
+    // A client looks for a server to join.
+    FindServer { nonce: u64 },
+}
+
+/// The answer to a `FindServer`, there is no server when they are all full or gone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerAssignment {
+    pub nonce: u64,
+    pub server: Option<SocketAddr>,
+}
+
+/// The servers known by the coordinator and when they last sent a heartbeat.
+#[derive(Debug)]
+pub struct ServerRegistry {
+    /// A server that didn't send a heartbeat for this long is forgotten.
+    pub timeout: Duration,
+    servers: HashMap<SocketAddr, (ServerInfo, Instant)>,
+}
+
+impl ServerRegistry {
+    pub fn new(timeout: Duration) -> ServerRegistry {
+        ServerRegistry { timeout, servers: HashMap::new() }
+    }
+
+    pub fn heartbeat(&mut self, game_addr: SocketAddr, info: ServerInfo, now: Instant) {
+        self.servers.insert(game_addr, (info, now));
+    }
+
+    /// Forgets the servers that missed their heartbeats, returns their addresses.
+    pub fn evict_stale(&mut self, now: Instant) -> Vec<SocketAddr> {
+        let timeout = self.timeout;
+        let stale: Vec<_> = self
+            .servers
+            .iter()
+            .filter(|(_, (_, seen))| now.duration_since(*seen) > timeout)
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in &stale {
+            self.servers.remove(addr);
+        }
+        stale
+    }
+
+    /// The server with the fewest players that isn't full, by address when they are tied.
+    pub fn least_loaded(&self) -> Option<SocketAddr> {
+        self.servers
+            .iter()
+            .filter(|(_, (info, _))| info.players < info.max_players)
+            .min_by_key(|(addr, (info, _))| (info.players, **addr))
+            .map(|(addr, _)| *addr)
+    }
+
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+}
+
+/// Returns the address the clients must be sent to, the game address a server registered
+/// with but reachable from the outside, using the IP the heartbeat came `from` when needed.
+pub fn advertised_addr(mut game_addr: SocketAddr, from: SocketAddr) -> SocketAddr {
+    if game_addr.ip().is_unspecified() || game_addr.ip().is_loopback() {
+        game_addr.set_ip(from.ip());
+    }
+    game_addr
+}
+
+/// Asks the coordinator for the least loaded server, waits at most `timeout` for the answer.
+pub fn find_server(coordinator: SocketAddr, timeout: Duration) -> io::Result<Option<SocketAddr>> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(timeout))?;
+    let nonce = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_nanos() as u64;
+    socket.send_to(&encode(&CoordinatorRequest::FindServer { nonce }), coordinator)?;
+
+    let mut buffer = [0; 256];
+    loop {
+        let (len, addr) = socket.recv_from(&mut buffer)?;
+        match try_decode::<ServerAssignment>(&buffer[..len]) {
+            Ok(assignment) if addr == coordinator && assignment.nonce == nonce => {
+                return Ok(assignment.server)
+            }
+            _ => continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(players: usize, max_players: usize) -> ServerInfo {
+        ServerInfo { name: String::from("acerbus"), players, max_players }
+    }
+
+    #[test]
+    fn least_loaded_skips_the_full_servers() {
+        let now = Instant::now();
+        let mut registry = ServerRegistry::new(Duration::from_secs(6));
+        let busy: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        let full: SocketAddr = "10.0.0.2:5000".parse().unwrap();
+        let quiet: SocketAddr = "10.0.0.3:5000".parse().unwrap();
+        registry.heartbeat(busy, info(5, 8), now);
+        registry.heartbeat(full, info(2, 2), now);
+        assert_eq!(registry.least_loaded(), Some(busy));
+
+        registry.heartbeat(quiet, info(1, 8), now);
+        assert_eq!(registry.least_loaded(), Some(quiet));
+    }
+
+    #[test]
+    fn servers_missing_heartbeats_are_evicted() {
+        let start = Instant::now();
+        let mut registry = ServerRegistry::new(Duration::from_secs(6));
+        let gone: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        let alive: SocketAddr = "10.0.0.2:5000".parse().unwrap();
+        registry.heartbeat(gone, info(0, 8), start);
+        registry.heartbeat(alive, info(3, 8), start);
+        registry.heartbeat(alive, info(3, 8), start + Duration::from_secs(4));
+
+        assert!(registry.evict_stale(start + Duration::from_secs(5)).is_empty());
+        assert_eq!(registry.evict_stale(start + Duration::from_secs(7)), vec![gone]);
+        assert_eq!(registry.least_loaded(), Some(alive));
+    }
+
+    #[test]
+    fn unreachable_ips_are_replaced_by_the_source_one() {
+        let from: SocketAddr = "203.0.113.7:41234".parse().unwrap();
+        let public: SocketAddr = "198.51.100.2:5000".parse().unwrap();
+        let expected: SocketAddr = "203.0.113.7:5000".parse().unwrap();
+        assert_eq!(advertised_addr("127.0.0.1:5000".parse().unwrap(), from), expected);
+        assert_eq!(advertised_addr("0.0.0.0:5000".parse().unwrap(), from), expected);
+        assert_eq!(advertised_addr(public, from), public);
+    }
+}
@@ -0,0 +1,171 @@
+use std::ops::{Add, Mul, Neg, Sub};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The number of fixed-point units in a world unit.
+pub const FIXED_SCALE: i32 = 1000;
+
+/// A number stored in thousandths of a world unit, the operations on it give the same
+/// results on every machine, unlike floats. It is meant for the deterministic code paths
+/// and converted from and to floats at the boundaries, like the rendering.
+///
+/// The operations saturate at the bounds of an `i32` instead of wrapping around, a position
+/// stuck at the edge of the world is easier to notice than one on the opposite side.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(FIXED_SCALE);
+
+    /// Rounds a float to the nearest thousandth, saturating, `NaN` gives zero.
+    pub fn from_f32(value: f32) -> Fixed {
+        Fixed((value * FIXED_SCALE as f32).round() as i32)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / FIXED_SCALE as f32
+    }
+
+    /// Divides by another fixed-point number, rounding toward zero, `None` when dividing
+    /// by zero or when the quotient doesn't fit.
+    pub fn checked_div(self, rhs: Fixed) -> Option<Fixed> {
+        let value = (self.0 as i64 * FIXED_SCALE as i64).checked_div(rhs.0 as i64)?;
+        i32::try_from(value).ok().map(Fixed)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(self.0.saturating_neg())
+    }
+}
+
+/// The product is computed on 64 bits, rounded toward zero and saturated.
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, rhs: Fixed) -> Fixed {
+        let value = self.0 as i64 * rhs.0 as i64 / FIXED_SCALE as i64;
+        Fixed(value.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+}
+
+/// A position or a velocity in fixed-point.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FixedVec2 {
+    pub x: Fixed,
+    pub y: Fixed,
+}
+
+impl FixedVec2 {
+    pub const ZERO: FixedVec2 = FixedVec2 { x: Fixed::ZERO, y: Fixed::ZERO };
+
+    pub fn new(x: Fixed, y: Fixed) -> FixedVec2 {
+        FixedVec2 { x, y }
+    }
+
+    pub fn from_vec2(vec: Vec2) -> FixedVec2 {
+        FixedVec2 { x: Fixed::from_f32(vec.x), y: Fixed::from_f32(vec.y) }
+    }
+
+    pub fn to_vec2(self) -> Vec2 {
+        Vec2::new(self.x.to_f32(), self.y.to_f32())
+    }
+}
+
+impl Add for FixedVec2 {
+    type Output = FixedVec2;
+
+    fn add(self, rhs: FixedVec2) -> FixedVec2 {
+        FixedVec2 { x: self.x + rhs.x, y: self.y + rhs.y }
+    }
+}
+
+impl Sub for FixedVec2 {
+    type Output = FixedVec2;
+
+    fn sub(self, rhs: FixedVec2) -> FixedVec2 {
+        FixedVec2 { x: self.x - rhs.x, y: self.y - rhs.y }
+    }
+}
+
+/// Scales a vector, for example a velocity by a delta time to get a displacement.
+impl Mul<Fixed> for FixedVec2 {
+    type Output = FixedVec2;
+
+    fn mul(self, rhs: Fixed) -> FixedVec2 {
+        FixedVec2 { x: self.x * rhs, y: self.y * rhs }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_is_exact_to_the_thousandth() {
+        let a = Fixed::from_f32(1.5);
+        let b = Fixed::from_f32(-0.25);
+        assert_eq!(a + b, Fixed(1250));
+        assert_eq!(a - b, Fixed(1750));
+        assert_eq!(-a, Fixed(-1500));
+        assert_eq!(a * b, Fixed(-375));
+        assert_eq!(a * Fixed::ONE, a);
+        // The products and quotients are rounded toward zero.
+        assert_eq!(Fixed(1) * Fixed(500), Fixed::ZERO);
+        assert_eq!(Fixed::ONE.checked_div(Fixed(3000)), Some(Fixed(333)));
+        assert_eq!(Fixed(-1000).checked_div(Fixed(3000)), Some(Fixed(-333)));
+        assert_eq!(a.checked_div(Fixed::ZERO), None);
+    }
+
+    #[test]
+    fn arithmetic_saturates() {
+        let max = Fixed(i32::MAX);
+        let min = Fixed(i32::MIN);
+        assert_eq!(max + Fixed::ONE, max);
+        assert_eq!(min - Fixed::ONE, min);
+        assert_eq!(-min, max);
+        assert_eq!(max * Fixed::from_f32(2.), max);
+        assert_eq!(max * Fixed::from_f32(-2.), min);
+        assert_eq!(max.checked_div(Fixed(1)), None);
+        assert_eq!(Fixed::from_f32(f32::INFINITY), max);
+        assert_eq!(Fixed::from_f32(f32::NAN), Fixed::ZERO);
+    }
+
+    #[test]
+    fn vectors_round_trip_within_a_thousandth() {
+        for vec in [Vec2::ZERO, Vec2::new(1.2344, -678.9), Vec2::new(-0.0004, 1e6)] {
+            let round_trip = FixedVec2::from_vec2(vec).to_vec2();
+            assert!((round_trip - vec).abs().max_element() <= 0.0005, "{} != {}", round_trip, vec);
+        }
+
+        let position = FixedVec2::new(Fixed::ONE, Fixed(-2000));
+        let velocity = FixedVec2::from_vec2(Vec2::new(10., 0.5));
+        let moved = position + velocity * Fixed::from_f32(0.5);
+        assert_eq!(moved, FixedVec2::new(Fixed(6000), Fixed(-1750)));
+        assert_eq!(moved - velocity * Fixed::from_f32(0.5), position);
+        assert_eq!(FixedVec2::ZERO.to_vec2(), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,99 @@
+//! Sends random payloads to the decoders to check that the messages of a peer,
+//! whatever they are, never make us panic, only in the tests.
+
+use std::any::type_name;
+use std::panic::{self, AssertUnwindSafe};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    parse_probe_request, try_decode, ClientMessage, ConnectRequest, ProbeResponse, ServerMessage,
+    WorldSyncPart, MESSAGE_SCHEMA_VERSION,
+};
+
+/// The longest random payload generated, longer ones mostly fail on the same first bytes.
+const MAX_PAYLOAD_LEN: usize = 256;
+
+/// A small xorshift generator, the payloads only need to be varied, not unpredictable.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Random bytes, most of them start with our schema version to reach the deserializer.
+    fn payload(&mut self) -> Vec<u8> {
+        let len = (self.next() % MAX_PAYLOAD_LEN as u64) as usize;
+        let mut bytes: Vec<u8> = (0..len).map(|_| self.next() as u8).collect();
+        if let Some(first) = bytes.first_mut().filter(|_| self.next() % 4 != 0) {
+            *first = MESSAGE_SCHEMA_VERSION;
+        }
+        bytes
+    }
+}
+
+/// How a decoding site handled random payloads, any panic is a bug.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FuzzReport {
+    name: &'static str,
+    payloads: usize,
+    decoded: usize,
+    panicked: usize,
+}
+
+/// Runs `decode` on random payloads and counts how many were accepted and how many panicked.
+fn fuzz<T>(
+    name: &'static str,
+    payloads: usize,
+    seed: u64,
+    decode: impl Fn(&[u8]) -> Option<T>,
+) -> FuzzReport {
+    let mut rng = XorShift(seed.max(1));
+    let mut report = FuzzReport { name, payloads, decoded: 0, panicked: 0 };
+    for _ in 0..payloads {
+        let bytes = rng.payload();
+        match panic::catch_unwind(AssertUnwindSafe(|| decode(&bytes))) {
+            Ok(Some(_)) => report.decoded += 1,
+            Ok(None) => (),
+            Err(_) => report.panicked += 1,
+        }
+    }
+    report
+}
+
+fn fuzz_decode<T: DeserializeOwned>(payloads: usize, seed: u64) -> FuzzReport {
+    fuzz(type_name::<T>(), payloads, seed, |bytes| try_decode::<T>(bytes).ok())
+}
+
+/// Sends random payloads to every place where the messages of a peer are decoded.
+fn fuzz_all_decoders(payloads: usize, seed: u64) -> Vec<FuzzReport> {
+    vec![
+        fuzz_decode::<ClientMessage>(payloads, seed),
+        fuzz_decode::<ServerMessage>(payloads, seed),
+        fuzz_decode::<WorldSyncPart>(payloads, seed),
+        fuzz_decode::<ProbeResponse>(payloads, seed),
+        fuzz("probe request", payloads, seed, parse_probe_request),
+        fuzz("connect request", payloads, seed, |bytes| {
+            let mut user_data = [0; bevy_renet::renet::NETCODE_USER_DATA_BYTES];
+            let len = bytes.len().min(user_data.len());
+            user_data[..len].copy_from_slice(&bytes[..len]);
+            ConnectRequest::from_user_data(&user_data).ok()
+        }),
+    ]
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoders_never_panic() {
+        for report in fuzz_all_decoders(5000, 0x5eed) {
+            assert_eq!(report.panicked, 0, "the {} decoder panicked", report.name);
+            assert!(report.decoded <= report.payloads);
+        }
+    }
+}
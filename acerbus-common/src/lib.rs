@@ -1,8 +1,40 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
 
 use bevy::prelude::*;
-use bevy_renet::renet::RenetError;
+use bevy_renet::renet::{
+    ChannelConfig, DisconnectionReason, RechannelError, ReliableChannelConfig,
+    RenetConnectionConfig, RenetError, UnreliableChannelConfig,
+};
+pub use codec::*;
+pub use config_file::*;
+pub use connect::*;
+pub use coordinator::*;
+pub use fixed::*;
+pub use name_filter::*;
+pub use probe::*;
+pub use replay::*;
+pub use send::*;
 use serde::{Deserialize, Serialize};
+pub use soccer::*;
+pub use transport::*;
+pub use world_sync::*;
+
+mod codec;
+mod config_file;
+mod connect;
+mod coordinator;
+mod fixed;
+#[cfg(test)]
+mod fuzz;
+mod name_filter;
+mod probe;
+mod replay;
+mod send;
+mod soccer;
+mod transport;
+mod world_sync;
 
 pub const PROTOCOL_ID: u64 = 7;
 
@@ -10,42 +42,660 @@ pub const PLAYER_MOVE_SPEED: f32 = 100.0;
 pub const PLAYER_SQUARE_HEIGHT: f32 = 50.0;
 pub const PLAYER_SQUARE_WIDTH: f32 = 25.0;
 
-pub const PLAYER_POSITION_CHANNEL: u8 = 0;
+pub const ARENA_WIDTH: f32 = 1200.0;
+pub const ARENA_HEIGHT: f32 = 800.0;
+
+pub const TELEPORT_PAD_SIZE: f32 = 40.0;
+pub const BALL_RADIUS: f32 = 15.0;
+
 pub const CONNECTION_EVENTS_CHANNEL: u8 = 0;
 pub const WORLD_SYNC_CHANNEL: u8 = 1;
+pub const CLIENT_MESSAGES_CHANNEL: u8 = 3;
+pub const CLIENT_INPUT_CHANNEL: u8 = 4;
+
+/// The channels used by the game, by name, as shown in the printed configurations.
+pub const CHANNELS: [(&str, u8); 4] = [
+    ("connection_events", CONNECTION_EVENTS_CHANNEL),
+    ("world_sync", WORLD_SYNC_CHANNEL),
+    ("client_messages", CLIENT_MESSAGES_CHANNEL),
+    ("client_input", CLIENT_INPUT_CHANNEL),
+];
 
-#[derive(Debug, Default, Serialize, Deserialize, Component)]
+/// The default renet channels with an additional reliable one for the client messages
+/// and an unreliable one for the inputs, a lost input is replaced by the next one anyway.
+pub fn connection_config() -> RenetConnectionConfig {
+    let mut config = RenetConnectionConfig::default();
+    let client_messages =
+        ReliableChannelConfig { channel_id: CLIENT_MESSAGES_CHANNEL, ..Default::default() };
+    let client_input =
+        UnreliableChannelConfig { channel_id: CLIENT_INPUT_CHANNEL, ..Default::default() };
+    for channels in [&mut config.send_channels_config, &mut config.receive_channels_config] {
+        channels.push(ChannelConfig::Reliable(client_messages.clone()));
+        channels.push(ChannelConfig::Unreliable(client_input.clone()));
+    }
+    config
+}
+
+/// The keys pressed by a player. The movement keys are predicted by a client moving its
+/// player itself, the effects of the actions, like the dash, are left to the server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Component)]
 pub struct PlayerInput {
     pub up: bool,
     pub down: bool,
     pub left: bool,
     pub right: bool,
+    /// Dash in the direction of the movement, the server ignores it during the cooldown.
+    pub dash: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Serialize, Deserialize)]
+impl PlayerInput {
+    /// The part of the input a client can predict, the movement without the actions.
+    pub fn movement(self) -> PlayerInput {
+        PlayerInput { dash: false, ..self }
+    }
+}
+
+/// How fast a player moves, a handicap or a power-up, players without it move at
+/// `PLAYER_MOVE_SPEED`. The server syncs it so that a trusted client moves at the same speed.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Serialize, Deserialize)]
+pub struct MoveSpeed(pub f32);
+
+impl Default for MoveSpeed {
+    fn default() -> MoveSpeed {
+        MoveSpeed(PLAYER_MOVE_SPEED)
+    }
+}
+
+/// Returns the velocity a player moves at with these keys pressed.
+pub fn input_velocity(input: &PlayerInput, speed: Option<&MoveSpeed>) -> Vec2 {
+    let x = (input.right as i8 - input.left as i8) as f32;
+    let y = (input.up as i8 - input.down as i8) as f32;
+    Vec2::new(x, y) * speed.copied().unwrap_or_default().0
+}
+
+/// Players are ordered by id so that iterating over them is deterministic. The id of a player
+/// is the client id of its first connection, it stays the same when the player reconnects.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Component, Serialize, Deserialize,
+)]
 pub struct Player {
     pub id: u64,
 }
 
+/// A token generated once by a client and presented on every connection,
+/// it lets the server recognize a player that reconnects with a new client id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Serialize, Deserialize)]
+pub struct SessionToken(pub u64);
+
+/// A team, the players of a team are drawn with the same color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Serialize, Deserialize)]
+pub struct Team(pub u8);
+
+/// The name a player is shown with, the server replaces the rejected ones.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Component, Serialize, Deserialize)]
+pub struct PlayerName(pub String);
+
+/// A texture chosen by a player to be drawn with instead of a plain colored cube.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Serialize, Deserialize)]
+pub enum Skin {
+    Green,
+    Purple,
+}
+
+impl FromStr for Skin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Skin, String> {
+        match s {
+            "green" => Ok(Skin::Green),
+            "purple" => Ok(Skin::Purple),
+            _ => Err(format!("unknown skin {:?}, expected green or purple", s)),
+        }
+    }
+}
+
+/// How big a player is, it can change during a match, the cube is scaled from its normal size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Serialize, Deserialize)]
+pub enum SizeClass {
+    Small,
+    Normal,
+    Large,
+}
+
+impl SizeClass {
+    pub fn scale(self) -> f32 {
+        match self {
+            SizeClass::Small => 0.5,
+            SizeClass::Normal => 1.0,
+            SizeClass::Large => 2.0,
+        }
+    }
+
+    /// The half size of the cube of a player of this size.
+    pub fn half_extents(self) -> Vec2 {
+        Vec2::new(PLAYER_SQUARE_WIDTH, PLAYER_SQUARE_HEIGHT) * self.scale() / 2.
+    }
+}
+
+impl Default for SizeClass {
+    fn default() -> SizeClass {
+        SizeClass::Normal
+    }
+}
+
+impl FromStr for SizeClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SizeClass, String> {
+        match s {
+            "small" => Ok(SizeClass::Small),
+            "normal" => Ok(SizeClass::Normal),
+            "large" => Ok(SizeClass::Large),
+            _ => Err(format!("unknown size {:?}, expected small, normal or large", s)),
+        }
+    }
+}
+
+/// The shape of the players, the same for all the players of a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Serialize, Deserialize)]
+pub enum BodyShape {
+    Square,
+    Circle,
+}
+
+impl BodyShape {
+    /// The radius of a circular player of this size, it is as wide as a square one.
+    pub fn radius(size: SizeClass) -> f32 {
+        size.half_extents().x
+    }
+
+    /// The half size of the box around a player of this shape and size.
+    pub fn half_extents(self, size: SizeClass) -> Vec2 {
+        match self {
+            BodyShape::Square => size.half_extents(),
+            BodyShape::Circle => Vec2::splat(BodyShape::radius(size)),
+        }
+    }
+}
+
+impl Default for BodyShape {
+    fn default() -> BodyShape {
+        BodyShape::Square
+    }
+}
+
+impl FromStr for BodyShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BodyShape, String> {
+        match s {
+            "square" => Ok(BodyShape::Square),
+            "circle" => Ok(BodyShape::Circle),
+            _ => Err(format!("unknown shape {:?}, expected square or circle", s)),
+        }
+    }
+}
+
+/// Returns the team a new player joins given the number of players in each team.
+/// The requested team is only honored if the teams stay within one player of each other.
+pub fn balanced_team(requested: Option<Team>, players_per_team: &[usize]) -> Team {
+    let min = players_per_team.iter().copied().min().unwrap_or(0);
+    match requested {
+        Some(Team(team)) if players_per_team.get(team as usize) == Some(&min) => Team(team),
+        _ => Team(players_per_team.iter().position(|&count| count == min).unwrap_or(0) as u8),
+    }
+}
+
+/// The dimensions of the play area, centered on the origin.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Arena {
+    fn default() -> Arena {
+        Arena { width: ARENA_WIDTH, height: ARENA_HEIGHT }
+    }
+}
+
+const WALL_THICKNESS: f32 = 20.0;
+
+/// Returns the center and half size of the four walls surrounding the arena.
+pub fn wall_bounds(arena: Arena) -> [(Vec2, Vec2); 4] {
+    let (half_width, half_height) = (arena.width / 2., arena.height / 2.);
+    let half_thickness = WALL_THICKNESS / 2.;
+    let vertical = Vec2::new(half_thickness, half_height + WALL_THICKNESS);
+    let horizontal = Vec2::new(half_width + WALL_THICKNESS, half_thickness);
+    [
+        (Vec2::new(-half_width - half_thickness, 0.), vertical),
+        (Vec2::new(half_width + half_thickness, 0.), vertical),
+        (Vec2::new(0., -half_height - half_thickness), horizontal),
+        (Vec2::new(0., half_height + half_thickness), horizontal),
+    ]
+}
+
+/// A pad that teleports the players stepping on it onto the pad it is linked to.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Serialize, Deserialize)]
+pub struct TeleportPad {
+    pub id: u32,
+    pub position: Vec2,
+    pub target_id: u32,
+}
+
+/// Returns the pad on which a player stepping on `pad` must land.
+pub fn teleport_pad_target<'a>(
+    pads: &'a [TeleportPad],
+    pad: &TeleportPad,
+) -> Option<&'a TeleportPad> {
+    pads.iter().find(|p| p.id == pad.target_id && p.id != pad.id)
+}
+
 #[derive(Debug, Default)]
 pub struct Lobby {
-    pub players: HashMap<Player, Entity>,
+    pub players: BTreeMap<Player, Entity>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct WorldSync {
-    pub players_positions: HashMap<Player, Vec2>,
+    pub players_positions: BTreeMap<Player, Vec2>,
+    pub players_connections: BTreeMap<Player, ConnectionStats>,
+    /// How many times the players were teleported, wrapping, the ones never teleported are
+    /// missing. A client sees a player jump instead of sliding when its count changes.
+    pub players_teleports: BTreeMap<Player, u8>,
+    /// The objects moved by the physics of the server, like the ball the players push.
+    pub objects: BTreeMap<ObjectId, Vec2>,
+}
+
+/// An object of the world that is not a player, the players can push it around.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Component, Serialize, Deserialize,
+)]
+pub struct ObjectId(pub u32);
+
+/// The quality of the connection between a player and the server, as seen by the server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Component, Serialize, Deserialize)]
+pub struct ConnectionStats {
+    /// The round-trip time in milliseconds.
+    pub rtt: f32,
+    /// The ratio of packets lost, between 0 and 1.
+    pub packet_loss: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionQuality {
+    Good,
+    Medium,
+    Poor,
+}
+
+impl ConnectionQuality {
+    pub fn from_stats(stats: ConnectionStats) -> ConnectionQuality {
+        if stats.rtt < 100.0 && stats.packet_loss < 0.02 {
+            ConnectionQuality::Good
+        } else if stats.rtt < 250.0 && stats.packet_loss < 0.1 {
+            ConnectionQuality::Medium
+        } else {
+            ConnectionQuality::Poor
+        }
+    }
+
+    /// The number of bars to show to represent this quality, out of three.
+    pub fn bars(self) -> usize {
+        match self {
+            ConnectionQuality::Good => 3,
+            ConnectionQuality::Medium => 2,
+            ConnectionQuality::Poor => 1,
+        }
+    }
+}
+
+/// The bounds, in seconds, of the delay with which the clients should render the other players.
+/// The server advertises them so that all the clients of a deployment behave the same.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InterpolationParams {
+    pub min_delay: f64,
+    pub max_delay: f64,
+}
+
+impl Default for InterpolationParams {
+    fn default() -> InterpolationParams {
+        InterpolationParams { min_delay: 0.0, max_delay: 0.25 }
+    }
+}
+
+/// Whether the players are still waiting for each other or already playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchState {
+    Lobby,
+    Playing,
+}
+
+impl Default for MatchState {
+    fn default() -> MatchState {
+        MatchState::Lobby
+    }
+}
+
+/// Something that happened in the match, the server keeps the last ones to feed the spectators.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+    PlayerJoined { player: Player },
+    PlayerLeft { player: Player },
+    PlayerEliminated { player: Player, by: Option<Player> },
+    MatchStateChanged { state: MatchState },
+    GoalScored { team: Team },
+    MatchWon { team: Team },
+}
+
+/// What a client sends to the server, the inputs and positions are sent on the
+/// `CLIENT_INPUT_CHANNEL` and everything else on the `CLIENT_MESSAGES_CHANNEL`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// An input and the tick at which the client applies it, the server applies it then too.
+    Input {
+        input: PlayerInput,
+        tick: u64,
+    },
+    /// An input the server echoes in an `InputsApplied` once applied, to measure its latency.
+    TaggedInput {
+        input: PlayerInput,
+        tick: u64,
+        sequence: u32,
+    },
+    /// Where the client moved its player, only accepted by a server that trusts the clients.
+    Position(Vec2),
+    Chat(String),
+    /// The player is (or is no more) ready for the match to start.
+    Ready(bool),
+    /// The server answers with a `Pong` containing the same value.
+    Ping(u64),
+    /// Asks the server to send all the connected players again.
+    RequestFullSync,
+    /// The client received the `Welcome` and everything before it, it can be sent the world.
+    WelcomeReceived,
+    /// How far around its player the client wants to see the others, the server may see less.
+    ViewRadius(f32),
 }
 
 #[derive(Debug, Serialize, Deserialize, Component)]
 pub enum ServerMessage {
-    PlayerConnected { player: Player },
-    PlayerDisconnected { player: Player },
+    /// The first message a client receives, it describes the world it joins and
+    /// the player it is, which is not its client id if it reconnected.
+    InitState {
+        player: Player,
+        /// The number of ticks the server simulates per second.
+        tick_rate: f64,
+        /// The tick the server is at, the client counts its own ticks from it.
+        tick: u64,
+        /// How many ticks ahead the client applies its inputs, and tags them for.
+        input_delay: u64,
+        /// The client moves its player itself and sends its position, this is insecure.
+        trusted_positions: bool,
+        /// The interpolation the client should use unless told otherwise by its player.
+        interpolation: InterpolationParams,
+        body_shape: BodyShape,
+        mode: GameMode,
+        arena: Arena,
+        teleport_pads: Vec<TeleportPad>,
+        match_state: MatchState,
+        /// The server kept the player of the client and only sends what changed since it left,
+        /// otherwise the client must forget the players it knows about.
+        resumed: bool,
+    },
+    /// Everything the client needs has been sent, it can start playing as this player.
+    /// The world is only sent to the client once it acknowledged this message.
+    Welcome {
+        player: Player,
+    },
+    PlayerConnected {
+        player: Player,
+        team: Option<Team>,
+        skin: Option<Skin>,
+        name: PlayerName,
+        /// The player is moved by the server, not by a client.
+        bot: bool,
+    },
+    PlayerDisconnected {
+        player: Player,
+    },
+    /// The player just (re)spawned and can't be pushed for this long.
+    SpawnProtected {
+        player: Player,
+        duration: Duration,
+    },
+    /// The player grew or shrank, the client animates the change.
+    PlayerResized {
+        player: Player,
+        size: SizeClass,
+    },
+    /// The player now moves at this speed, the default speed is `PLAYER_MOVE_SPEED`.
+    PlayerSpeedChanged {
+        player: Player,
+        speed: MoveSpeed,
+    },
+    /// A player came back within the grace period with a new client id, it keeps its entity.
+    PlayerReconnected {
+        player: Player,
+    },
+    ReadyChanged {
+        player: Player,
+        ready: bool,
+    },
+    MatchStateChanged {
+        state: MatchState,
+    },
+    /// The arena grew or shrank with the number of players, its teleport pads moved with it.
+    ArenaChanged {
+        arena: Arena,
+        teleport_pads: Vec<TeleportPad>,
+    },
+    /// The player left the arena and is back at its center, `by` pushed it out.
+    PlayerEliminated {
+        player: Player,
+        by: Option<Player>,
+    },
+    Chat {
+        player: Player,
+        text: String,
+    },
+    /// A private message only sent to its recipient.
+    Whisper {
+        from: Player,
+        text: String,
+    },
+    /// The whisper couldn't be delivered, the target is not connected or the command is wrong.
+    WhisperFailed {
+        reason: String,
+    },
+    /// The server is about to close the connection with this client, for this reason.
+    Disconnecting {
+        reason: String,
+    },
+    /// The number of players this player eliminated, sent on connect and when it changes.
+    ScoreChanged {
+        player: Player,
+        score: u32,
+    },
+    /// The score of every team, in the soccer mode, sent on connect and after every goal.
+    TeamScores {
+        scores: Vec<u32>,
+    },
+    /// A message from the administrator of the server to everyone, not from a player.
+    Announcement {
+        text: String,
+    },
+    /// The last chat message was dropped, the player must wait before sending another one.
+    ChatThrottled {
+        retry_after: Duration,
+    },
+    Pong(u64),
+    /// The tagged inputs applied since the last batch, by sequence number, and the tick
+    /// each of them was applied on.
+    InputsApplied {
+        inputs: Vec<(u32, u64)>,
+    },
+    /// Something happened in the match, the recent events are also sent on connect.
+    GameEvent(GameEvent),
+}
+
+/// Returns the channel an error comes from, if it comes from a single channel.
+pub fn error_channel(error: &RenetError) -> Option<u8> {
+    match error {
+        RenetError::Rechannel(RechannelError::ClientDisconnected(
+            DisconnectionReason::ChannelError { channel_id, .. },
+        )) => Some(*channel_id),
+        _ => None,
+    }
+}
+
+/// Returns the name of a channel, as listed in `CHANNELS`.
+pub fn channel_name(channel_id: u8) -> &'static str {
+    CHANNELS.iter().find(|(_, id)| *id == channel_id).map_or("unknown", |(name, _)| name)
 }
 
-// If any error is found we just panic
-pub fn panic_on_error_system(mut renet_error: EventReader<RenetError>) {
+/// An error of a single channel only ends the connection it happened on, renet drops it and
+/// the usual disconnection handling takes over, so it is logged. We still panic on the others.
+pub fn handle_renet_errors_system(mut renet_error: EventReader<RenetError>) {
     for e in renet_error.iter() {
-        panic!("{}", e);
+        match error_channel(e) {
+            Some(channel_id) => {
+                error!("The {} channel ({}) failed: {}", channel_name(channel_id), channel_id, e)
+            }
+            None => panic!("{}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players_join_the_smallest_team() {
+        assert_eq!(balanced_team(None, &[2, 1, 2]), Team(1));
+        assert_eq!(balanced_team(None, &[0, 0]), Team(0));
+        // The requested team is too big, we join the smallest one instead.
+        assert_eq!(balanced_team(Some(Team(0)), &[3, 2]), Team(1));
+        assert_eq!(balanced_team(Some(Team(1)), &[1, 1]), Team(1));
+        assert_eq!(balanced_team(Some(Team(9)), &[1, 1]), Team(0));
+    }
+
+    #[test]
+    fn skins_are_parsed_from_their_names() {
+        assert_eq!("green".parse(), Ok(Skin::Green));
+        assert_eq!("purple".parse(), Ok(Skin::Purple));
+        assert!("Green".parse::<Skin>().is_err());
+    }
+
+    #[test]
+    fn inputs_have_their_own_unreliable_channel() {
+        let config = connection_config();
+        for channels in [&config.send_channels_config, &config.receive_channels_config] {
+            let mut ids: Vec<_> = channels
+                .iter()
+                .map(|channel| match channel {
+                    ChannelConfig::Reliable(config) => config.channel_id,
+                    ChannelConfig::Unreliable(config) => config.channel_id,
+                    ChannelConfig::Block(config) => config.channel_id,
+                })
+                .collect();
+            let input_channel = channels.iter().find(|channel| {
+                matches!(channel, ChannelConfig::Unreliable(config)
+                    if config.channel_id == CLIENT_INPUT_CHANNEL)
+            });
+            assert!(input_channel.is_some());
+
+            // Every channel has its own id.
+            let count = ids.len();
+            ids.sort_unstable();
+            ids.dedup();
+            assert_eq!(ids.len(), count);
+        }
+    }
+
+    #[test]
+    fn world_syncs_are_ordered_by_player() {
+        let ids = [42, 7, 1000, 3];
+        let mut world = WorldSync::default();
+        let mut reversed = WorldSync::default();
+        for id in ids {
+            world.players_positions.insert(Player { id }, Vec2::splat(id as f32));
+        }
+        for id in ids.iter().rev() {
+            reversed.players_positions.insert(Player { id: *id }, Vec2::splat(*id as f32));
+        }
+
+        let order: Vec<_> = world.players_positions.keys().map(|player| player.id).collect();
+        assert_eq!(order, vec![3, 7, 42, 1000]);
+        // The same world is always serialized the same way.
+        let bytes = bincode::serialize(&world).unwrap();
+        assert_eq!(bytes, bincode::serialize(&reversed).unwrap());
+    }
+
+    #[test]
+    fn connections_are_bucketed_by_their_worst_stat() {
+        let quality = |rtt, packet_loss| {
+            ConnectionQuality::from_stats(ConnectionStats { rtt, packet_loss }).bars()
+        };
+        assert_eq!(quality(30., 0.), 3);
+        assert_eq!(quality(150., 0.), 2);
+        assert_eq!(quality(30., 0.05), 2);
+        assert_eq!(quality(300., 0.), 1);
+        assert_eq!(quality(30., 0.2), 1);
+        // The boundaries belong to the worst bucket.
+        assert_eq!(quality(100., 0.), 2);
+        assert_eq!(quality(250., 0.), 1);
+    }
+
+    #[test]
+    fn only_the_errors_of_a_single_channel_are_recovered() {
+        let channel_error = RenetError::Rechannel(RechannelError::ClientDisconnected(
+            DisconnectionReason::ChannelError {
+                channel_id: CLIENT_MESSAGES_CHANNEL,
+                error: bevy_renet::renet::ChannelError::ReliableChannelMaxMemoryReached,
+            },
+        ));
+        assert_eq!(error_channel(&channel_error), Some(CLIENT_MESSAGES_CHANNEL));
+        assert_eq!(channel_name(CLIENT_MESSAGES_CHANNEL), "client_messages");
+        assert_eq!(channel_name(42), "unknown");
+
+        let disconnected = RenetError::Rechannel(RechannelError::ClientDisconnected(
+            DisconnectionReason::DisconnectedByServer,
+        ));
+        assert_eq!(error_channel(&disconnected), None);
+    }
+
+    #[test]
+    fn channel_errors_do_not_panic() {
+        let mut world = World::new();
+        world.insert_resource(Events::<RenetError>::default());
+        world.resource_mut::<Events<RenetError>>().send(RenetError::Rechannel(
+            RechannelError::ClientDisconnected(DisconnectionReason::ChannelError {
+                channel_id: CLIENT_INPUT_CHANNEL,
+                error: bevy_renet::renet::ChannelError::ReliableChannelMaxMemoryReached,
+            }),
+        ));
+        let mut stage = SystemStage::single_threaded().with_system(handle_renet_errors_system);
+        stage.run(&mut world);
+    }
+
+    #[test]
+    fn players_move_at_their_own_speed() {
+        let input = PlayerInput { up: true, down: false, left: true, right: true, dash: true };
+        assert_eq!(input_velocity(&input, None), Vec2::new(0., PLAYER_MOVE_SPEED));
+        assert_eq!(input_velocity(&input, Some(&MoveSpeed(10.))), Vec2::new(0., 10.));
+    }
+
+    #[test]
+    fn round_players_are_as_wide_as_square_ones() {
+        assert_eq!("circle".parse(), Ok(BodyShape::Circle));
+        assert_eq!("square".parse(), Ok(BodyShape::Square));
+        assert!("triangle".parse::<BodyShape>().is_err());
+
+        let size = SizeClass::Large;
+        assert_eq!(BodyShape::radius(size), size.half_extents().x);
+        assert_eq!(BodyShape::Square.half_extents(size), size.half_extents());
+        assert_eq!(BodyShape::Circle.half_extents(size), Vec2::splat(size.half_extents().x));
     }
 }
@@ -0,0 +1,120 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::Player;
+
+/// The longest name a player can take, in characters, the rest is cut.
+pub const MAX_NAME_CHARS: usize = 16;
+
+/// The words a moderated server doesn't want in the names of its players or in the chat.
+/// A word matches whatever its case and wherever it is in the text.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NameFilter {
+    words: Vec<String>,
+}
+
+impl NameFilter {
+    pub fn from_words<I, S>(words: I) -> NameFilter
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let words = words
+            .into_iter()
+            .map(|word| word.as_ref().trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        NameFilter { words }
+    }
+
+    /// Reads one word per line, the empty lines and the ones starting with `#` are skipped.
+    pub fn load(path: &Path) -> io::Result<NameFilter> {
+        let content = fs::read_to_string(path)?;
+        let lines = content.lines().filter(|line| !line.trim_start().starts_with('#'));
+        Ok(NameFilter::from_words(lines))
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        self.words.iter().any(|word| text.contains(word.as_str()))
+    }
+
+    /// Replaces every character of the words found in the text with a `*`.
+    pub fn mask(&self, text: &str) -> String {
+        let mut chars: Vec<char> = text.chars().collect();
+        let lowercase: Vec<char> = chars.iter().map(|c| lowercase_char(*c)).collect();
+        for word in &self.words {
+            let word: Vec<char> = word.chars().collect();
+            let mut start = 0;
+            while start + word.len() <= lowercase.len() {
+                if lowercase[start..start + word.len()] == word[..] {
+                    chars[start..start + word.len()].fill('*');
+                    start += word.len();
+                } else {
+                    start += 1;
+                }
+            }
+        }
+        chars.into_iter().collect()
+    }
+
+    /// The name the player is shown with, the one it asked for unless it is empty
+    /// or matches a word of the filter.
+    pub fn player_name(&self, requested: Option<&str>, player: Player) -> String {
+        let requested = requested.map(|name| name.trim().chars().take(MAX_NAME_CHARS));
+        match requested.map(String::from_iter) {
+            Some(name) if !name.is_empty() && !self.matches(&name) => name,
+            _ => fallback_name(player),
+        }
+    }
+}
+
+/// The name of a player that didn't choose one or whose name was rejected.
+pub fn fallback_name(player: Player) -> String {
+    format!("Player#{}", player.id)
+}
+
+/// Lowercases a character that stays a single character, the others are kept as is
+/// so that the masked text has as many characters as the original one.
+fn lowercase_char(c: char) -> char {
+    let mut lowercase = c.to_lowercase();
+    match (lowercase.next(), lowercase.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_match_whatever_their_case() {
+        let filter = NameFilter::from_words(["Badword", "  ", "rude "]);
+        assert!(filter.matches("xXBADWORDXx"));
+        assert!(filter.matches("so rude"));
+        assert!(!filter.matches("friendly"));
+        assert!(!NameFilter::default().matches("badword"));
+    }
+
+    #[test]
+    fn only_the_words_are_masked() {
+        let filter = NameFilter::from_words(["bad"]);
+        assert_eq!(filter.mask("a BAD day, bad bad"), "a *** day, *** ***");
+        assert_eq!(filter.mask("Ünïcode bad"), "Ünïcode ***");
+        assert_eq!(filter.mask("good"), "good");
+    }
+
+    #[test]
+    fn rejected_names_fall_back_to_the_player_id() {
+        let filter = NameFilter::from_words(["bad"]);
+        let player = Player { id: 7 };
+        assert_eq!(filter.player_name(Some(" Alice "), player), "Alice");
+        assert_eq!(filter.player_name(Some("badguy"), player), "Player#7");
+        assert_eq!(filter.player_name(Some("   "), player), "Player#7");
+        assert_eq!(filter.player_name(None, player), "Player#7");
+        let long = "a".repeat(MAX_NAME_CHARS + 4);
+        assert_eq!(filter.player_name(Some(&long), player).chars().count(), MAX_NAME_CHARS);
+    }
+}
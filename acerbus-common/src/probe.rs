@@ -0,0 +1,62 @@
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+/// The first bytes of a probe, followed by a nonce that the server sends back.
+pub const PROBE_MAGIC: &[u8; 8] = b"acerprob";
+
+/// What a server says about itself when probed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub name: String,
+    pub players: usize,
+    pub max_players: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeResponse {
+    pub nonce: u64,
+    pub info: ServerInfo,
+}
+
+/// The probes are answered on the port following the game one,
+/// the game socket only understands the renet packets.
+/// A server listening on the last port can't be probed.
+pub fn probe_addr(server_addr: SocketAddr) -> Option<SocketAddr> {
+    let mut addr = server_addr;
+    addr.set_port(server_addr.port().checked_add(1)?);
+    Some(addr)
+}
+
+pub fn probe_request(nonce: u64) -> [u8; 16] {
+    let mut request = [0; 16];
+    request[..8].copy_from_slice(PROBE_MAGIC);
+    request[8..].copy_from_slice(&nonce.to_le_bytes());
+    request
+}
+
+/// Returns the nonce of this probe, `None` if it isn't one.
+pub fn parse_probe_request(bytes: &[u8]) -> Option<u64> {
+    let nonce = bytes.strip_prefix(PROBE_MAGIC)?;
+    Some(u64::from_le_bytes(nonce.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probes_are_answered_on_the_next_port() {
+        let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        assert_eq!(probe_addr(server_addr), Some("127.0.0.1:5001".parse().unwrap()));
+        let last_port: SocketAddr = "127.0.0.1:65535".parse().unwrap();
+        assert_eq!(probe_addr(last_port), None);
+    }
+
+    #[test]
+    fn probe_request_round_trip() {
+        assert_eq!(parse_probe_request(&probe_request(42)), Some(42));
+        assert_eq!(parse_probe_request(b"acerprob"), None);
+        assert_eq!(parse_probe_request(b"notaprobe-12345"), None);
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{Player, PlayerInput};
+
+/// The bytes a replay file starts with, followed by the version of its format.
+/// The files recorded before the format was versioned start with the tick rate.
+pub const REPLAY_MAGIC: [u8; 4] = *b"ACRP";
+/// The version of the replay format, bumped every time `Replay` changes.
+pub const REPLAY_VERSION: u8 = 3;
+
+/// The inputs of the players for every tick of a session, replaying them
+/// runs the same simulation again without any client.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub tick_rate: f64,
+    pub ticks: Vec<ReplayTick>,
+}
+
+/// The players connected during a tick with the input they were moving with,
+/// and where they were, to watch the session again without simulating it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayTick {
+    pub inputs: BTreeMap<Player, PlayerInput>,
+    pub positions: BTreeMap<Player, Vec2>,
+}
+
+/// An input recorded before the players could dash, in the first two versions of the format.
+#[derive(Deserialize)]
+struct MovementInput {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+}
+
+impl From<MovementInput> for PlayerInput {
+    fn from(MovementInput { up, down, left, right }: MovementInput) -> PlayerInput {
+        PlayerInput { up, down, left, right, dash: false }
+    }
+}
+
+fn movement_inputs(inputs: BTreeMap<Player, MovementInput>) -> BTreeMap<Player, PlayerInput> {
+    inputs.into_iter().map(|(player, input)| (player, input.into())).collect()
+}
+
+/// A replay recorded before the positions were, the first version of the format.
+#[derive(Deserialize)]
+struct ReplayV1 {
+    tick_rate: f64,
+    ticks: Vec<ReplayTickV1>,
+}
+
+#[derive(Deserialize)]
+struct ReplayTickV1 {
+    inputs: BTreeMap<Player, MovementInput>,
+}
+
+impl From<ReplayV1> for Replay {
+    fn from(replay: ReplayV1) -> Replay {
+        let ticks = replay
+            .ticks
+            .into_iter()
+            .map(|tick| ReplayTick {
+                inputs: movement_inputs(tick.inputs),
+                positions: BTreeMap::new(),
+            })
+            .collect();
+        Replay { tick_rate: replay.tick_rate, ticks }
+    }
+}
+
+/// A replay recorded before the players could dash, the second version of the format.
+#[derive(Deserialize)]
+struct ReplayV2 {
+    tick_rate: f64,
+    ticks: Vec<ReplayTickV2>,
+}
+
+#[derive(Deserialize)]
+struct ReplayTickV2 {
+    inputs: BTreeMap<Player, MovementInput>,
+    positions: BTreeMap<Player, Vec2>,
+}
+
+impl From<ReplayV2> for Replay {
+    fn from(replay: ReplayV2) -> Replay {
+        let ticks = replay
+            .ticks
+            .into_iter()
+            .map(|tick| ReplayTick {
+                inputs: movement_inputs(tick.inputs),
+                positions: tick.positions,
+            })
+            .collect();
+        Replay { tick_rate: replay.tick_rate, ticks }
+    }
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        Replay::read_from(BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    /// Reads a replay of any version, the old ones have no position to watch.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Replay> {
+        let invalid_data = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        match bytes.strip_prefix(&REPLAY_MAGIC) {
+            Some([REPLAY_VERSION, replay @ ..]) => {
+                bincode::deserialize(replay).map_err(invalid_data)
+            }
+            Some([2, replay @ ..]) => {
+                bincode::deserialize::<ReplayV2>(replay).map(Replay::from).map_err(invalid_data)
+            }
+            Some([version, ..]) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the replay version {} is not {}", version, REPLAY_VERSION),
+            )),
+            Some([]) => Err(io::ErrorKind::UnexpectedEof.into()),
+            None => {
+                bincode::deserialize::<ReplayV1>(&bytes).map(Replay::from).map_err(invalid_data)
+            }
+        }
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&REPLAY_MAGIC)?;
+        writer.write_all(&[REPLAY_VERSION])?;
+        bincode::serialize_into(&mut writer, self)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        let player = Player { id: 1 };
+        let tick = ReplayTick {
+            inputs: BTreeMap::from([(player, PlayerInput::default())]),
+            positions: BTreeMap::from([(player, Vec2::new(1., 2.))]),
+        };
+        Replay { tick_rate: 60., ticks: vec![tick] }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bytes = Vec::new();
+        replay().write_to(&mut bytes).unwrap();
+        assert!(bytes.starts_with(&REPLAY_MAGIC));
+        assert_eq!(Replay::read_from(&bytes[..]).unwrap().ticks, replay().ticks);
+    }
+
+    #[test]
+    fn reads_the_replays_without_version() {
+        // The first version of the format, the ticks only had the movement inputs.
+        let player = Player { id: 1 };
+        let movement = BTreeMap::from([(player, [false, false, false, true])]);
+        let bytes = bincode::serialize(&(60.0f64, vec![movement])).unwrap();
+
+        let replay = Replay::read_from(&bytes[..]).unwrap();
+        assert_eq!(replay.tick_rate, 60.);
+        let inputs =
+            BTreeMap::from([(player, PlayerInput { right: true, ..PlayerInput::default() })]);
+        assert_eq!(replay.ticks, vec![ReplayTick { inputs, positions: BTreeMap::new() }]);
+    }
+
+    #[test]
+    fn reads_the_replays_without_dash() {
+        let player = Player { id: 1 };
+        let movement = BTreeMap::from([(player, [true, false, false, false])]);
+        let positions = BTreeMap::from([(player, Vec2::new(1., 2.))]);
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        bytes.push(2);
+        bincode::serialize_into(&mut bytes, &(60.0f64, vec![(movement, positions.clone())]))
+            .unwrap();
+
+        let replay = Replay::read_from(&bytes[..]).unwrap();
+        let inputs = BTreeMap::from([(player, PlayerInput { up: true, ..PlayerInput::default() })]);
+        assert_eq!(replay.ticks, vec![ReplayTick { inputs, positions }]);
+    }
+
+    #[test]
+    fn rejects_an_unknown_version() {
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        bytes.push(REPLAY_VERSION + 1);
+        let error = Replay::read_from(&bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
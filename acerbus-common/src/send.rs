@@ -0,0 +1,37 @@
+use bevy_renet::renet::RenetServer;
+
+/// Sends the same message to all these clients.
+pub fn send_to<I: IntoIterator<Item = u64>>(
+    server: &mut RenetServer,
+    channel: u8,
+    client_ids: I,
+    message: Vec<u8>,
+) {
+    for client_id in client_ids {
+        server.send_message(client_id, channel, message.clone());
+    }
+}
+
+/// Sends a message to all the clients except one, usually the one the message is about.
+pub fn broadcast_except(server: &mut RenetServer, channel: u8, except_id: u64, message: Vec<u8>) {
+    let client_ids = clients_except(server.clients_id(), except_id);
+    send_to(server, channel, client_ids, message);
+}
+
+/// Returns the clients a message broadcast except to `except_id` is sent to.
+pub fn clients_except(client_ids: Vec<u64>, except_id: u64) -> Vec<u64> {
+    client_ids.into_iter().filter(|id| *id != except_id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_excluded_client_is_omitted() {
+        assert_eq!(clients_except(vec![1, 2, 3], 2), vec![1, 3]);
+        assert_eq!(clients_except(vec![1, 3], 2), vec![1, 3]);
+        assert_eq!(clients_except(vec![2], 2), Vec::<u64>::new());
+        assert_eq!(clients_except(Vec::new(), 2), Vec::<u64>::new());
+    }
+}
@@ -0,0 +1,104 @@
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Arena, Team};
+
+/// How deep the goals are, from the left and right edges of the arena.
+pub const GOAL_DEPTH: f32 = 40.0;
+/// The part of the height of the arena the goals take.
+pub const GOAL_HEIGHT_RATIO: f32 = 1. / 3.;
+
+/// The rules of the match, sent to the clients in the `InitState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// The players push each other out of the arena.
+    Classic,
+    /// Two teams push a ball into the goal of the other team.
+    Soccer,
+}
+
+impl Default for GameMode {
+    fn default() -> GameMode {
+        GameMode::Classic
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GameMode, String> {
+        match s {
+            "classic" => Ok(GameMode::Classic),
+            "soccer" => Ok(GameMode::Soccer),
+            _ => Err(format!("unknown mode {:?}, expected classic or soccer", s)),
+        }
+    }
+}
+
+/// The goal a team defends, the first team on the left and the second one on the right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoalZone {
+    pub defended_by: Team,
+    pub center: Vec2,
+    pub half_size: Vec2,
+}
+
+impl GoalZone {
+    pub fn contains(&self, position: Vec2) -> bool {
+        let delta = (position - self.center).abs();
+        delta.x <= self.half_size.x && delta.y <= self.half_size.y
+    }
+}
+
+/// The two goals, at the ends of the arena, they follow it when it is resized.
+pub fn goal_zones(arena: Arena) -> [GoalZone; 2] {
+    let half_size = Vec2::new(GOAL_DEPTH / 2., arena.height * GOAL_HEIGHT_RATIO / 2.);
+    let x = arena.width / 2. - GOAL_DEPTH / 2.;
+    [
+        GoalZone { defended_by: Team(0), center: Vec2::new(-x, 0.), half_size },
+        GoalZone { defended_by: Team(1), center: Vec2::new(x, 0.), half_size },
+    ]
+}
+
+/// Returns the team that scores when the ball is at this position, the one attacking the goal.
+pub fn scoring_team(ball: Vec2, arena: Arena) -> Option<Team> {
+    goal_zones(arena)
+        .iter()
+        .find(|goal| goal.contains(ball))
+        .map(|goal| Team(1 - goal.defended_by.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_attacking_team_scores() {
+        let arena = Arena { width: 1000., height: 600. };
+        let left = Vec2::new(-arena.width / 2. + GOAL_DEPTH / 2., 0.);
+        let right = Vec2::new(arena.width / 2. - GOAL_DEPTH / 2., 50.);
+        assert_eq!(scoring_team(left, arena), Some(Team(1)));
+        assert_eq!(scoring_team(right, arena), Some(Team(0)));
+        assert_eq!(scoring_team(Vec2::ZERO, arena), None);
+        // Next to the goal, along the same edge.
+        assert_eq!(scoring_team(Vec2::new(left.x, arena.height / 2. - 1.), arena), None);
+    }
+
+    #[test]
+    fn goals_follow_the_arena() {
+        let arena = Arena { width: 2000., height: 900. };
+        let [left, right] = goal_zones(arena);
+        assert_eq!(left.center, Vec2::new(-980., 0.));
+        assert_eq!(right.center, Vec2::new(980., 0.));
+        assert!((left.half_size.y - 150.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn modes_are_parsed_from_their_name() {
+        assert_eq!("soccer".parse(), Ok(GameMode::Soccer));
+        assert_eq!("classic".parse(), Ok(GameMode::Classic));
+        assert!("golf".parse::<GameMode>().is_err());
+    }
+}
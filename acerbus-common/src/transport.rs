@@ -0,0 +1,225 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bevy_renet::renet::RechannelError;
+use rechannel::remote_connection::{ConnectionConfig, RemoteConnection};
+
+use crate::connection_config;
+
+/// Sends and receives the packets of the connections, whatever carries them.
+pub trait Transport: Send + Sync {
+    /// The address the peers send their packets to.
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<()>;
+
+    /// Returns the next packet received and who sent it, `None` when none is waiting.
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>>;
+}
+
+/// The default transport, a non-blocking UDP socket.
+///
+/// renet 0.0.9 reads and writes its socket itself, the `RenetServer` and `RenetClient`
+/// of the game are built from `into_socket`. The other transports run the same channels
+/// through a `TransportConnection`.
+#[derive(Debug)]
+pub struct UdpTransport(UdpSocket);
+
+impl UdpTransport {
+    pub fn bind(addr: SocketAddr) -> io::Result<UdpTransport> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport(socket))
+    }
+
+    pub fn into_socket(self) -> UdpSocket {
+        self.0
+    }
+}
+
+impl Transport for UdpTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        self.0.send_to(packet, addr).map(drop)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        match self.0.recv_from(buf) {
+            Ok(received) => Ok(Some(received)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+type Inboxes = HashMap<SocketAddr, VecDeque<(Vec<u8>, SocketAddr)>>;
+
+/// A network in memory, the transports bound to it exchange their packets without sockets.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryNetwork {
+    inboxes: Arc<Mutex<Inboxes>>,
+}
+
+impl MemoryNetwork {
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<MemoryTransport> {
+        let mut inboxes = self.inboxes.lock().unwrap();
+        if inboxes.contains_key(&addr) {
+            return Err(io::ErrorKind::AddrInUse.into());
+        }
+        inboxes.insert(addr, VecDeque::new());
+        Ok(MemoryTransport { network: self.clone(), addr })
+    }
+}
+
+/// An address bound on a `MemoryNetwork`, it is released when dropped.
+#[derive(Debug)]
+pub struct MemoryTransport {
+    network: MemoryNetwork,
+    addr: SocketAddr,
+}
+
+impl Transport for MemoryTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    /// Like with UDP, the packets sent to an address nobody is bound to are lost.
+    fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        let mut inboxes = self.network.inboxes.lock().unwrap();
+        if let Some(inbox) = inboxes.get_mut(&addr) {
+            inbox.push_back((packet.to_vec(), self.addr));
+        }
+        Ok(())
+    }
+
+    /// The packets too big for the buffer are truncated, like with UDP.
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let mut inboxes = self.network.inboxes.lock().unwrap();
+        let inbox = inboxes.get_mut(&self.addr).unwrap();
+        Ok(inbox.pop_front().map(|(packet, from)| {
+            let len = packet.len().min(buf.len());
+            buf[..len].copy_from_slice(&packet[..len]);
+            (len, from)
+        }))
+    }
+}
+
+impl Drop for MemoryTransport {
+    fn drop(&mut self) {
+        self.network.inboxes.lock().unwrap().remove(&self.addr);
+    }
+}
+
+/// The channels of `connection_config` between two peers, over any transport.
+pub struct TransportConnection<T> {
+    transport: T,
+    peer: SocketAddr,
+    connection: RemoteConnection,
+    buffer: Vec<u8>,
+}
+
+impl<T: Transport> TransportConnection<T> {
+    pub fn new(transport: T, peer: SocketAddr) -> TransportConnection<T> {
+        let renet_config = connection_config();
+        let config = ConnectionConfig {
+            send_channels_config: renet_config.send_channels_config,
+            receive_channels_config: renet_config.receive_channels_config,
+            ..Default::default()
+        };
+        let buffer = vec![0; config.max_packet_size as usize];
+        TransportConnection { transport, peer, connection: RemoteConnection::new(config), buffer }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_connected()
+    }
+
+    pub fn send_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        self.connection.send_message(channel_id, message);
+    }
+
+    pub fn receive_message(&mut self, channel_id: u8) -> Option<Vec<u8>> {
+        self.connection.receive_message(channel_id)
+    }
+
+    /// Processes the packets the peer sent, advances the connection by the duration
+    /// and sends the packets it has for the peer. The packets of strangers are ignored.
+    pub fn update(&mut self, duration: Duration) -> io::Result<()> {
+        while let Some((len, from)) = self.transport.recv_from(&mut self.buffer)? {
+            if from == self.peer {
+                self.connection.process_packet(&self.buffer[..len]).map_err(rechannel_error)?;
+            }
+        }
+
+        self.connection.advance_time(duration);
+        self.connection.update().map_err(rechannel_error)?;
+        for packet in self.connection.get_packets_to_send().map_err(rechannel_error)? {
+            self.transport.send_to(&packet, self.peer)?;
+        }
+        Ok(())
+    }
+}
+
+fn rechannel_error(error: RechannelError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode, try_decode, ClientMessage, CLIENT_MESSAGES_CHANNEL};
+
+    #[test]
+    fn packets_are_delivered_in_memory() {
+        let network = MemoryNetwork::default();
+        let a = network.bind("10.0.0.1:5000".parse().unwrap()).unwrap();
+        let b = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+        let error = network.bind(a.local_addr().unwrap()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+
+        a.send_to(b"hello", b.local_addr().unwrap()).unwrap();
+        a.send_to(b"nobody", "10.0.0.3:5000".parse().unwrap()).unwrap();
+        let mut buf = [0; 16];
+        let (len, from) = b.recv_from(&mut buf).unwrap().unwrap();
+        assert_eq!((&buf[..len], from), (&b"hello"[..], a.local_addr().unwrap()));
+        assert_eq!(b.recv_from(&mut buf).unwrap(), None);
+
+        // The address is free again once the transport is dropped.
+        let addr = b.local_addr().unwrap();
+        drop(b);
+        a.send_to(b"lost", addr).unwrap();
+        let b = network.bind(addr).unwrap();
+        assert_eq!(b.recv_from(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn client_and_server_talk_without_sockets() {
+        let network = MemoryNetwork::default();
+        let server_addr = "10.0.0.1:5000".parse().unwrap();
+        let client_addr = "10.0.0.2:6000".parse().unwrap();
+        let mut server = TransportConnection::new(network.bind(server_addr).unwrap(), client_addr);
+        let mut client = TransportConnection::new(network.bind(client_addr).unwrap(), server_addr);
+
+        let message = ClientMessage::Chat("hello".to_string());
+        client.send_message(CLIENT_MESSAGES_CHANNEL, encode(&message));
+        let mut received = None;
+        for _ in 0..10 {
+            client.update(Duration::from_millis(16)).unwrap();
+            server.update(Duration::from_millis(16)).unwrap();
+            if let Some(bytes) = server.receive_message(CLIENT_MESSAGES_CHANNEL) {
+                received = try_decode(&bytes).ok();
+                break;
+            }
+        }
+
+        assert_eq!(received, Some(message));
+        assert!(client.is_connected());
+        assert!(server.is_connected());
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Arena, ConnectionStats, Player, WorldSync};
+
+/// The maximum number of bytes of player entries that we put in a single world sync message.
+pub const WORLD_SYNC_MAX_PART_BYTES: usize = 1024;
+
+/// A piece of the world sent for a given server tick, a `WorldSync` that is too big
+/// is split into multiple parts that the clients reassemble.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldSyncPart {
+    pub tick: u64,
+    pub index: u16,
+    pub total: u16,
+    pub world: WorldSync,
+    /// The positions of the players when they are sent quantized,
+    /// they are then missing from the world.
+    pub quantized: Option<QuantizedPositions>,
+    /// Nothing moved since the last world sent, this part is empty and only
+    /// tells the clients that the players are still where they were.
+    pub unchanged: bool,
+}
+
+/// Positions stored on 16 bits per axis, mapped to the bounds of the arena.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuantizedPositions {
+    pub arena: Arena,
+    pub positions: BTreeMap<Player, [u16; 2]>,
+}
+
+/// Maps a position in the arena to 16 bits per axis, the positions beyond the edges are clamped.
+pub fn quantize_position(position: Vec2, arena: Arena) -> [u16; 2] {
+    let axis = |value: f32, length: f32| {
+        let ratio = (value / length + 0.5).clamp(0., 1.);
+        (ratio * u16::MAX as f32).round() as u16
+    };
+    [axis(position.x, arena.width), axis(position.y, arena.height)]
+}
+
+pub fn dequantize_position(quantized: [u16; 2], arena: Arena) -> Vec2 {
+    let axis = |value: u16, length: f32| (value as f32 / u16::MAX as f32 - 0.5) * length;
+    Vec2::new(axis(quantized[0], arena.width), axis(quantized[1], arena.height))
+}
+
+impl WorldSyncPart {
+    /// The part sent instead of the world when nothing moved.
+    pub fn unchanged(tick: u64) -> WorldSyncPart {
+        WorldSyncPart {
+            tick,
+            index: 0,
+            total: 1,
+            world: WorldSync::default(),
+            quantized: None,
+            unchanged: true,
+        }
+    }
+
+    /// Replaces the positions of the world by their quantized version, to send half the bytes.
+    pub fn quantize(&mut self, arena: Arena) {
+        let positions = std::mem::take(&mut self.world.players_positions)
+            .into_iter()
+            .map(|(player, position)| (player, quantize_position(position, arena)))
+            .collect();
+        self.quantized = Some(QuantizedPositions { arena, positions });
+    }
+
+    /// Puts the quantized positions back into the world, if any.
+    pub fn dequantize(&mut self) {
+        if let Some(QuantizedPositions { arena, positions }) = self.quantized.take() {
+            let positions = positions
+                .into_iter()
+                .map(|(player, quantized)| (player, dequantize_position(quantized, arena)));
+            self.world.players_positions.extend(positions);
+        }
+    }
+}
+
+impl WorldSync {
+    /// Copies everything we know about this player from another world.
+    pub fn copy_player(&mut self, other: &WorldSync, player: Player) {
+        if let Some(position) = other.players_positions.get(&player) {
+            self.players_positions.insert(player, *position);
+        }
+        if let Some(stats) = other.players_connections.get(&player) {
+            self.players_connections.insert(player, *stats);
+        }
+        if let Some(teleports) = other.players_teleports.get(&player) {
+            self.players_teleports.insert(player, *teleports);
+        }
+    }
+
+    /// Returns the number of bytes the entries of this player take once serialized.
+    pub fn player_bytes(&self, player: Player) -> usize {
+        let position = self.players_positions.get(&player).map(|position| (player, position));
+        let stats = self.players_connections.get(&player).map(|stats| (player, stats));
+        let teleports = self.players_teleports.get(&player).map(|teleports| (player, teleports));
+        let position_size = position.map_or(0, |entry| bincode::serialized_size(&entry).unwrap());
+        let stats_size = stats.map_or(0, |entry| bincode::serialized_size(&entry).unwrap());
+        let teleports_size = teleports.map_or(0, |entry| bincode::serialized_size(&entry).unwrap());
+        (position_size + stats_size + teleports_size) as usize
+    }
+
+    /// Returns whether a player or an object appeared, disappeared or moved by more than `epsilon`.
+    pub fn positions_differ(&self, other: &WorldSync, epsilon: f32) -> bool {
+        fn differ<K: PartialEq>(
+            a: &BTreeMap<K, Vec2>,
+            b: &BTreeMap<K, Vec2>,
+            epsilon: f32,
+        ) -> bool {
+            a.len() != b.len()
+                || a.iter().zip(b).any(|((ka, a), (kb, b))| ka != kb || a.distance(*b) > epsilon)
+        }
+        differ(&self.players_positions, &other.players_positions, epsilon)
+            || differ(&self.objects, &other.objects, epsilon)
+    }
+
+    pub fn merge(&mut self, other: WorldSync) {
+        self.players_positions.extend(other.players_positions);
+        self.players_connections.extend(other.players_connections);
+        self.players_teleports.extend(other.players_teleports);
+        self.objects.extend(other.objects);
+    }
+}
+
+/// Splits the world into parts whose player entries don't exceed `max_bytes` once serialized,
+/// a part always contains at least one player even if it is bigger than that.
+/// The objects, there are only a few, are all in the first part.
+pub fn split_world_sync(tick: u64, world: &WorldSync, max_bytes: usize) -> Vec<WorldSyncPart> {
+    let objects = world.objects.clone();
+    let mut worlds = vec![WorldSync { objects, ..WorldSync::default() }];
+    let mut size = 0;
+
+    for player in world.players_positions.keys() {
+        let entry_size = world.player_bytes(*player);
+        let current = worlds.last().unwrap();
+        if size + entry_size > max_bytes && !current.players_positions.is_empty() {
+            worlds.push(WorldSync::default());
+            size = 0;
+        }
+        worlds.last_mut().unwrap().copy_player(world, *player);
+        size += entry_size;
+    }
+
+    let total = worlds.len() as u16;
+    worlds
+        .into_iter()
+        .enumerate()
+        .map(|(index, world)| WorldSyncPart {
+            tick,
+            index: index as u16,
+            total,
+            world,
+            quantized: None,
+            unchanged: false,
+        })
+        .collect()
+}
+
+/// Returns the number of bytes a single player takes in a serialized world sync.
+pub fn world_sync_player_bytes() -> usize {
+    let player = Player { id: 0 };
+    let mut world = WorldSync::default();
+    world.players_positions.insert(player, Vec2::ZERO);
+    world.players_connections.insert(player, ConnectionStats::default());
+    world.player_bytes(player)
+}
+
+/// Keeps at most `max_players` players of the world, the closest to `own` first.
+/// The `own` player is always kept, whatever the limit is.
+pub fn nearest_players(world: &WorldSync, own: Player, max_players: usize) -> WorldSync {
+    let own_position = world.players_positions.get(&own).copied();
+    let origin = own_position.unwrap_or(Vec2::ZERO);
+
+    let mut others: Vec<_> =
+        world.players_positions.iter().filter(|(player, _)| **player != own).collect();
+    others.sort_by(|(_, a), (_, b)| {
+        a.distance_squared(origin).total_cmp(&b.distance_squared(origin))
+    });
+
+    let mut nearest = WorldSync { objects: world.objects.clone(), ..WorldSync::default() };
+    nearest.copy_player(world, own);
+    for (player, _) in others {
+        if nearest.players_positions.len() >= max_players.max(1) {
+            break;
+        }
+        nearest.copy_player(world, *player);
+    }
+    nearest
+}
+
+/// Keeps the players of the world within `radius` of `own`, which is always kept.
+pub fn players_within(world: &WorldSync, own: Player, radius: f32) -> WorldSync {
+    let mut within = WorldSync { objects: world.objects.clone(), ..WorldSync::default() };
+    within.copy_player(world, own);
+    if let Some(origin) = world.players_positions.get(&own).copied() {
+        for (player, position) in &world.players_positions {
+            if position.distance(origin) <= radius {
+                within.copy_player(world, *player);
+            }
+        }
+    }
+    within
+}
+
+/// Gathers the parts of the world sent by the server. The parts are sent on an unreliable
+/// channel therefore an incomplete tick is discarded as soon as a part of a newer one arrives.
+#[derive(Debug, Default)]
+pub struct WorldSyncAssembler {
+    tick: u64,
+    parts: Vec<Option<WorldSync>>,
+}
+
+impl WorldSyncAssembler {
+    /// Returns the whole world once all the parts of a tick have been received.
+    pub fn push(&mut self, part: WorldSyncPart) -> Option<WorldSync> {
+        if part.tick < self.tick || part.index >= part.total {
+            return None;
+        }
+
+        if part.tick > self.tick || self.parts.len() != part.total as usize {
+            self.tick = part.tick;
+            self.parts = vec![None; part.total as usize];
+        }
+
+        self.parts[part.index as usize] = Some(part.world);
+        if self.parts.iter().any(Option::is_none) {
+            return None;
+        }
+
+        // We will ignore any duplicated part of this tick.
+        self.tick = part.tick + 1;
+        let mut world = WorldSync::default();
+        for part in self.parts.drain(..).flatten() {
+            world.merge(part);
+        }
+        Some(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world(players: u64) -> WorldSync {
+        let mut world = WorldSync::default();
+        for id in 0..players {
+            world.players_positions.insert(Player { id }, Vec2::new((id * id) as f32, 0.));
+        }
+        world
+    }
+
+    #[test]
+    fn parts_are_reassembled_in_any_order() {
+        let world = world(100);
+        let mut parts = split_world_sync(3, &world, 200);
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|part| part.total as usize == parts.len()));
+
+        parts.reverse();
+        let last = parts.pop().unwrap();
+        let mut assembler = WorldSyncAssembler::default();
+        for part in parts {
+            assert!(assembler.push(part).is_none());
+        }
+        let assembled = assembler.push(last).unwrap();
+        assert_eq!(assembled.players_positions, world.players_positions);
+    }
+
+    #[test]
+    fn a_part_always_holds_a_player() {
+        let parts = split_world_sync(0, &world(3), 0);
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|part| part.world.players_positions.len() == 1));
+    }
+
+    #[test]
+    fn over_budget_only_the_nearest_players_are_kept() {
+        let world = world(10);
+        let budget = 4 * world_sync_player_bytes();
+        let own = Player { id: 5 };
+        let nearest = nearest_players(&world, own, budget / world_sync_player_bytes());
+
+        let mut players: Vec<_> = nearest.players_positions.keys().map(|p| p.id).collect();
+        players.sort_unstable();
+        assert_eq!(players, vec![3, 4, 5, 6]);
+        let parts = split_world_sync(0, &nearest, budget);
+        assert_eq!(parts.len(), 1);
+    }
+
+    #[test]
+    fn our_player_is_kept_whatever_the_budget() {
+        let nearest = nearest_players(&world(10), Player { id: 9 }, 0);
+        assert_eq!(nearest.players_positions.len(), 1);
+        assert!(nearest.players_positions.contains_key(&Player { id: 9 }));
+    }
+
+    #[test]
+    fn stale_ticks_are_discarded() {
+        let mut assembler = WorldSyncAssembler::default();
+        let old = split_world_sync(4, &world(50), 200);
+        let new = split_world_sync(5, &world(2), 1024);
+        assert_eq!(new.len(), 1);
+
+        // A part of the old tick arrives, then the whole new tick.
+        assert!(assembler.push(old[0].clone()).is_none());
+        assert!(assembler.push(new[0].clone()).is_some());
+
+        // The rest of the old tick and a duplicate of the new one are ignored.
+        for part in old.into_iter().skip(1) {
+            assert!(assembler.push(part).is_none());
+        }
+        assert!(assembler.push(new[0].clone()).is_none());
+    }
+
+    #[test]
+    fn quantized_positions_stay_within_a_step() {
+        let arena = Arena { width: 1280., height: 720. };
+        // Half a step of rounding, with some room for the float precision.
+        let max_error = Vec2::new(arena.width, arena.height) / u16::MAX as f32 * 0.51;
+        for i in 0..=100 {
+            for j in 0..=100 {
+                let ratio = Vec2::new(i as f32, j as f32) / 100. - 0.5;
+                let position = ratio * Vec2::new(arena.width, arena.height);
+                let error =
+                    dequantize_position(quantize_position(position, arena), arena) - position;
+                assert!(error.abs().cmple(max_error).all(), "{} is off by {}", position, error);
+            }
+        }
+    }
+
+    #[test]
+    fn positions_beyond_the_arena_are_clamped() {
+        let arena = Arena { width: 1000., height: 500. };
+        assert_eq!(quantize_position(Vec2::new(-2000., 900.), arena), [0, u16::MAX]);
+        let corner = dequantize_position([0, u16::MAX], arena);
+        assert_eq!(corner, Vec2::new(-500., 250.));
+    }
+
+    #[test]
+    fn parts_are_dequantized_into_the_world() {
+        let arena = Arena::default();
+        let player = Player { id: 3 };
+        let mut world = WorldSync::default();
+        world.players_positions.insert(player, Vec2::new(12., -34.));
+        let mut part = split_world_sync(7, &world, 1024).remove(0);
+        part.quantize(arena);
+        assert!(part.world.players_positions.is_empty());
+
+        part.dequantize();
+        assert!(part.quantized.is_none());
+        let position = part.world.players_positions[&player];
+        assert!((position - Vec2::new(12., -34.)).length() < 0.1);
+    }
+
+    #[test]
+    fn only_moved_players_make_the_worlds_differ() {
+        let base = world(3);
+        assert!(!base.positions_differ(&base.clone(), 0.01));
+
+        let mut nudged = base.clone();
+        *nudged.players_positions.get_mut(&Player { id: 1 }).unwrap() += Vec2::new(0.005, 0.);
+        assert!(!base.positions_differ(&nudged, 0.01));
+
+        let mut moved = base.clone();
+        *moved.players_positions.get_mut(&Player { id: 1 }).unwrap() += Vec2::new(1., 0.);
+        assert!(base.positions_differ(&moved, 0.01));
+
+        // A player joined or left.
+        assert!(base.positions_differ(&world(4), 0.01));
+        assert!(world(4).positions_differ(&base, 0.01));
+    }
+
+    #[test]
+    fn unchanged_parts_are_empty() {
+        let part = WorldSyncPart::unchanged(12);
+        let part: WorldSyncPart = crate::try_decode(&crate::encode(&part)).unwrap();
+        assert!(part.unchanged);
+        assert_eq!(part.tick, 12);
+        assert!(part.world.players_positions.is_empty());
+    }
+
+    #[test]
+    fn objects_are_synced_with_the_first_part() {
+        let mut world = world(100);
+        world.objects.insert(ObjectId(0), Vec2::new(1., 2.));
+        let parts = split_world_sync(3, &world, 200);
+        assert!(parts.len() > 1);
+        assert_eq!(parts[0].world.objects, world.objects);
+        assert!(parts[1..].iter().all(|part| part.world.objects.is_empty()));
+        assert_eq!(nearest_players(&world, Player { id: 0 }, 2).objects, world.objects);
+
+        let mut moved = world.clone();
+        moved.objects.insert(ObjectId(0), Vec2::new(10., 2.));
+        assert!(!world.positions_differ(&world, 0.01));
+        assert!(world.positions_differ(&moved, 0.01));
+    }
+
+    #[test]
+    fn moving_by_the_epsilon_is_not_moving() {
+        let base = world(3);
+        let mut moved = base.clone();
+        *moved.players_positions.get_mut(&Player { id: 2 }).unwrap() += Vec2::new(0., 0.5);
+        assert!(!base.positions_differ(&moved, 0.5));
+        assert!(base.positions_differ(&moved, 0.25));
+        // A zero epsilon sends every change, however small.
+        assert!(!base.positions_differ(&base, 0.));
+        assert!(base.positions_differ(&moved, 0.));
+    }
+
+    #[test]
+    fn only_the_players_within_the_radius_are_kept() {
+        let world = world(4);
+        let within = players_within(&world, Player { id: 1 }, 3.);
+        let ids: Vec<_> = within.players_positions.keys().map(|player| player.id).collect();
+        assert_eq!(ids, [0, 1, 2]);
+        // Our own player is always there, even with no radius at all.
+        let within = players_within(&world, Player { id: 3 }, 0.);
+        assert_eq!(within.players_positions.keys().collect::<Vec<_>>(), [&Player { id: 3 }]);
+    }
+}
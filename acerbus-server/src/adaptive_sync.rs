@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+/// The RTT, in milliseconds, above which a client is considered congested.
+const CONGESTED_RTT: f32 = 250.0;
+/// The packet loss above which a client is considered congested.
+const CONGESTED_PACKET_LOSS: f32 = 0.05;
+/// The world is sent to every client at least once every this many ticks.
+pub const MAX_SYNC_INTERVAL: u64 = 6;
+
+/// Sends the world less often to the clients that can't receive it every tick.
+pub struct AdaptiveSync {
+    /// The bandwidth, in kilobits per second, a client is assumed to have when it loses no packet.
+    /// It is also the most a client is considered to have.
+    pub client_kbps: f32,
+    /// The tick at which the world was last sent to each client.
+    last_sent: HashMap<u64, u64>,
+}
+
+impl AdaptiveSync {
+    pub fn new(client_kbps: f32) -> AdaptiveSync {
+        AdaptiveSync { client_kbps, last_sent: HashMap::new() }
+    }
+
+    /// Whether the world must be sent to this client at this tick, it is then recorded as sent.
+    pub fn is_due(&mut self, client_id: u64, tick: u64, interval: u64) -> bool {
+        let due = self.last_sent.get(&client_id).map_or(true, |last| tick - last >= interval);
+        if due {
+            self.last_sent.insert(client_id, tick);
+        }
+        due
+    }
+
+    /// Forgets the clients that are not connected anymore.
+    pub fn retain(&mut self, client_ids: &[u64]) {
+        self.last_sent.retain(|client_id, _| client_ids.contains(client_id));
+    }
+}
+
+/// Returns the bandwidth available to a client. When it loses packets, its connection is
+/// saturated and it only has what was measured to reach it, the part of `sent_kbps` not lost.
+pub fn available_kbps(client_kbps: f32, sent_kbps: f32, packet_loss: f32) -> f32 {
+    if packet_loss <= 0. {
+        return client_kbps;
+    }
+    (sent_kbps * (1. - packet_loss.min(1.))).min(client_kbps)
+}
+
+/// Returns every how many ticks the world is sent to a client with this much bandwidth,
+/// often enough to use it without exceeding it, and half as often when it is congested.
+pub fn sync_interval(available_kbps: f32, world_kbps: f32, rtt: f32, packet_loss: f32) -> u64 {
+    let mut interval = if available_kbps > 0. {
+        (world_kbps / available_kbps).ceil().max(1.) as u64
+    } else {
+        MAX_SYNC_INTERVAL
+    };
+    if rtt > CONGESTED_RTT || packet_loss > CONGESTED_PACKET_LOSS {
+        interval *= 2;
+    }
+    interval.clamp(1, MAX_SYNC_INTERVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constrained_clients_are_synced_less_often() {
+        let world_kbps = 200.;
+        let fast = sync_interval(available_kbps(1000., 200., 0.), world_kbps, 50., 0.);
+        let slow = sync_interval(available_kbps(1000., 200., 0.5), world_kbps, 50., 0.);
+        assert_eq!(fast, 1);
+        assert!(slow > fast);
+        // A congested client is synced less often with the same bandwidth.
+        let congested = sync_interval(400., world_kbps, 300., 0.);
+        assert!(congested > sync_interval(400., world_kbps, 50., 0.));
+        assert_eq!(sync_interval(0., world_kbps, 50., 0.), MAX_SYNC_INTERVAL);
+    }
+
+    #[test]
+    fn available_bandwidth_is_measured_when_losing_packets() {
+        assert_eq!(available_kbps(1000., 200., 0.), 1000.);
+        assert_eq!(available_kbps(1000., 200., 0.25), 150.);
+        assert_eq!(available_kbps(100., 400., 0.5), 100.);
+    }
+
+    #[test]
+    fn sync_timers_are_kept_per_client() {
+        let mut adaptive = AdaptiveSync::new(1000.);
+        assert!(adaptive.is_due(1, 0, 3));
+        assert!(adaptive.is_due(2, 1, 3));
+        assert!(!adaptive.is_due(1, 2, 3));
+        assert!(adaptive.is_due(1, 3, 3));
+        assert!(!adaptive.is_due(2, 3, 3));
+
+        adaptive.retain(&[1]);
+        assert!(adaptive.is_due(2, 4, 3));
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use acerbus_common::Player;
+
+/// Only the players around its own are sent to a client, within the radius it asked for
+/// or the default one, but never beyond the maximum radius of the server.
+#[derive(Debug, Clone)]
+pub struct AreaOfInterest {
+    pub default_radius: f32,
+    pub max_radius: f32,
+    requested: HashMap<Player, f32>,
+}
+
+impl AreaOfInterest {
+    pub fn new(default_radius: f32, max_radius: f32) -> AreaOfInterest {
+        let default_radius = clamp_view_radius(default_radius, max_radius);
+        AreaOfInterest { default_radius, max_radius, requested: HashMap::new() }
+    }
+
+    /// Records the radius requested by the player and returns the one it will be given.
+    pub fn request(&mut self, player: Player, radius: f32) -> f32 {
+        let radius = clamp_view_radius(radius, self.max_radius);
+        self.requested.insert(player, radius);
+        radius
+    }
+
+    pub fn radius(&self, player: Player) -> f32 {
+        self.requested.get(&player).copied().unwrap_or(self.default_radius)
+    }
+}
+
+/// A requested radius that is not a number gives the smallest view.
+pub fn clamp_view_radius(requested: f32, max_radius: f32) -> f32 {
+    requested.max(0.).min(max_radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radius_is_clamped_to_the_server_max() {
+        assert_eq!(clamp_view_radius(300., 500.), 300.);
+        assert_eq!(clamp_view_radius(900., 500.), 500.);
+        assert_eq!(clamp_view_radius(-10., 500.), 0.);
+        assert_eq!(clamp_view_radius(f32::NAN, 500.), 0.);
+    }
+
+    #[test]
+    fn players_get_the_radius_they_asked_for() {
+        let mut aoi = AreaOfInterest::new(800., 500.);
+        assert_eq!(aoi.default_radius, 500.);
+
+        let (asking, other) = (Player { id: 1 }, Player { id: 2 });
+        assert_eq!(aoi.request(asking, 200.), 200.);
+        assert_eq!(aoi.radius(asking), 200.);
+        assert_eq!(aoi.radius(other), 500.);
+    }
+}
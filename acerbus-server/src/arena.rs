@@ -0,0 +1,124 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+use heron::prelude::*;
+
+use crate::size_class::keep_inside_arena;
+use crate::teleport::{scale_teleport_pads, TeleportPads};
+
+/// The arena grows with the number of players, starting from its base size.
+pub struct ArenaScaling {
+    pub base: Arena,
+    /// By how much the width grows for each player after the first one,
+    /// the height grows proportionally, zero to keep the arena size fixed.
+    pub per_player: f32,
+}
+
+#[derive(Component)]
+pub struct Wall;
+
+/// Returns the size of the arena for this number of players.
+pub fn arena_for_players(base: Arena, per_player: f32, players: usize) -> Arena {
+    let extra_width = per_player * players.saturating_sub(1) as f32;
+    let extra_height = extra_width * base.height / base.width;
+    Arena { width: base.width + extra_width, height: base.height + extra_height }
+}
+
+/// Resizes the arena, and moves its teleport pads with it, when players join or leave
+/// and tells the clients about it.
+pub fn resize_arena_system(
+    scaling: Res<ArenaScaling>,
+    lobby: Res<Lobby>,
+    mut arena: ResMut<Arena>,
+    mut pads: ResMut<TeleportPads>,
+    mut server: ResMut<RenetServer>,
+) {
+    if scaling.per_player == 0. || !lobby.is_changed() {
+        return;
+    }
+
+    let new_arena = arena_for_players(scaling.base, scaling.per_player, lobby.players.len());
+    if new_arena != *arena {
+        if !pads.0.is_empty() {
+            pads.0 = scale_teleport_pads(&pads.0, *arena, new_arena);
+        }
+        *arena = new_arena;
+        let message = encode(&ServerMessage::ArenaChanged {
+            arena: new_arena,
+            teleport_pads: pads.0.clone(),
+        });
+        server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+    }
+}
+
+/// (Re)builds the walls every time the arena changes and brings
+/// the players back inside when it shrinks.
+pub fn arena_walls_system(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    walls: Query<Entity, With<Wall>>,
+    mut players: Query<(&mut Transform, Option<&SizeClass>, Option<&BodyShape>), With<Player>>,
+) {
+    if !arena.is_changed() {
+        return;
+    }
+
+    for entity in walls.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    for (center, half_size) in wall_bounds(*arena) {
+        commands
+            .spawn()
+            .insert(Transform::from_translation(center.extend(0.)))
+            .insert(GlobalTransform::default())
+            .insert(Wall)
+            .insert(RigidBody::Static)
+            .insert(CollisionShape::Cuboid {
+                half_extends: half_size.extend(0.),
+                border_radius: None,
+            });
+    }
+
+    for (mut transform, size, shape) in players.iter_mut() {
+        let size = size.copied().unwrap_or_default();
+        let shape = shape.copied().unwrap_or_default();
+        let position = keep_inside_arena(transform.translation.truncate(), shape, size, *arena);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: Arena = Arena { width: 800., height: 600. };
+
+    #[test]
+    fn the_arena_grows_with_the_players() {
+        assert_eq!(arena_for_players(BASE, 100., 0), BASE);
+        assert_eq!(arena_for_players(BASE, 100., 1), BASE);
+        assert_eq!(arena_for_players(BASE, 100., 3), Arena { width: 1000., height: 750. });
+        assert_eq!(arena_for_players(BASE, 0., 10), BASE);
+    }
+
+    #[test]
+    fn players_are_brought_back_when_the_arena_shrinks() {
+        let mut world = World::new();
+        world.insert_resource(Arena { width: 200., height: 100. });
+        let player = world
+            .spawn()
+            .insert(Player { id: 1 })
+            .insert(Transform::from_xyz(500., -500., 0.))
+            .id();
+
+        let mut stage = SystemStage::single_threaded().with_system(arena_walls_system);
+        stage.run(&mut world);
+
+        let translation = world.get::<Transform>(player).unwrap().translation;
+        assert_eq!(translation.x, (200. - PLAYER_SQUARE_WIDTH) / 2.);
+        assert_eq!(translation.y, -(100. - PLAYER_SQUARE_HEIGHT) / 2.);
+        assert_eq!(world.query::<&Wall>().iter(&world).count(), 4);
+    }
+}
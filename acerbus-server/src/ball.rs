@@ -0,0 +1,38 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+use heron::prelude::*;
+
+use crate::elimination::is_out_of_bounds;
+
+/// The ball the players push around, it is heavier than nothing but
+/// lighter than a player and slows down on its own.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct Ball;
+
+pub fn spawn_ball(mut commands: Commands) {
+    commands
+        .spawn()
+        .insert(Transform::default())
+        .insert(GlobalTransform::default())
+        .insert(Ball)
+        .insert(ObjectId(0))
+        .insert(RigidBody::Dynamic)
+        .insert(CollisionShape::Sphere { radius: BALL_RADIUS })
+        .insert(PhysicMaterial { restitution: 0.8, density: 0.2, ..default() })
+        .insert(Damping::from_linear(1.5))
+        .insert(Velocity::default())
+        .insert(RotationConstraints::lock());
+}
+
+/// Puts the ball back at the center of the arena when it left it, with the walls removed.
+pub fn reset_ball_system(
+    arena: Res<Arena>,
+    mut balls: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+) {
+    for (mut transform, mut velocity) in balls.iter_mut() {
+        if is_out_of_bounds(transform.translation.xy(), *arena) {
+            transform.translation = Vec3::new(0., 0., transform.translation.z);
+            *velocity = Velocity::default();
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Tracks the running servers and sends the clients that ask to the least loaded one.
+
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+struct Opt {
+    #[clap(long, short, default_value = "127.0.0.1:4999")]
+    listen_addr: SocketAddr,
+
+    /// How long, in seconds, a server can miss its heartbeats before being forgotten.
+    #[clap(long, default_value = "6")]
+    server_timeout: f64,
+}
+
+fn main() {
+    let opt = Opt::parse();
+    let socket = UdpSocket::bind(opt.listen_addr).unwrap();
+    // We wake up regularly to forget the servers that stopped sending heartbeats.
+    socket.set_read_timeout(Some(HEARTBEAT_INTERVAL)).unwrap();
+    println!("Coordinating the servers on {}.", opt.listen_addr);
+
+    let mut registry = ServerRegistry::new(Duration::from_secs_f64(opt.server_timeout));
+    let mut buffer = [0; 1024];
+    loop {
+        let now = Instant::now();
+        for addr in registry.evict_stale(now) {
+            println!("The server {} stopped sending heartbeats.", addr);
+        }
+
+        let (len, from) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(_) => continue,
+        };
+        match try_decode(&buffer[..len]) {
+            Ok(CoordinatorRequest::Heartbeat { game_addr, info }) => {
+                registry.heartbeat(advertised_addr(game_addr, from), info, now);
+            }
+            Ok(CoordinatorRequest::FindServer { nonce }) => {
+                let server = registry.least_loaded();
+                println!("Sending {} to {:?}.", from, server);
+                let answer = encode(&ServerAssignment { nonce, server });
+                if let Err(e) = socket.send_to(&answer, from) {
+                    eprintln!("Failed to answer {}: {}", from, e);
+                }
+            }
+            Err(e) => eprintln!("Skipped a request from {}: {}", from, e),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::console::AdminCommand;
+
+/// Limits the number of chat messages a player can send in a given window of time,
+/// a player exceeding it is muted for a cooldown period.
+#[derive(Debug)]
+pub struct ChatRateLimit {
+    pub max_messages: usize,
+    pub window: Duration,
+    pub cooldown: Duration,
+    sent: HashMap<Player, VecDeque<Instant>>,
+    muted_until: HashMap<Player, Instant>,
+}
+
+impl ChatRateLimit {
+    pub fn new(max_messages: usize, window: Duration, cooldown: Duration) -> ChatRateLimit {
+        ChatRateLimit {
+            max_messages,
+            window,
+            cooldown,
+            sent: HashMap::new(),
+            muted_until: HashMap::new(),
+        }
+    }
+
+    /// Records a message sent by this player, returns the time it must wait before
+    /// sending another one if the message must be dropped.
+    pub fn check(&mut self, player: Player, now: Instant) -> Result<(), Duration> {
+        if let Some(until) = self.muted_until.get(&player).copied() {
+            if now < until {
+                return Err(until - now);
+            }
+            self.muted_until.remove(&player);
+        }
+
+        let sent = self.sent.entry(player).or_default();
+        while sent.front().map_or(false, |at| now.duration_since(*at) >= self.window) {
+            sent.pop_front();
+        }
+
+        if sent.len() >= self.max_messages {
+            sent.clear();
+            self.muted_until.insert(player, now + self.cooldown);
+            return Err(self.cooldown);
+        }
+
+        sent.push_back(now);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, player: Player) {
+        self.sent.remove(&player);
+        self.muted_until.remove(&player);
+    }
+}
+
+/// A private message, sent with `/w <name> <text>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Whisper<'a> {
+    pub target: &'a str,
+    pub text: &'a str,
+}
+
+/// Returns `None` if this chat message is not a whisper and an error
+/// if it is one but doesn't have a target and a text.
+pub fn parse_whisper(text: &str) -> Option<Result<Whisper, &'static str>> {
+    let rest = text.strip_prefix("/w")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let rest = rest.trim_start();
+    let (target, text) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let text = text.trim();
+    if target.is_empty() || text.is_empty() {
+        Some(Err("usage: /w <name> <text>"))
+    } else {
+        Some(Ok(Whisper { target, text }))
+    }
+}
+
+/// Finds the connected player a whisper is addressed to by its name, whatever its case,
+/// or by its id for the players whose name can't be typed as a single word.
+pub fn resolve_whisper_target(target: &str, connected: &[(Player, PlayerName)]) -> Option<Player> {
+    let lowercase_target = target.to_lowercase();
+    let by_name = connected.iter().find(|(_, name)| name.0.to_lowercase() == lowercase_target);
+    let by_id = || {
+        let id: u64 = target.parse().ok()?;
+        connected.iter().find(|(player, _)| player.id == id)
+    };
+    by_name.or_else(by_id).map(|(player, _)| *player)
+}
+
+/// Returns the player to which the whisper must be sent with the message to send, the sender
+/// is sent the reason of the failure if the whisper is malformed or can't be delivered.
+pub fn whisper_message(
+    from: Player,
+    whisper: Result<Whisper, &'static str>,
+    connected: &[(Player, PlayerName)],
+) -> (Player, ServerMessage) {
+    let target = whisper.and_then(|whisper| {
+        let target = resolve_whisper_target(whisper.target, connected);
+        target.map(|target| (target, whisper.text)).ok_or("this player is not connected")
+    });
+
+    match target {
+        Ok((target, text)) => (target, ServerMessage::Whisper { from, text: text.to_string() }),
+        Err(reason) => (from, ServerMessage::WhisperFailed { reason: reason.to_string() }),
+    }
+}
+
+/// Sends an announcement to all the players with `say <text>`.
+pub fn announce_command_system(
+    mut commands: EventReader<AdminCommand>,
+    mut server: ResMut<RenetServer>,
+) {
+    for AdminCommand(words) in commands.iter() {
+        match words.split_first() {
+            Some((command, text)) if command == "say" && !text.is_empty() => {
+                let text = text.join(" ");
+                println!("[announcement] {}", text);
+                let message = encode(&ServerMessage::Announcement { text });
+                server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+            }
+            Some((command, _)) if command == "say" => println!("usage: say <text>"),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spamming_players_are_muted() {
+        let secs = Duration::from_secs;
+        let mut limit = ChatRateLimit::new(3, secs(5), secs(10));
+        let (player, other) = (Player { id: 1 }, Player { id: 2 });
+        let now = Instant::now();
+
+        for i in 0..3 {
+            assert_eq!(limit.check(player, now + secs(i)), Ok(()));
+        }
+        assert_eq!(limit.check(player, now + secs(3)), Err(secs(10)));
+        // The muted player is told how long it must still wait, the others can talk.
+        assert_eq!(limit.check(player, now + secs(7)), Err(secs(6)));
+        assert_eq!(limit.check(other, now + secs(7)), Ok(()));
+
+        assert_eq!(limit.check(player, now + secs(13)), Ok(()));
+    }
+
+    #[test]
+    fn messages_spread_over_the_window_are_accepted() {
+        let secs = Duration::from_secs;
+        let mut limit = ChatRateLimit::new(2, secs(5), secs(10));
+        let player = Player { id: 1 };
+        let now = Instant::now();
+        for i in 0..10 {
+            assert_eq!(limit.check(player, now + secs(i * 3)), Ok(()));
+        }
+    }
+
+    #[test]
+    fn whispers_are_parsed() {
+        assert_eq!(parse_whisper("hello"), None);
+        assert_eq!(parse_whisper("/wave"), None);
+        let whisper = Whisper { target: "Kero", text: "hello there" };
+        assert_eq!(parse_whisper("/w  Kero hello there "), Some(Ok(whisper)));
+        assert_eq!(parse_whisper("/w"), Some(Err("usage: /w <name> <text>")));
+        assert_eq!(parse_whisper("/w Kero"), Some(Err("usage: /w <name> <text>")));
+    }
+
+    fn connected() -> Vec<(Player, PlayerName)> {
+        vec![
+            (Player { id: 1 }, PlayerName("Kero".to_string())),
+            (Player { id: 2 }, PlayerName("Ünïcode".to_string())),
+            (Player { id: 3 }, PlayerName("two words".to_string())),
+        ]
+    }
+
+    #[test]
+    fn whispers_are_addressed_by_name_or_by_id() {
+        let connected = connected();
+        assert_eq!(resolve_whisper_target("kERO", &connected), Some(Player { id: 1 }));
+        assert_eq!(resolve_whisper_target("üNÏCODE", &connected), Some(Player { id: 2 }));
+        assert_eq!(resolve_whisper_target("3", &connected), Some(Player { id: 3 }));
+        assert_eq!(resolve_whisper_target("4", &connected), None);
+        assert_eq!(resolve_whisper_target("Mops", &connected), None);
+    }
+
+    /// The reason of a failed whisper, `None` for the other messages.
+    fn failure(message: &ServerMessage) -> Option<&str> {
+        match message {
+            ServerMessage::WhisperFailed { reason } => Some(reason),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn whispers_are_delivered_or_explained_to_the_sender() {
+        let connected = connected();
+        let from = Player { id: 2 };
+        let whisper = Ok(Whisper { target: "kero", text: "hello" });
+        match whisper_message(from, whisper, &connected) {
+            (Player { id: 1 }, ServerMessage::Whisper { from: sender, text }) => {
+                assert_eq!((sender, text.as_str()), (from, "hello"))
+            }
+            other => panic!("the whisper was not delivered: {:?}", other),
+        }
+
+        let whisper = Ok(Whisper { target: "Mops", text: "hello" });
+        let (recipient, message) = whisper_message(from, whisper, &connected);
+        assert_eq!((recipient, failure(&message)), (from, Some("this player is not connected")));
+
+        let usage = "usage: /w <name> <text>";
+        let (recipient, message) = whisper_message(from, Err(usage), &connected);
+        assert_eq!((recipient, failure(&message)), (from, Some(usage)));
+    }
+}
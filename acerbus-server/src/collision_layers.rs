@@ -0,0 +1,56 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+use clap::ArgEnum;
+use heron::prelude::*;
+use serde::Serialize;
+
+/// Whether the players of the same team collide with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FriendlyCollisions {
+    On,
+    Off,
+}
+
+/// The most teams there can be, every team needs a collision group of its own. Of the 16
+/// groups the players use, the group 0 is reserved for the players without a team, the walls
+/// and the pads, which leaves 15 groups, the teams 0 to 14 are in the groups 1 to 15.
+pub const MAX_TEAMS: u8 = 15;
+
+/// The collision layers of a player that passes through its teammates,
+/// every team has its own group and collides with everything but it.
+pub fn team_collision_layers(team: Team) -> CollisionLayers {
+    let group = team_collision_group(team);
+    CollisionLayers::from_bits(group, !group)
+}
+
+/// The group of a team, shifted by one as the group 0 is reserved.
+pub fn team_collision_group(team: Team) -> u32 {
+    debug_assert!(team.0 < MAX_TEAMS, "{:?} has no collision group", team);
+    1 << (1 + team.0 as u32)
+}
+
+/// Puts the players in the collision group of their team when they join one.
+pub fn friendly_collisions_system(
+    mut commands: Commands,
+    players: Query<(Entity, &Team), (With<Player>, Added<Team>)>,
+) {
+    for (entity, team) in players.iter() {
+        commands.entity(entity).insert(team_collision_layers(*team));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_team_has_its_own_group() {
+        let groups: Vec<_> = (0..MAX_TEAMS).map(|team| team_collision_group(Team(team))).collect();
+        for (i, group) in groups.iter().enumerate() {
+            assert_ne!(*group, 1, "the first group is not for the teams");
+            assert!(*group < 1 << 16);
+            assert!(!groups[i + 1..].contains(group));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+/// How long the clients are given to receive the reason of their disconnection.
+const DISCONNECT_DELAY: Duration = Duration::from_millis(200);
+
+/// Maps the transient client ids of the connections to the players, a player is identified
+/// by the client id of its first connection and keeps it when it reconnects with a new one.
+#[derive(Debug, Default)]
+pub struct Connections {
+    players: HashMap<u64, Player>,
+    /// The clients that received everything sent on connection, the world
+    /// is only sent to them so that they know about all the players in it.
+    initialized: HashSet<u64>,
+    /// The clients that were told why they are disconnected and when to disconnect them.
+    disconnecting: Vec<(u64, Instant)>,
+}
+
+impl Connections {
+    pub fn insert(&mut self, client_id: u64, player: Player) {
+        self.players.insert(client_id, player);
+    }
+
+    /// Returns the player of this connection, a client that is not known yet is its own player.
+    pub fn player(&self, client_id: u64) -> Player {
+        self.players.get(&client_id).copied().unwrap_or(Player { id: client_id })
+    }
+
+    /// Returns the id of the client through which this player is connected, if any.
+    pub fn client_id(&self, player: Player) -> Option<u64> {
+        self.players.iter().find(|(_, p)| **p == player).map(|(client_id, _)| *client_id)
+    }
+
+    pub fn remove(&mut self, client_id: u64) -> Player {
+        self.initialized.remove(&client_id);
+        self.players.remove(&client_id).unwrap_or(Player { id: client_id })
+    }
+
+    /// Records that this client acknowledged its `Welcome`.
+    pub fn set_initialized(&mut self, client_id: u64) {
+        if self.players.contains_key(&client_id) {
+            self.initialized.insert(client_id);
+        }
+    }
+
+    /// The clients to which the world can be sent.
+    pub fn initialized(&self) -> Vec<u64> {
+        self.initialized.iter().copied().collect()
+    }
+
+    /// Whether this player can be put in the world sent to the clients, the players that just
+    /// connected are only sent once the others had time to receive their `PlayerConnected`.
+    pub fn is_player_initialized(&self, player: Player) -> bool {
+        self.client_id(player).map_or(true, |client_id| self.initialized.contains(&client_id))
+    }
+
+    /// Tells the client why it is disconnected and disconnects it a bit later,
+    /// giving the message a chance to arrive before the connection is closed.
+    pub fn disconnect(&mut self, server: &mut RenetServer, client_id: u64, reason: &str) {
+        let message = encode(&ServerMessage::Disconnecting { reason: reason.to_string() });
+        server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+        self.disconnecting.push((client_id, Instant::now() + DISCONNECT_DELAY));
+    }
+
+    /// Whether this client is about to be disconnected, its messages must be ignored.
+    pub fn is_disconnecting(&self, client_id: u64) -> bool {
+        self.disconnecting.iter().any(|(id, _)| *id == client_id)
+    }
+}
+
+pub fn disconnect_clients_system(
+    mut server: ResMut<RenetServer>,
+    mut connections: ResMut<Connections>,
+) {
+    let now = Instant::now();
+    connections.disconnecting.retain(|(client_id, at)| {
+        let disconnect = *at <= now;
+        if disconnect {
+            server.disconnect(*client_id);
+        }
+        !disconnect
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnected_clients_keep_their_player() {
+        let mut connections = Connections::default();
+        let player = connections.player(7);
+        assert_eq!(player, Player { id: 7 });
+        connections.insert(7, player);
+
+        // The player reconnects through a new client.
+        assert_eq!(connections.remove(7), player);
+        connections.insert(12, player);
+        assert_eq!(connections.player(12), player);
+        assert_eq!(connections.client_id(player), Some(12));
+        assert_eq!(connections.client_id(Player { id: 12 }), None);
+    }
+
+    #[test]
+    fn the_world_waits_for_the_welcome_acknowledgement() {
+        let mut connections = Connections::default();
+        let (old, new) = (Player { id: 1 }, Player { id: 2 });
+        connections.insert(1, old);
+        connections.set_initialized(1);
+        connections.insert(2, new);
+        assert_eq!(connections.initialized(), [1]);
+        assert!(connections.is_player_initialized(old));
+        assert!(!connections.is_player_initialized(new));
+
+        connections.set_initialized(2);
+        let mut initialized = connections.initialized();
+        initialized.sort_unstable();
+        assert_eq!(initialized, [1, 2]);
+        assert!(connections.is_player_initialized(new));
+
+        // An acknowledgement from an unknown client is ignored and
+        // the clients must acknowledge again when they reconnect.
+        connections.set_initialized(3);
+        connections.remove(1);
+        assert_eq!(connections.initialized(), [2]);
+        // The players without a client, like the dummies, are always sent.
+        assert!(connections.is_player_initialized(Player { id: 3 }));
+    }
+
+    #[test]
+    fn disconnected_clients_are_ignored_until_closed() {
+        let mut server = crate::new_renet_server("127.0.0.1:0".parse().unwrap(), 8);
+        let mut connections = Connections::default();
+        connections.disconnect(&mut server, 7, "the server is full");
+        assert!(connections.is_disconnecting(7));
+        assert!(!connections.is_disconnecting(8));
+    }
+}
@@ -0,0 +1,102 @@
+use std::io::Read;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::shutdown::{Shutdown, POLL_INTERVAL};
+
+/// As big as the buffer of the standard input, a read never leaves bytes in it
+/// that would not be seen when polling the standard input.
+const STDIN_READ_SIZE: usize = 8 * 1024;
+
+/// A line typed by the administrator, split into words.
+#[derive(Debug, Clone)]
+pub struct AdminCommand(pub Vec<String>);
+
+/// The commands typed by the administrator on the server standard input.
+pub struct AdminConsole(Mutex<Receiver<String>>);
+
+impl AdminConsole {
+    /// Reads the standard input on a dedicated thread, one command per line.
+    /// The thread only reads once something was typed to see the shutdown in the meantime.
+    pub fn spawn(shutdown: &Shutdown) -> AdminConsole {
+        let (sender, receiver) = mpsc::channel();
+        shutdown.spawn("admin-console", move |shutdown| {
+            let mut pending = Vec::new();
+            let mut buffer = vec![0; STDIN_READ_SIZE];
+            while !shutdown.is_signaled() {
+                if !stdin_readable(POLL_INTERVAL) {
+                    continue;
+                }
+                match std::io::stdin().read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(len) => pending.extend_from_slice(&buffer[..len]),
+                }
+                if take_lines(&mut pending).into_iter().any(|line| sender.send(line).is_err()) {
+                    break;
+                }
+            }
+        });
+        AdminConsole(Mutex::new(receiver))
+    }
+
+    /// Returns the commands typed since the last call.
+    pub fn commands(&self) -> Vec<String> {
+        self.0.lock().unwrap().try_iter().collect()
+    }
+}
+
+/// Removes the complete lines from the bytes read so far, without their line ending.
+pub fn take_lines(pending: &mut Vec<u8>) -> Vec<String> {
+    let mut lines = Vec::new();
+    while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+        let line: Vec<_> = pending.drain(..=end).collect();
+        let line = String::from_utf8_lossy(&line);
+        lines.push(line.trim_end_matches(&['\r', '\n'][..]).to_string());
+    }
+    lines
+}
+
+/// Waits at most `timeout` for something to read on the standard input.
+#[cfg(unix)]
+fn stdin_readable(timeout: Duration) -> bool {
+    let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    let timeout = timeout.as_millis().try_into().unwrap_or(libc::c_int::MAX);
+    // SAFETY: the single pollfd given lives during the whole call.
+    unsafe { libc::poll(&mut fd, 1, timeout) > 0 }
+}
+
+/// The standard input can't be polled here, the reads block until a line is typed
+/// and the thread is left behind if the server exits in the meantime.
+#[cfg(not(unix))]
+fn stdin_readable(_timeout: Duration) -> bool {
+    true
+}
+
+/// Forwards the typed commands as events, each system handles the commands it knows.
+pub fn admin_console_system(console: Res<AdminConsole>, mut commands: EventWriter<AdminCommand>) {
+    for line in console.commands() {
+        let words: Vec<_> = line.split_whitespace().map(String::from).collect();
+        if !words.is_empty() {
+            commands.send(AdminCommand(words));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incomplete_lines_are_kept() {
+        let mut pending = b"kick 3\r\nannounce hel".to_vec();
+        assert_eq!(take_lines(&mut pending), vec!["kick 3".to_string()]);
+        assert_eq!(pending, b"announce hel");
+
+        pending.extend_from_slice(b"lo\n\n");
+        assert_eq!(take_lines(&mut pending), vec!["announce hello".to_string(), String::new()]);
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+use serde::Serialize;
+
+use crate::connections::Connections;
+use crate::elimination::Score;
+use crate::idle::FullServerPolicy;
+use crate::reconnect::PendingDisconnects;
+use crate::shutdown::{Shutdown, POLL_INTERVAL};
+
+/// How long a tool has to send its request before the connection is dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// The number of requests handled at the same time, the others are answered 503 right away.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// What a tool asks the server, once the request is authorized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// `GET /players`
+    ListPlayers,
+    /// `POST /players/<id>/kick`
+    Kick(Player),
+    /// `GET /status`
+    Status,
+    NotFound,
+    MethodNotAllowed,
+}
+
+pub fn route(method: &str, path: &str) -> Route {
+    let segments: Vec<_> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        ("GET", ["players"]) => Route::ListPlayers,
+        ("GET", ["status"]) => Route::Status,
+        ("POST", ["players", id, "kick"]) => match id.parse() {
+            Ok(id) => Route::Kick(Player { id }),
+            Err(_) => Route::NotFound,
+        },
+        (_, ["players"] | ["status"] | ["players", _, "kick"]) => Route::MethodNotAllowed,
+        _ => Route::NotFound,
+    }
+}
+
+/// Whether the `Authorization` header carries the bearer token of the server.
+pub fn authorized(header: Option<&str>, token: &str) -> bool {
+    let given = header.and_then(|header| header.trim().strip_prefix("Bearer "));
+    // Every byte is compared to not tell how much of the token was right.
+    given.map_or(false, |given| {
+        given.len() == token.len()
+            && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json<T: Serialize>(value: &T) -> Response {
+        Response { status: 200, body: serde_json::to_string(value).unwrap() }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        let body = serde_json::json!({ "error": message }).to_string();
+        Response { status, body }
+    }
+
+    fn write_to(&self, stream: &mut TcpStream) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Service Unavailable",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            self.status,
+            reason,
+            self.body.len(),
+            self.body
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ControlPlayer {
+    pub id: u64,
+    pub team: Option<u8>,
+    pub score: u32,
+    /// Whether the player is waiting to reconnect.
+    pub disconnected: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ControlStatus {
+    pub players: usize,
+    pub max_players: usize,
+    pub playing: bool,
+}
+
+/// An authorized request waiting for the game to answer it.
+struct PendingRequest {
+    route: Route,
+    respond: Sender<Response>,
+}
+
+/// An HTTP API for the orchestration tools, to list and kick the players or get the status
+/// of the server. Every connection is handled on its own thread, a slow tool doesn't keep
+/// the others waiting, and only the authorized requests reach the game. The connections are
+/// accepted, read and answered without blocking for longer than the `POLL_INTERVAL`,
+/// to see the shutdown.
+pub struct ControlApi(Mutex<Receiver<PendingRequest>>);
+
+impl ControlApi {
+    pub fn spawn(addr: SocketAddr, token: String, shutdown: &Shutdown) -> io::Result<ControlApi> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let (sender, receiver) = mpsc::channel();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        shutdown.spawn("control-api", move |shutdown| {
+            while !shutdown.is_signaled() {
+                let mut stream = match listener.accept() {
+                    Ok((stream, _)) => stream,
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Failed to accept a control request: {}", e);
+                        continue;
+                    }
+                };
+
+                if in_flight.fetch_add(1, Ordering::SeqCst) >= MAX_CONCURRENT_REQUESTS {
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    let _ = Response::error(503, "too many requests").write_to(&mut stream);
+                    continue;
+                }
+                let (token, sender, in_flight) = (token.clone(), sender.clone(), in_flight.clone());
+                shutdown.spawn("control-request", move |shutdown| {
+                    if let Err(e) = answer(stream, &token, &sender, &shutdown) {
+                        warn!("Failed to answer a control request: {}", e);
+                    }
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        Ok(ControlApi(Mutex::new(receiver)))
+    }
+}
+
+fn answer(
+    mut stream: TcpStream,
+    token: &str,
+    sender: &Sender<PendingRequest>,
+    shutdown: &Shutdown,
+) -> io::Result<()> {
+    let response = match read_request(&stream, shutdown) {
+        Ok((method, path, authorization)) => {
+            if authorized(authorization.as_deref(), token) {
+                ask_the_game(sender, route(&method, &path), shutdown)
+            } else {
+                Response::error(401, "invalid bearer token")
+            }
+        }
+        Err(_) => Response::error(400, "invalid request"),
+    };
+    response.write_to(&mut stream)
+}
+
+/// Reads from a connection by short timeouts, to give up once the server shuts down,
+/// until the `REQUEST_TIMEOUT`.
+struct InterruptibleReader<'a> {
+    stream: &'a TcpStream,
+    shutdown: &'a Shutdown,
+    deadline: Instant,
+}
+
+impl Read for InterruptibleReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.stream.read(buf) {
+                Err(e)
+                    if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+                        && !self.shutdown.is_signaled()
+                        && Instant::now() < self.deadline => {}
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Reads the method, the path and the `Authorization` header of a request, the body is ignored.
+fn read_request(
+    stream: &TcpStream,
+    shutdown: &Shutdown,
+) -> io::Result<(String, String, Option<String>)> {
+    // The connections may inherit the non-blocking listener.
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let reader =
+        InterruptibleReader { stream, shutdown, deadline: Instant::now() + REQUEST_TIMEOUT };
+    let mut lines = BufReader::new(reader).lines();
+    let request_line = lines.next().transpose()?.unwrap_or_default();
+    let mut words = request_line.split_whitespace();
+    let (method, path) = match (words.next(), words.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid request line")),
+    };
+
+    let mut authorization = None;
+    for line in lines {
+        let line = line?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    Ok((method, path, authorization))
+}
+
+/// Waits for the game to answer, it doesn't once it shut down.
+fn ask_the_game(sender: &Sender<PendingRequest>, route: Route, shutdown: &Shutdown) -> Response {
+    match route {
+        Route::NotFound => return Response::error(404, "not found"),
+        Route::MethodNotAllowed => return Response::error(405, "method not allowed"),
+        _ => (),
+    }
+    let (respond, response) = mpsc::channel();
+    if sender.send(PendingRequest { route, respond }).is_ok() {
+        while !shutdown.is_signaled() {
+            match response.recv_timeout(POLL_INTERVAL) {
+                Ok(response) => return response,
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+    Response::error(503, "shutting down")
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn control_api_system(
+    api: Res<ControlApi>,
+    mut commands: Commands,
+    mut server: ResMut<RenetServer>,
+    mut connections: ResMut<Connections>,
+    mut pending: ResMut<PendingDisconnects>,
+    mut lobby: ResMut<Lobby>,
+    match_state: Res<MatchState>,
+    full_server: Res<FullServerPolicy>,
+    players: Query<(&Player, &Score, Option<&Team>)>,
+) {
+    let requests: Vec<_> = api.0.lock().unwrap().try_iter().collect();
+    for PendingRequest { route, respond } in requests {
+        let response = match route {
+            Route::ListPlayers => {
+                let mut list: Vec<_> = players
+                    .iter()
+                    .map(|(player, score, team)| ControlPlayer {
+                        id: player.id,
+                        team: team.map(|team| team.0),
+                        score: score.0,
+                        disconnected: connections.client_id(*player).is_none(),
+                    })
+                    .collect();
+                list.sort_by_key(|player| player.id);
+                Response::json(&list)
+            }
+            Route::Kick(player) if lobby.players.contains_key(&player) => {
+                println!("Kicking {:?} on request of the control API.", player);
+                crate::kick_player(
+                    &mut commands,
+                    &mut server,
+                    &mut connections,
+                    &mut pending,
+                    &mut lobby,
+                    player,
+                    "kicked by the administrator",
+                );
+                Response::json(&serde_json::json!({ "kicked": player.id }))
+            }
+            Route::Kick(_) => Response::error(404, "no such player"),
+            Route::Status => Response::json(&ControlStatus {
+                players: lobby.players.len(),
+                max_players: full_server.max_players,
+                playing: *match_state == MatchState::Playing,
+            }),
+            Route::NotFound => Response::error(404, "not found"),
+            Route::MethodNotAllowed => Response::error(405, "method not allowed"),
+        };
+        // The tool may have given up waiting.
+        let _ = respond.send(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes() {
+        assert_eq!(route("GET", "/players"), Route::ListPlayers);
+        assert_eq!(route("GET", "/status/"), Route::Status);
+        assert_eq!(route("POST", "/players/42/kick"), Route::Kick(Player { id: 42 }));
+        assert_eq!(route("POST", "/players/me/kick"), Route::NotFound);
+        assert_eq!(route("DELETE", "/players"), Route::MethodNotAllowed);
+        assert_eq!(route("GET", "/players/42/kick"), Route::MethodNotAllowed);
+        assert_eq!(route("GET", "/"), Route::NotFound);
+    }
+
+    #[test]
+    fn bearer_token() {
+        assert!(authorized(Some("Bearer secret"), "secret"));
+        assert!(!authorized(Some("Bearer secre"), "secret"));
+        assert!(!authorized(Some("Bearer secreT"), "secret"));
+        assert!(!authorized(Some("secret"), "secret"));
+        assert!(!authorized(None, "secret"));
+    }
+
+    #[test]
+    fn bad_token_is_unauthorized() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        write!(client, "GET /status HTTP/1.1\r\nAuthorization: Bearer nope\r\n\r\n").unwrap();
+
+        let (stream, _) = listener.accept().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        answer(stream, "secret", &sender, &Shutdown::default()).unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        let mut response = String::new();
+        io::Read::read_to_string(&mut client, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+    }
+
+    #[test]
+    fn silent_tools_are_dropped_on_shutdown() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let shutdown = Shutdown::default();
+        shutdown.signal();
+        let started = Instant::now();
+        assert!(read_request(&stream, &shutdown).is_err());
+        assert!(started.elapsed() < REQUEST_TIMEOUT);
+    }
+
+    #[test]
+    fn unanswered_requests_are_dropped_on_shutdown() {
+        let shutdown = Shutdown::default();
+        let (sender, receiver) = mpsc::channel();
+        let asking = {
+            let shutdown = shutdown.clone();
+            thread::spawn(move || ask_the_game(&sender, Route::Status, &shutdown))
+        };
+
+        // The request reaches the game, which exits before answering it.
+        let _request = receiver.recv().unwrap();
+        shutdown.signal();
+        assert_eq!(asking.join().unwrap().status, 503);
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::knockback::Knockback;
+
+/// The speed a dash adds in the direction the player moves, it fades like a knockback.
+const DASH_SPEED: f32 = 900.0;
+/// How long a player waits between two dashes, the dashes asked for meanwhile are rejected.
+pub const DASH_COOLDOWN: Duration = Duration::from_millis(1500);
+
+/// When the player can dash again.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct DashCooldown {
+    ready_at: Option<Instant>,
+}
+
+impl DashCooldown {
+    /// Returns whether the player can dash at `now` and starts the cooldown if it can.
+    pub fn try_dash(&mut self, now: Instant) -> bool {
+        if self.ready_at.map_or(false, |ready_at| now < ready_at) {
+            return false;
+        }
+        self.ready_at = Some(now + DASH_COOLDOWN);
+        true
+    }
+}
+
+/// Pushes the players that dash in the direction they move, the clients never predict it.
+pub fn dash_system(mut players: Query<(&PlayerInput, &mut DashCooldown, &mut Knockback)>) {
+    let now = Instant::now();
+    for (input, mut cooldown, mut knockback) in players.iter_mut() {
+        let direction = input_velocity(&input.movement(), None).normalize_or_zero();
+        if input.dash && direction != Vec2::ZERO && cooldown.try_dash(now) {
+            knockback.0 += direction * DASH_SPEED;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dashes_are_rejected_during_the_cooldown() {
+        let now = Instant::now();
+        let mut cooldown = DashCooldown::default();
+        assert!(cooldown.try_dash(now));
+        assert!(!cooldown.try_dash(now + DASH_COOLDOWN / 2));
+        assert!(cooldown.try_dash(now + DASH_COOLDOWN));
+    }
+}
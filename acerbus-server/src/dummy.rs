@@ -0,0 +1,133 @@
+use std::f32::consts::TAU;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::console::AdminCommand;
+use crate::{free_spawn_position, spawn_player, ClientPolicy};
+
+/// How fast the dummies go around their circle, in radians per second.
+const DUMMY_ANGULAR_SPEED: f32 = TAU / 4.0;
+
+/// A player without a client, moved by the server to test the sync and the rendering.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct Dummy {
+    pub angle: f32,
+}
+
+/// Returns the input that moves along a circle, counter-clockwise, at this angle.
+/// The inputs only have eight directions, the circle is therefore an octagon.
+pub fn dummy_input(angle: f32) -> PlayerInput {
+    let threshold = (TAU / 16.0).sin();
+    let (x, y) = (-angle.sin(), angle.cos());
+    PlayerInput {
+        up: y > threshold,
+        down: y < -threshold,
+        left: x < -threshold,
+        right: x > threshold,
+        dash: false,
+    }
+}
+
+/// Spawns dummies with `spawn-dummy <n>`, their ids are counted down from the largest one
+/// so that they don't clash with the ones of the clients.
+pub fn spawn_dummy_command_system(
+    mut commands: Commands,
+    mut admin_commands: EventReader<AdminCommand>,
+    mut server: ResMut<RenetServer>,
+    mut lobby: ResMut<Lobby>,
+    client_policy: Res<ClientPolicy>,
+    players: Query<&Transform, With<Player>>,
+    mut spawned: Local<u64>,
+) {
+    for AdminCommand(words) in admin_commands.iter() {
+        let words: Vec<_> = words.iter().map(String::as_str).collect();
+        let count: usize = match words.as_slice() {
+            ["spawn-dummy", count] => match count.parse() {
+                Ok(count) => count,
+                Err(_) => {
+                    println!("usage: spawn-dummy <n>");
+                    continue;
+                }
+            },
+            ["spawn-dummy", ..] => {
+                println!("usage: spawn-dummy <n>");
+                continue;
+            }
+            _ => continue,
+        };
+
+        // The dummies spawned this frame are not in the world yet.
+        let mut occupied: Vec<_> = players.iter().map(|t| t.translation.truncate()).collect();
+        for _ in 0..count {
+            let player = Player { id: u64::MAX - *spawned };
+            *spawned += 1;
+            let position = free_spawn_position(&occupied);
+            occupied.push(position);
+            let entity = spawn_player(&mut commands, player, position, client_policy.body_shape);
+            commands.entity(entity).insert(Dummy::default());
+            lobby.players.insert(player, entity);
+
+            let message = encode(&ServerMessage::PlayerConnected {
+                player,
+                team: None,
+                skin: None,
+                name: PlayerName(fallback_name(player)),
+                bot: true,
+            });
+            server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+        }
+        println!("{} dummies spawned.", count);
+    }
+}
+
+pub fn move_dummies_system(time: Res<Time>, mut dummies: Query<(&mut Dummy, &mut PlayerInput)>) {
+    for (mut dummy, mut input) in dummies.iter_mut() {
+        dummy.angle = (dummy.angle + DUMMY_ANGULAR_SPEED * time.delta_seconds()) % TAU;
+        *input = dummy_input(dummy.angle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::new_renet_server;
+
+    fn command(line: &str) -> AdminCommand {
+        AdminCommand(line.split_whitespace().map(String::from).collect())
+    }
+
+    #[test]
+    fn dummies_are_spawned_with_the_largest_ids() {
+        let mut app = App::new();
+        app.add_event::<AdminCommand>();
+        app.insert_resource(Lobby::default());
+        app.insert_resource(new_renet_server("127.0.0.1:0".parse().unwrap(), 8));
+        app.add_system(spawn_dummy_command_system);
+
+        app.world.send_event(command("spawn-dummy 2"));
+        app.update();
+        app.world.send_event(command("spawn-dummy"));
+        app.world.send_event(command("spawn-dummy many"));
+        app.update();
+        app.world.send_event(command("spawn-dummy 1"));
+        app.update();
+
+        let ids: Vec<_> = app.world.resource::<Lobby>().players.keys().map(|p| p.id).collect();
+        assert_eq!(ids, [u64::MAX - 2, u64::MAX - 1, u64::MAX]);
+        assert_eq!(app.world.query::<&Dummy>().iter(&app.world).count(), 3);
+    }
+
+    #[test]
+    fn dummies_go_around_counter_clockwise() {
+        let directions: Vec<_> = (0..8).map(|i| dummy_input(i as f32 * TAU / 8.)).collect();
+        let input = |up, down, left, right| PlayerInput { up, down, left, right, dash: false };
+        assert_eq!(directions[0], input(true, false, false, false));
+        assert_eq!(directions[1], input(true, false, true, false));
+        assert_eq!(directions[2], input(false, false, true, false));
+        assert_eq!(directions[4], input(false, true, false, false));
+        assert_eq!(directions[6], input(false, false, false, true));
+        assert_eq!(directions[7], input(true, false, false, true));
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+use heron::prelude::*;
+
+use crate::event_log::EventLog;
+use crate::knockback::Knockback;
+use crate::spawn_protection::{SpawnProtected, SpawnProtection};
+use crate::teleport::Teleports;
+
+/// The arena has no walls, the players pushed beyond its edges are eliminated
+/// and the last player that hit them scores a point.
+pub struct EliminationMode {
+    /// How long a hit is remembered to know who eliminated a player.
+    pub last_hit_window: Duration,
+}
+
+/// The last player that collided with this one.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct LastHit {
+    pub by: Player,
+    pub at: Instant,
+}
+
+/// The number of players this player eliminated.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct Score(pub u32);
+
+/// Returns whether the center of the player is beyond the edges of the arena.
+pub fn is_out_of_bounds(position: Vec2, arena: Arena) -> bool {
+    position.x.abs() > arena.width / 2. || position.y.abs() > arena.height / 2.
+}
+
+/// Returns the player that eliminated another one, the one that hit it last if it was recent.
+pub fn eliminated_by(last_hit: Option<LastHit>, now: Instant, window: Duration) -> Option<Player> {
+    last_hit.filter(|hit| now.duration_since(hit.at) <= window).map(|hit| hit.by)
+}
+
+/// Remembers the players that collide with each other.
+pub fn record_last_hits_system(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    players: Query<&Player>,
+) {
+    let now = Instant::now();
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(..) = event {
+            let (a, b) = event.rigid_body_entities();
+            if let (Ok(player_a), Ok(player_b)) = (players.get(a), players.get(b)) {
+                commands.entity(a).insert(LastHit { by: *player_b, at: now });
+                commands.entity(b).insert(LastHit { by: *player_a, at: now });
+            }
+        }
+    }
+}
+
+/// Sends the players that left the arena back to its center and credits the one that pushed them.
+#[allow(clippy::too_many_arguments)]
+pub fn eliminate_out_of_bounds_system(
+    mut commands: Commands,
+    mode: Res<EliminationMode>,
+    arena: Res<Arena>,
+    lobby: Res<Lobby>,
+    mut server: ResMut<RenetServer>,
+    mut event_log: ResMut<EventLog>,
+    protection: Option<Res<SpawnProtection>>,
+    mut players: Query<(
+        Entity,
+        &Player,
+        &mut Transform,
+        &mut Teleports,
+        &mut Knockback,
+        Option<&LastHit>,
+    )>,
+    mut scores: Query<&mut Score>,
+) {
+    let now = Instant::now();
+    for (entity, player, mut transform, mut teleports, mut knockback, last_hit) in
+        players.iter_mut()
+    {
+        if !is_out_of_bounds(transform.translation.xy(), *arena) {
+            continue;
+        }
+
+        transform.translation = Vec3::new(0., 0., transform.translation.z);
+        teleports.record();
+        *knockback = Knockback::default();
+        commands.entity(entity).remove::<LastHit>();
+        // The player respawns at the center, maybe in the middle of the others.
+        if let Some(protection) = protection.as_deref() {
+            commands.entity(entity).insert(SpawnProtected(now + protection.duration));
+        }
+
+        let by = eliminated_by(last_hit.copied(), now, mode.last_hit_window);
+        let by_entity = by.and_then(|by| lobby.players.get(&by));
+        if let Some(mut score) = by_entity.and_then(|entity| scores.get_mut(*entity).ok()) {
+            score.0 += 1;
+        }
+
+        println!("{:?} has been eliminated by {:?}.", player, by);
+        let message = encode(&ServerMessage::PlayerEliminated { player: *player, by });
+        server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+        event_log.push(GameEvent::PlayerEliminated { player: *player, by });
+    }
+}
+
+/// Sends the scores that changed to everyone, the clients that connect get all of them.
+pub fn player_scores_system(
+    mut server: ResMut<RenetServer>,
+    scores: Query<(&Player, &Score), Changed<Score>>,
+) {
+    for (player, score) in scores.iter() {
+        let message = encode(&ServerMessage::ScoreChanged { player: *player, score: score.0 });
+        server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+    }
+}
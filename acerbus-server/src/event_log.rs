@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::connections::Connections;
+
+/// The last events of the match, sent to the clients as they happen and
+/// replayed to the ones that connect so that they can show a recent feed.
+#[derive(Debug)]
+pub struct EventLog {
+    capacity: usize,
+    history: VecDeque<GameEvent>,
+    unsent: Vec<GameEvent>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> EventLog {
+        EventLog { capacity, history: VecDeque::new(), unsent: Vec::new() }
+    }
+
+    /// Records an event, the oldest one is forgotten when the log is full.
+    pub fn push(&mut self, event: GameEvent) {
+        self.history.push_back(event);
+        while self.history.len() > self.capacity {
+            self.history.pop_front();
+        }
+        self.unsent.push(event);
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &GameEvent> {
+        self.history.iter()
+    }
+}
+
+/// Records the joins, leaves and match changes, then sends the new events to everyone
+/// and the recent ones to the clients that just got initialized.
+#[allow(clippy::too_many_arguments)]
+pub fn event_log_system(
+    mut event_log: ResMut<EventLog>,
+    mut server: ResMut<RenetServer>,
+    connections: Res<Connections>,
+    match_state: Res<MatchState>,
+    joined: Query<(Entity, &Player), Added<Player>>,
+    left: RemovedComponents<Player>,
+    mut players: Local<HashMap<Entity, Player>>,
+    mut synced_clients: Local<HashSet<u64>>,
+) {
+    for (entity, player) in joined.iter() {
+        players.insert(entity, *player);
+        event_log.push(GameEvent::PlayerJoined { player: *player });
+    }
+    for entity in left.iter() {
+        if let Some(player) = players.remove(&entity) {
+            event_log.push(GameEvent::PlayerLeft { player });
+        }
+    }
+    if match_state.is_changed() && !match_state.is_added() {
+        event_log.push(GameEvent::MatchStateChanged { state: *match_state });
+    }
+
+    // The clients initialized during this frame get the history, which includes the new events.
+    let initialized = connections.initialized();
+    for client_id in initialized.iter().filter(|client_id| !synced_clients.contains(client_id)) {
+        for event in event_log.history() {
+            let message = encode(&ServerMessage::GameEvent(*event));
+            server.send_message(*client_id, CONNECTION_EVENTS_CHANNEL, message);
+        }
+    }
+    for event in std::mem::take(&mut event_log.unsent) {
+        let message = encode(&ServerMessage::GameEvent(event));
+        for client_id in initialized.iter().filter(|client_id| synced_clients.contains(client_id)) {
+            server.send_message(*client_id, CONNECTION_EVENTS_CHANNEL, message.clone());
+        }
+    }
+    *synced_clients = initialized.into_iter().collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::new_renet_server;
+
+    #[test]
+    fn the_match_events_are_logged_and_sent() {
+        let mut app = App::new();
+        app.insert_resource(EventLog::new(2));
+        app.insert_resource(new_renet_server("127.0.0.1:0".parse().unwrap(), 8));
+        app.insert_resource(Connections::default());
+        app.insert_resource(MatchState::Lobby);
+        app.add_system(event_log_system);
+        app.update();
+        assert_eq!(app.world.resource::<EventLog>().history().count(), 0);
+
+        let player = Player { id: 1 };
+        let entity = app.world.spawn().insert(player).id();
+        app.update();
+        app.world.despawn(entity);
+        *app.world.resource_mut::<MatchState>() = MatchState::Playing;
+        app.update();
+
+        // Only the most recent events are kept, they were all sent.
+        let event_log = app.world.resource::<EventLog>();
+        let history: Vec<_> = event_log.history().copied().collect();
+        assert_eq!(
+            history,
+            [
+                GameEvent::PlayerLeft { player },
+                GameEvent::MatchStateChanged { state: MatchState::Playing },
+            ]
+        );
+        assert!(event_log.unsent.is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use acerbus_common::*;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+
+use crate::console::AdminCommand;
+
+/// The smallest cell, smaller cells would take too much memory to cover the arena.
+pub const MIN_HEATMAP_CELL_SIZE: f32 = 1.0;
+
+/// Counts the ticks spent by the players in each cell of a grid covering the arena,
+/// the memory used only depends on the size of the cells. The grid keeps
+/// the size the arena had at startup, even if the arena grows with the players.
+#[derive(Debug)]
+pub struct Heatmap {
+    pub arena: Arena,
+    pub cell_size: f32,
+    pub columns: usize,
+    pub rows: usize,
+    counts: Vec<u64>,
+}
+
+impl Heatmap {
+    pub fn new(arena: Arena, cell_size: f32) -> Heatmap {
+        let columns = (arena.width / cell_size).ceil().max(1.) as usize;
+        let rows = (arena.height / cell_size).ceil().max(1.) as usize;
+        Heatmap { arena, cell_size, columns, rows, counts: vec![0; columns * rows] }
+    }
+
+    pub fn record(&mut self, position: Vec2) {
+        if let Some(index) = heatmap_cell_index(position, self.arena, self.cell_size) {
+            self.counts[index] += 1;
+        }
+    }
+
+    /// Writes one line per row of cells, starting from the top of the arena.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for row in self.counts.chunks(self.columns).rev() {
+            let line: Vec<_> = row.iter().map(u64::to_string).collect();
+            writeln!(writer, "{}", line.join(","))?;
+        }
+        Ok(())
+    }
+
+    pub fn dump(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()
+    }
+}
+
+/// Parses the `--heatmap-cell-size`, the cells can't be smaller than `MIN_HEATMAP_CELL_SIZE`.
+pub fn parse_cell_size(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(size) if size >= MIN_HEATMAP_CELL_SIZE => Ok(size),
+        Ok(_) => Err(format!("the cells must be at least {} wide", MIN_HEATMAP_CELL_SIZE)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Returns the index of the cell in which this position is, the cells are ordered
+/// row by row from the bottom left corner of the arena, `None` if outside of it.
+pub fn heatmap_cell_index(position: Vec2, arena: Arena, cell_size: f32) -> Option<usize> {
+    let x = position.x + arena.width / 2.;
+    let y = position.y + arena.height / 2.;
+    if x < 0. || y < 0. || x >= arena.width || y >= arena.height {
+        return None;
+    }
+
+    let columns = (arena.width / cell_size).ceil().max(1.) as usize;
+    let column = (x / cell_size) as usize;
+    let row = (y / cell_size) as usize;
+    Some(row * columns + column)
+}
+
+pub fn record_heatmap_system(
+    mut heatmap: ResMut<Heatmap>,
+    players: Query<&Transform, With<Player>>,
+) {
+    for transform in players.iter() {
+        heatmap.record(transform.translation.xy());
+    }
+}
+
+/// Dumps the heatmap as CSV when the `heatmap <path>` command is typed.
+pub fn heatmap_command_system(
+    mut commands: EventReader<AdminCommand>,
+    heatmap: Option<Res<Heatmap>>,
+) {
+    for AdminCommand(words) in commands.iter() {
+        let words: Vec<_> = words.iter().map(String::as_str).collect();
+        match (words.as_slice(), heatmap.as_deref()) {
+            (["heatmap", path], Some(heatmap)) => match heatmap.dump(Path::new(path)) {
+                Ok(()) => println!("The heatmap has been written to {}.", path),
+                Err(e) => println!("Failed to write the heatmap to {}: {}", path, e),
+            },
+            (["heatmap", _], None) => println!("The heatmap is disabled, see --heatmap-cell-size."),
+            (["heatmap", ..], _) => println!("usage: heatmap <path>"),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_ordered_from_the_bottom_left_corner() {
+        let arena = Arena { width: 100., height: 50. };
+        assert_eq!(heatmap_cell_index(Vec2::new(-50., -25.), arena, 10.), Some(0));
+        assert_eq!(heatmap_cell_index(Vec2::new(-35., -25.), arena, 10.), Some(1));
+        assert_eq!(heatmap_cell_index(Vec2::new(-50., -15.), arena, 10.), Some(10));
+        assert_eq!(heatmap_cell_index(Vec2::new(49., 24.), arena, 10.), Some(49));
+        assert_eq!(heatmap_cell_index(Vec2::new(50., 0.), arena, 10.), None);
+    }
+
+    #[test]
+    fn too_small_cells_are_rejected() {
+        assert_eq!(parse_cell_size("20"), Ok(20.));
+        assert!(parse_cell_size("0").is_err());
+        assert!(parse_cell_size("-5").is_err());
+        assert!(parse_cell_size("NaN").is_err());
+        assert!(parse_cell_size("big").is_err());
+    }
+}
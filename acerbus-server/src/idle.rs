@@ -0,0 +1,54 @@
+use std::time::Instant;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+
+/// The last time a player pressed a key, an idle player only sends empty inputs.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct LastActivity(pub Instant);
+
+/// What to do when a new player connects to a server that has no room left.
+pub struct FullServerPolicy {
+    pub max_players: usize,
+    /// Make room by kicking the player waiting to reconnect for the longest time,
+    /// or the most idle one, instead of refusing the new player.
+    pub kick_idle: bool,
+}
+
+/// Returns the player to kick to make room for a new one, the players waiting to reconnect
+/// are kicked first, the one that left the earliest, then the least recently active ones.
+pub fn eviction_candidate(
+    pending: impl IntoIterator<Item = (Player, Instant)>,
+    active: impl IntoIterator<Item = (Player, Instant)>,
+) -> Option<Player> {
+    let oldest = |players: Vec<(Player, Instant)>| {
+        players.into_iter().min_by_key(|(player, at)| (*at, *player)).map(|(player, _)| player)
+    };
+    oldest(pending.into_iter().collect()).or_else(|| oldest(active.into_iter().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn pending_players_are_kicked_first() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(10);
+        let pending = [(Player { id: 1 }, later), (Player { id: 2 }, now)];
+        let active = [(Player { id: 3 }, now)];
+        assert_eq!(eviction_candidate(pending, active), Some(Player { id: 2 }));
+    }
+
+    #[test]
+    fn the_most_idle_player_is_kicked() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(10);
+        let active = [(Player { id: 1 }, later), (Player { id: 2 }, now), (Player { id: 3 }, now)];
+        // The lowest id breaks the ties.
+        assert_eq!(eviction_candidate([], active), Some(Player { id: 2 }));
+        assert_eq!(eviction_candidate([], []), None);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::connections::Connections;
+
+/// How many ticks the echoes of the tagged inputs are gathered before being sent together.
+pub const ECHO_BATCH_TICKS: u64 = 10;
+
+/// How the inputs of the players are buffered, and the tick the server is at.
+pub struct InputBufferSettings {
+    /// How many inputs of a player are kept ahead, a bigger buffer absorbs more jitter
+    /// but delays the inputs of the player by as many ticks when it is full.
+    pub size: usize,
+    /// How many ticks ahead the clients tag their inputs, the same for everyone so that
+    /// the players with a low latency don't get an advantage over the others.
+    pub delay: u64,
+    /// The current tick, counted by `apply_buffered_inputs_system`.
+    pub tick: u64,
+}
+
+impl InputBufferSettings {
+    /// The tick at which an input tagged for the tick `target` must be applied, the late
+    /// inputs are applied on the next tick and none is applied further than the delay.
+    pub fn apply_at(&self, target: u64) -> u64 {
+        target.clamp(self.tick + 1, self.tick + self.delay.max(1))
+    }
+}
+
+/// The inputs received from a player and not applied yet, tagged with the tick at which to
+/// apply them and the sequence number to echo, if the client asked for one. One is applied
+/// per tick so that inputs arriving in bursts still move the player smoothly.
+#[derive(Debug, Default, Component)]
+pub struct InputBuffer {
+    inputs: VecDeque<(u64, PlayerInput, Option<u32>)>,
+    /// The tagged inputs applied and not echoed yet, with the tick they were applied on.
+    echoes: Vec<(u32, u64)>,
+}
+
+impl InputBuffer {
+    /// Queues an input for the tick `tick`, the oldest ones are dropped once there are
+    /// more than `size`. An input can't be applied before the ones queued before it.
+    pub fn push(&mut self, input: PlayerInput, sequence: Option<u32>, tick: u64, size: usize) {
+        let tick = self.inputs.back().map_or(tick, |(last, _, _)| tick.max(*last));
+        self.inputs.push_back((tick, input, sequence));
+        while self.inputs.len() > size.max(1) {
+            self.inputs.pop_front();
+        }
+    }
+
+    /// Returns the input to apply at `tick` and its sequence number, if any arrived and
+    /// its tick came.
+    pub fn pop(&mut self, tick: u64) -> Option<(PlayerInput, Option<u32>)> {
+        match self.inputs.front() {
+            Some((at, _, _)) if *at <= tick => {
+                self.inputs.pop_front().map(|(_, input, sequence)| (input, sequence))
+            }
+            _ => None,
+        }
+    }
+
+    /// Remembers that the tagged input `sequence` was applied at `tick`.
+    pub fn applied(&mut self, sequence: u32, tick: u64) {
+        self.echoes.push((sequence, tick));
+    }
+
+    /// Returns the echoes to send at `tick`, they are gathered for `ECHO_BATCH_TICKS`.
+    pub fn echo_batch(&mut self, tick: u64) -> Option<Vec<(u32, u64)>> {
+        if tick % ECHO_BATCH_TICKS == 0 && !self.echoes.is_empty() {
+            Some(std::mem::take(&mut self.echoes))
+        } else {
+            None
+        }
+    }
+}
+
+/// Applies one buffered input per tick, a player keeps its last input when the buffer is empty.
+pub fn apply_buffered_inputs_system(
+    mut settings: ResMut<InputBufferSettings>,
+    mut server: ResMut<RenetServer>,
+    connections: Res<Connections>,
+    mut query: Query<(&Player, &mut InputBuffer, &mut PlayerInput)>,
+) {
+    settings.tick += 1;
+    for (player, mut buffer, mut player_input) in query.iter_mut() {
+        if let Some((input, sequence)) = buffer.pop(settings.tick) {
+            *player_input = input;
+            if let Some(sequence) = sequence {
+                buffer.applied(sequence, settings.tick);
+            }
+        }
+        if let Some(inputs) = buffer.echo_batch(settings.tick) {
+            if let Some(client_id) = connections.client_id(*player) {
+                let message = encode(&ServerMessage::InputsApplied { inputs });
+                server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::new_renet_server;
+
+    /// A different input for every number below 32.
+    fn input(n: u8) -> PlayerInput {
+        PlayerInput {
+            up: n & 1 != 0,
+            down: n & 2 != 0,
+            left: n & 4 != 0,
+            right: n & 8 != 0,
+            dash: n & 16 != 0,
+        }
+    }
+
+    #[test]
+    fn jittery_inputs_are_applied_one_per_tick() {
+        let mut world = World::new();
+        world.insert_resource(InputBufferSettings { size: 3, delay: 0, tick: 0 });
+        world.insert_resource(new_renet_server("127.0.0.1:0".parse().unwrap(), 8));
+        world.insert_resource(Connections::default());
+        let player = world
+            .spawn()
+            .insert(Player { id: 1 })
+            .insert(InputBuffer::default())
+            .insert(input(0))
+            .id();
+        let mut stage = SystemStage::single_threaded().with_system(apply_buffered_inputs_system);
+
+        // The inputs are sent once per tick but arrive in bursts, then not at all.
+        let arrivals = [vec![1, 2], vec![], vec![3], vec![4, 5], vec![], vec![], vec![]];
+        let mut applied = Vec::new();
+        for arrived in arrivals {
+            for n in arrived {
+                let settings = world.resource::<InputBufferSettings>();
+                let at = settings.apply_at(settings.tick + 1);
+                world.get_mut::<InputBuffer>(player).unwrap().push(input(n), None, at, 3);
+            }
+            stage.run(&mut world);
+            applied.push(*world.get::<PlayerInput>(player).unwrap());
+        }
+
+        // None is skipped and the last one is kept once the buffer is empty.
+        let expected: Vec<_> = [1, 2, 3, 4, 5, 5, 5].into_iter().map(input).collect();
+        assert_eq!(applied, expected);
+    }
+
+    #[test]
+    fn the_oldest_inputs_are_dropped_when_full() {
+        let mut buffer = InputBuffer::default();
+        for n in 1..=5 {
+            buffer.push(input(n), None, 0, 3);
+        }
+        assert_eq!(buffer.pop(0), Some((input(3), None)));
+        assert_eq!(buffer.pop(0), Some((input(4), None)));
+        assert_eq!(buffer.pop(0), Some((input(5), None)));
+        assert_eq!(buffer.pop(0), None);
+    }
+
+    #[test]
+    fn inputs_wait_for_the_delay() {
+        let settings = InputBufferSettings { size: 3, delay: 5, tick: 100 };
+        let mut buffer = InputBuffer::default();
+        buffer.push(input(1), None, settings.apply_at(105), settings.size);
+        // A late input can't overtake the one queued before it.
+        buffer.push(input(2), Some(7), 100, settings.size);
+
+        assert_eq!(buffer.pop(104), None);
+        assert_eq!(buffer.pop(105), Some((input(1), None)));
+        assert_eq!(buffer.pop(105), Some((input(2), Some(7))));
+        assert_eq!(buffer.pop(106), None);
+    }
+
+    #[test]
+    fn echoes_are_sent_in_batches() {
+        let mut buffer = InputBuffer::default();
+        for tick in 1..ECHO_BATCH_TICKS {
+            buffer.applied(tick as u32, tick);
+            assert_eq!(buffer.echo_batch(tick), None);
+        }
+        buffer.applied(0, ECHO_BATCH_TICKS);
+
+        let batch = buffer.echo_batch(ECHO_BATCH_TICKS).unwrap();
+        assert_eq!(batch.len(), ECHO_BATCH_TICKS as usize);
+        assert_eq!(batch.last(), Some(&(0, ECHO_BATCH_TICKS)));
+        assert_eq!(buffer.echo_batch(ECHO_BATCH_TICKS * 2), None);
+    }
+
+    #[test]
+    fn inputs_are_applied_at_their_tick() {
+        let settings = InputBufferSettings { size: 3, delay: 5, tick: 100 };
+        let mut buffer = InputBuffer::default();
+        let input = PlayerInput { up: true, ..PlayerInput::default() };
+        buffer.push(input, None, settings.apply_at(103), settings.size);
+
+        assert_eq!(buffer.pop(102), None);
+        assert_eq!(buffer.pop(103), Some((input, None)));
+        assert_eq!(buffer.pop(104), None);
+    }
+
+    #[test]
+    fn inputs_are_applied_within_the_delay() {
+        let settings = InputBufferSettings { size: 3, delay: 5, tick: 100 };
+        assert_eq!(settings.apply_at(90), 101);
+        assert_eq!(settings.apply_at(105), 105);
+        assert_eq!(settings.apply_at(1000), 105);
+    }
+}
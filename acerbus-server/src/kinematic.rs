@@ -0,0 +1,104 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+use heron::prelude::*;
+
+use crate::size_class::keep_inside_arena;
+
+/// The longest frame the players are moved by, a server that stalled for longer
+/// doesn't make them jump through the arena once it catches up.
+const MAX_FRAME_DELTA: f32 = 0.25;
+
+/// Moves the players without the physics engine, by the duration of a tick every tick,
+/// which is lighter and gives the same positions on every run. Moving them by the actual
+/// duration of the frame instead keeps their speed when the ticks are late or irregular.
+pub struct KinematicMovement {
+    pub step: f32,
+    pub frame_delta: bool,
+}
+
+impl KinematicMovement {
+    /// The number of seconds to move the players by this frame.
+    pub fn delta(&self, frame_delta: f32) -> f32 {
+        match self.frame_delta {
+            true => frame_delta.min(MAX_FRAME_DELTA),
+            false => self.step,
+        }
+    }
+}
+
+/// Returns the position reached by moving at this velocity during this many seconds. The
+/// position is accumulated in fixed-point so that the rounding errors don't drift apart
+/// between machines over the ticks.
+pub fn integrate(position: Vec2, velocity: Vec2, delta: f32) -> Vec2 {
+    let displacement = FixedVec2::from_vec2(velocity * delta);
+    (FixedVec2::from_vec2(position) + displacement).to_vec2()
+}
+
+/// Moves the players by their velocity and keeps them inside the arena, without physics
+/// they go through each other and the teleport pads, which need the collision events.
+pub fn kinematic_movement_system(
+    time: Res<Time>,
+    movement: Res<KinematicMovement>,
+    arena: Res<Arena>,
+    mut query: Query<
+        (&Velocity, &mut Transform, Option<&SizeClass>, Option<&BodyShape>),
+        With<Player>,
+    >,
+) {
+    let delta = movement.delta(time.delta_seconds());
+    for (velocity, mut transform, size, shape) in query.iter_mut() {
+        let position =
+            integrate(transform.translation.truncate(), velocity.linear.truncate(), delta);
+        let size = size.copied().unwrap_or_default();
+        let position =
+            keep_inside_arena(position, shape.copied().unwrap_or_default(), size, *arena);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players_are_moved_by_a_tick_and_kept_in_the_arena() {
+        assert_eq!(integrate(Vec2::new(1., 2.), Vec2::new(10., -20.), 0.5), Vec2::new(6., -8.));
+        // The positions are kept on the fixed-point grid.
+        assert_eq!(integrate(Vec2::ZERO, Vec2::new(0.0004, 1.), 1.), Vec2::new(0., 1.));
+
+        let mut world = World::new();
+        world.insert_resource(Time::default());
+        world.insert_resource(KinematicMovement { step: 0.5, frame_delta: false });
+        world.insert_resource(Arena { width: 400., height: 400. });
+        let moving = world
+            .spawn()
+            .insert(Player { id: 1 })
+            .insert(Velocity::from_linear(Vec3::new(100., 0., 0.)))
+            .insert(Transform::default())
+            .id();
+        let leaving = world
+            .spawn()
+            .insert(Player { id: 2 })
+            .insert(Velocity::from_linear(Vec3::new(0., 10000., 0.)))
+            .insert(Transform::default())
+            .id();
+        let mut stage = SystemStage::single_threaded().with_system(kinematic_movement_system);
+        stage.run(&mut world);
+
+        assert_eq!(world.get::<Transform>(moving).unwrap().translation, Vec3::new(50., 0., 0.));
+        let leaving = world.get::<Transform>(leaving).unwrap().translation;
+        assert!(leaving.y > 0. && leaving.y < 200.);
+    }
+
+    #[test]
+    fn players_move_by_the_tick_or_by_the_frame() {
+        let step = 1. / 60.;
+        let by_tick = KinematicMovement { step, frame_delta: false };
+        assert_eq!(by_tick.delta(0.05), step);
+
+        let by_frame = KinematicMovement { step, frame_delta: true };
+        assert_eq!(by_frame.delta(0.05), 0.05);
+        assert_eq!(by_frame.delta(3.), MAX_FRAME_DELTA);
+    }
+}
@@ -0,0 +1,132 @@
+use std::time::Instant;
+
+use acerbus_common::*;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use heron::prelude::*;
+
+use crate::spawn_protection::{knockback_applies, SpawnProtected, SpawnProtection};
+
+/// The ratio of the knockback velocity kept from one tick to the next.
+const KNOCKBACK_DAMPING: f32 = 0.9;
+/// Below this speed a knockback is over.
+const KNOCKBACK_MIN_SPEED: f32 = 1.0;
+
+/// How strongly the players bounce off each other, zero to disable the knockback.
+pub struct KnockbackStrength(pub f32);
+
+/// A velocity added to the one of the inputs, it fades over a few ticks.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct Knockback(pub Vec2);
+
+impl Knockback {
+    /// Returns the current knockback velocity and damps it for the next tick.
+    pub fn take(&mut self) -> Vec2 {
+        let velocity = self.0;
+        self.0 *= KNOCKBACK_DAMPING;
+        if self.0.length() < KNOCKBACK_MIN_SPEED {
+            self.0 = Vec2::ZERO;
+        }
+        velocity
+    }
+}
+
+/// Returns the impulse given to the player `a` when it collides with the player `b`, it pushes
+/// `a` away from `b` along the collision normal proportionally to the speed they collide at.
+/// The player `b` receives the opposite impulse.
+pub fn knockback_impulse(
+    position_a: Vec2,
+    position_b: Vec2,
+    velocity_a: Vec2,
+    velocity_b: Vec2,
+    strength: f32,
+) -> Vec2 {
+    let normal = (position_a - position_b).normalize_or_zero();
+    // Only the players moving toward each other are pushed apart.
+    let closing_speed = (velocity_b - velocity_a).dot(normal).max(0.);
+    normal * closing_speed * strength
+}
+
+/// Pushes the players that collide apart, the ones protected after spawning are not pushed.
+pub fn knockback_system(
+    strength: Res<KnockbackStrength>,
+    protection: Option<Res<SpawnProtection>>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut players: Query<
+        (&Transform, &Velocity, &mut Knockback, Option<&SpawnProtected>),
+        With<Player>,
+    >,
+) {
+    let now = Instant::now();
+    let passive = protection.map_or(false, |protection| protection.passive);
+    for event in collision_events.iter() {
+        let (a, b) = match event {
+            CollisionEvent::Started(..) => event.rigid_body_entities(),
+            CollisionEvent::Stopped(..) => continue,
+        };
+        let (impulse, protected_a, protected_b) = match (players.get(a), players.get(b)) {
+            (
+                Ok((transform_a, velocity_a, _, protected_a)),
+                Ok((transform_b, velocity_b, _, protected_b)),
+            ) => {
+                let impulse = knockback_impulse(
+                    transform_a.translation.xy(),
+                    transform_b.translation.xy(),
+                    velocity_a.linear.xy(),
+                    velocity_b.linear.xy(),
+                    strength.0,
+                );
+                let protected_a = SpawnProtected::is_active(protected_a, now);
+                let protected_b = SpawnProtected::is_active(protected_b, now);
+                (impulse, protected_a, protected_b)
+            }
+            _ => continue,
+        };
+
+        let pushes =
+            [(a, impulse, protected_a, protected_b), (b, -impulse, protected_b, protected_a)];
+        for (entity, impulse, target_protected, source_protected) in pushes {
+            if !knockback_applies(target_protected, source_protected, passive) {
+                continue;
+            }
+            if let Ok((_, _, mut knockback, _)) = players.get_mut(entity) {
+                knockback.0 += impulse;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colliding_players_are_pushed_apart() {
+        // `a` is on the right of `b` and they run into each other at 10 + 30.
+        let (position_a, position_b) = (Vec2::new(10., 0.), Vec2::ZERO);
+        let (velocity_a, velocity_b) = (Vec2::new(-10., 0.), Vec2::new(30., 0.));
+        let impulse = knockback_impulse(position_a, position_b, velocity_a, velocity_b, 0.5);
+        assert_eq!(impulse, Vec2::new(20., 0.));
+    }
+
+    #[test]
+    fn players_moving_apart_are_not_pushed() {
+        let impulse = knockback_impulse(Vec2::new(10., 0.), Vec2::ZERO, Vec2::X, Vec2::ZERO, 1.);
+        assert_eq!(impulse, Vec2::ZERO);
+        // Overlapping players have no normal to be pushed along.
+        let impulse = knockback_impulse(Vec2::ZERO, Vec2::ZERO, Vec2::X, -Vec2::X, 1.);
+        assert_eq!(impulse, Vec2::ZERO);
+    }
+
+    #[test]
+    fn knockbacks_fade_out() {
+        let mut knockback = Knockback(Vec2::new(10., 0.));
+        assert_eq!(knockback.take(), Vec2::new(10., 0.));
+        assert_eq!(knockback.0, Vec2::new(9., 0.));
+        // It is stopped once under the minimum speed, after 22 ticks.
+        for _ in 0..21 {
+            assert_ne!(knockback.take(), Vec2::ZERO);
+        }
+        assert_eq!(knockback.take(), Vec2::ZERO);
+    }
+}
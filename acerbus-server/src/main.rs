@@ -1,148 +1,1410 @@
-use std::net::{SocketAddr, UdpSocket};
-use std::time::{Duration, SystemTime};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::iter;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 
 use acerbus_common::*;
+use adaptive_sync::{available_kbps, sync_interval, AdaptiveSync};
+use aoi::AreaOfInterest;
+use arena::{arena_walls_system, resize_arena_system, ArenaScaling};
+use ball::{reset_ball_system, spawn_ball};
 use bevy::app::ScheduleRunnerSettings;
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
-use bevy_renet::renet::{
-    RenetConnectionConfig, RenetServer, ServerAuthentication, ServerConfig, ServerEvent,
-};
+use bevy_renet::renet::{RenetServer, ServerAuthentication, ServerConfig, ServerEvent};
 use bevy_renet::RenetServerPlugin;
+use chat::{announce_command_system, parse_whisper, whisper_message, ChatRateLimit};
 use clap::Parser;
+use collision_layers::{friendly_collisions_system, FriendlyCollisions, MAX_TEAMS};
+use connections::{disconnect_clients_system, Connections};
+use console::{admin_console_system, AdminCommand, AdminConsole};
+#[cfg(feature = "control-api")]
+use control_api::{control_api_system, ControlApi};
+use dash::{dash_system, DashCooldown};
+use dummy::{move_dummies_system, spawn_dummy_command_system, Dummy};
+use elimination::{
+    eliminate_out_of_bounds_system, player_scores_system, record_last_hits_system, EliminationMode,
+    Score,
+};
+use event_log::{event_log_system, EventLog};
+use heatmap::{heatmap_command_system, parse_cell_size, record_heatmap_system, Heatmap};
 use heron::prelude::*;
+use idle::{eviction_candidate, FullServerPolicy, LastActivity};
+use input_buffer::{apply_buffered_inputs_system, InputBuffer, InputBufferSettings};
+use kinematic::{kinematic_movement_system, KinematicMovement};
+use knockback::{knockback_system, Knockback, KnockbackStrength};
+use move_speed::move_speed_command_system;
+#[cfg(feature = "observer")]
+use observer::{observer_feed_system, ObserverFeed};
+use probe::{
+    answer_probes_system, coordinator_heartbeat_system, CoordinatorLink, ProbeSocket, ServerName,
+};
+use ready::{ready_check_system, ReadyCheck};
+use reconnect::{expire_pending_disconnects_system, lobby_diff, LobbyDiff, PendingDisconnects};
+use replay::{print_benchmark, record_replay_system, replay_command_system, ReplayRecorder};
+use serde::Serialize;
+use shutdown::{quit_command_system, shutdown_on_exit_system, Shutdown, SHUTDOWN_TIMEOUT};
+use size_class::{player_collision_shape, resize_command_system};
+use soccer::{soccer_goal_system, team_scores_system, Soccer};
+use spawn_protection::{
+    announce_spawn_protection_system, expire_spawn_protection_system,
+    protect_spawned_players_system, SpawnProtection,
+};
+use substeps::add_physics_plugin;
+use teleport::{
+    default_teleport_pads, move_teleport_pads_system, spawn_teleport_pads, teleport_players_system,
+    TeleportPads, Teleports,
+};
+use tick_timing::{tick_end_system, tick_start_system, tick_timing_command_system, TickTiming};
+
+mod adaptive_sync;
+mod aoi;
+mod arena;
+mod ball;
+mod chat;
+mod collision_layers;
+mod connections;
+mod console;
+#[cfg(feature = "control-api")]
+mod control_api;
+mod dash;
+mod dummy;
+mod elimination;
+mod event_log;
+mod heatmap;
+mod idle;
+mod input_buffer;
+mod kinematic;
+mod knockback;
+mod move_speed;
+#[cfg(feature = "observer")]
+mod observer;
+mod probe;
+mod ready;
+mod reconnect;
+mod replay;
+mod shutdown;
+mod size_class;
+mod soccer;
+mod spawn_protection;
+mod substeps;
+mod teleport;
+mod tick_timing;
+
+/// The number of server ticks per second.
+const TICK_RATE: f64 = 60.0;
 
-#[derive(Parser)]
+/// The distance between two spawn points, relative to the size of a player.
+const SPAWN_SPACING: f32 = 1.5;
+
+#[derive(Debug, Parser, Serialize)]
+#[clap(args_override_self = true)]
 struct Opt {
+    /// Read the options from this JSON file, like `{ "max_players": 16, "ball": true }`,
+    /// the flags given on the command line win over it.
+    #[clap(long)]
+    config: Option<PathBuf>,
+
     #[clap(long, short, default_value = "127.0.0.1:5000")]
     listen_addr: SocketAddr,
+
+    /// The name shown in the server browser of the clients.
+    #[clap(long, default_value = "acerbus")]
+    name: String,
+
+    /// Register to this coordinator, which sends the clients to the least loaded server.
+    #[clap(long)]
+    coordinator: Option<SocketAddr>,
+
+    /// The address the coordinator sends the clients to, the listen address by default.
+    /// The coordinator replaces an unspecified or loopback IP by the one it hears us from.
+    #[clap(long, requires = "coordinator")]
+    public_addr: Option<SocketAddr>,
+
+    /// How long, in seconds, a disconnected player can take to reconnect and get its cube back.
+    #[clap(long, default_value = "10")]
+    reconnect_grace: f64,
+
+    #[clap(long, default_value_t = ARENA_WIDTH)]
+    arena_width: f32,
+
+    #[clap(long, default_value_t = ARENA_HEIGHT)]
+    arena_height: f32,
+
+    /// Grow the arena width by this much for each player after the first one,
+    /// the height grows proportionally. The arena size given above is the base size.
+    #[clap(long, default_value = "0")]
+    arena_per_player: f32,
+
+    /// The size above which a world sync is split into multiple messages.
+    #[clap(long, default_value_t = WORLD_SYNC_MAX_PART_BYTES)]
+    world_sync_max_bytes: usize,
+
+    /// The maximum size of the world sent to each client, only the players
+    /// nearest to a client are sent when there are too many.
+    #[clap(long)]
+    world_sync_budget: Option<usize>,
+
+    /// Only send to each client the players within this distance of its own player,
+    /// the clients can ask for another distance up to `--aoi-radius-max`.
+    #[clap(long)]
+    aoi_radius: Option<f32>,
+
+    /// The farthest a client can ask to see the other players.
+    #[clap(long, default_value = "2000")]
+    aoi_radius_max: f32,
+
+    /// Let the clients move their player themselves and accept the positions they send,
+    /// INSECURE, the clients can go anywhere, only use it to prototype client features.
+    /// The players can't dash as the server doesn't move them.
+    #[clap(long)]
+    trust_clients: bool,
+
+    /// The minimum delay, in seconds, with which the clients should render the other players.
+    #[clap(long, default_value = "0.0")]
+    interp_min_delay: f64,
+
+    /// The maximum delay, in seconds, with which the clients should render the other players.
+    #[clap(long, default_value = "0.25")]
+    interp_max_delay: f64,
+
+    /// Send the world less often to the clients that can't receive it every tick, each client
+    /// is assumed to have this bandwidth, in kilobits per second, until it loses packets.
+    #[clap(long)]
+    adaptive_sync_kbps: Option<f32>,
+
+    /// Only send a small keepalive instead of the world when no player moved.
+    #[clap(long)]
+    skip_idle_syncs: bool,
+
+    /// The distance a player must move for the world to be considered changed, the physics
+    /// jitter under it is ignored when skipping the idle syncs.
+    #[clap(long, default_value = "0.01")]
+    pos_epsilon: f32,
+
+    /// Send the positions on 16 bits per axis, relative to the arena bounds, to save bandwidth.
+    #[clap(long)]
+    quantize_positions: bool,
+
+    /// Stream the state of the game as JSON lines to the tools connecting to this TCP address.
+    #[cfg(feature = "observer")]
+    #[clap(long)]
+    observer_addr: Option<SocketAddr>,
+
+    /// Serve an HTTP API on this address to list and kick the players or get the status of
+    /// the server, the requests must carry the `--control-token` as a bearer token.
+    #[cfg(feature = "control-api")]
+    #[clap(long, requires = "control_token")]
+    control_addr: Option<SocketAddr>,
+
+    /// The bearer token expected by the control API.
+    #[cfg(feature = "control-api")]
+    #[clap(long)]
+    #[serde(skip_serializing)]
+    control_token: Option<String>,
+
+    /// Send the state of the game to the observers every this many ticks.
+    #[cfg(feature = "observer")]
+    #[clap(long, default_value = "6")]
+    observer_interval: u64,
+
+    /// Surround the arena with walls, moved when the arena is resized, the players can't leave it.
+    #[clap(long)]
+    arena_walls: bool,
+
+    /// Add a pair of linked teleport pads to the arena.
+    #[clap(long)]
+    teleport_pads: bool,
+
+    /// Add a ball in the middle of the arena, the players push it around.
+    #[clap(long)]
+    ball: bool,
+
+    /// The rules of the match, classic or soccer. In soccer, two teams push
+    /// the ball into the goal of the other team.
+    #[clap(long, default_value = "classic")]
+    mode: GameMode,
+
+    /// The number of goals that wins a soccer match.
+    #[clap(long, default_value = "5")]
+    win_score: u32,
+
+    /// The size above which the messages sent are reported as too big, in debug builds.
+    #[clap(long, default_value_t = DEFAULT_SAFE_MESSAGE_BYTES)]
+    safe_message_bytes: usize,
+
+    /// The shape of the players, square or circle, the collisions and the knockbacks feel
+    /// different with round players.
+    #[clap(long, default_value = "square")]
+    shape: BodyShape,
+
+    /// A file of words, one per line, the players can't have in their name. A player whose
+    /// name contains one of them, whatever its case, is named `Player#<id>` instead.
+    #[clap(long)]
+    name_filter: Option<PathBuf>,
+
+    /// Also mask the words of the name filter in the chat.
+    #[clap(long, requires = "name_filter")]
+    filter_chat: bool,
+
+    /// Split the players into this number of teams, zero to disable teams, 15 at most.
+    #[clap(
+        long,
+        default_value = "0",
+        value_parser = clap::value_parser!(u8).range(..=MAX_TEAMS as i64)
+    )]
+    teams: u8,
+
+    /// Let the players of the same team pass through each other with `off`.
+    #[clap(long, arg_enum, default_value = "on")]
+    friendly_collisions: FriendlyCollisions,
+
+    /// How long, in seconds, to wait for the other players once one is ready before starting.
+    #[clap(long, default_value = "60")]
+    ready_timeout: f64,
+
+    /// The number of recent events, joins, eliminations and match changes, sent on connect.
+    #[clap(long, default_value = "20")]
+    event_log_size: usize,
+
+    /// The number of chat messages a player can send in a window of time.
+    #[clap(long, default_value = "5")]
+    chat_max_messages: usize,
+
+    /// The duration, in seconds, of the chat rate limit window.
+    #[clap(long, default_value = "10")]
+    chat_window: f64,
+
+    /// How long, in seconds, a player that sent too many chat messages is muted.
+    #[clap(long, default_value = "10")]
+    chat_cooldown: f64,
+
+    /// How many times the physics are stepped during a server tick, with a subdivided
+    /// delta time, more steps prevent fast players from going through thin walls.
+    #[clap(long, default_value = "1")]
+    physics_substeps: u32,
+
+    /// Move the players by their velocity without the physics engine, they are kept in the
+    /// arena but go through each other, the teleport pads and the eliminations are disabled.
+    #[clap(long)]
+    no_physics: bool,
+
+    /// Move the players by the actual duration of each frame instead of the duration of a
+    /// tick, they keep their speed when the server misses its ticks, only without physics.
+    #[clap(long, requires = "no_physics")]
+    frame_delta_movement: bool,
+
+    /// How many inputs of each player are buffered ahead and applied one per tick.
+    #[clap(long, default_value = "3")]
+    input_buffer: usize,
+
+    /// How many ticks ahead the clients apply their inputs, the server applies them at the same
+    /// tick, the same for all the players whatever their latency, to make the game fairer.
+    #[clap(long, default_value = "0")]
+    input_delay: u64,
+
+    /// The maximum number of players, the cubes waiting for their player to reconnect included.
+    #[clap(long, default_value = "64")]
+    max_players: usize,
+
+    /// When the server is full, kick the player that disconnected the earliest
+    /// or the most idle one to make room for a new player.
+    #[clap(long)]
+    kick_idle_on_full: bool,
+
+    /// Record where the players spend their time in cells of this size,
+    /// type `heatmap <path>` to write it as CSV.
+    #[clap(long, value_parser = parse_cell_size)]
+    heatmap_cell_size: Option<f32>,
+
+    /// How strongly the players bounce off each other when they collide, relative to the speed
+    /// at which they collide, zero to disable it.
+    #[clap(long, default_value = "0")]
+    knockback: f32,
+
+    /// How long, in seconds, the players that just spawned can't be pushed, 0 to disable.
+    #[clap(long, default_value = "0")]
+    spawn_protection: f64,
+
+    /// The players protected after spawning can't push the others either.
+    #[clap(long)]
+    spawn_protection_passive: bool,
+
+    /// The players pushed beyond the edges of the arena are eliminated
+    /// and the last player that hit them scores.
+    #[clap(long, conflicts_with = "arena_walls")]
+    eliminate_out_of_bounds: bool,
+
+    /// How long, in seconds, a hit is remembered to know who eliminated a player.
+    #[clap(long, default_value = "3")]
+    last_hit_window: f64,
+
+    /// Record the distribution of the ticks durations, type `ticks` to print it.
+    #[clap(long)]
+    tick_histogram: bool,
+
+    /// Record the inputs of the players, type `replay` to write them to this file.
+    #[clap(long)]
+    record_replay: Option<PathBuf>,
+
+    /// Simulate a recorded replay as fast as possible and print how long it took,
+    /// the server doesn't listen for clients.
+    #[clap(long)]
+    bench_replay: Option<PathBuf>,
+
+    /// Print the configuration resolved from the flags, the config file and the defaults
+    /// on startup, as JSON.
+    #[clap(long)]
+    print_config: bool,
+
+    /// Exit once the configuration is read, use it with `--print-config` to check it.
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// Parses the flags, the ones of the `--config` file are parsed first for the command line
+/// to override them.
+fn parse_opt(args: Vec<String>) -> Opt {
+    let opt = Opt::parse_from(&args);
+    let path = match &opt.config {
+        Some(path) => path,
+        None => return opt,
+    };
+    let content = fs::read_to_string(path).expect("failed to read the config file");
+    let file_args = config_file_args(&content).expect("invalid config file");
+    let (program, flags) = args.split_first().unwrap();
+    Opt::parse_from(iter::once(program).chain(&file_args).chain(flags))
 }
 
 fn main() {
-    let opt = Opt::parse();
+    let opt = parse_opt(env::args().collect());
+
+    if opt.print_config {
+        println!("{}", serde_json::to_string_pretty(&Config::resolve(&opt)).unwrap());
+    }
+    if opt.dry_run {
+        return;
+    }
+
+    if let Some(path) = opt.bench_replay {
+        print_benchmark(&path);
+        return;
+    }
+
+    set_safe_message_bytes(opt.safe_message_bytes);
 
     let mut app = App::new();
     app.add_plugins(MinimalPlugins);
-    app.add_plugin(PhysicsPlugin::default());
-    app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(1.0 / 60.0)));
+    // The minimal plugins don't print the logs, the warnings would be lost without it,
+    // it also installs the subscriber recording the spans of the systems when profiling.
+    app.add_plugin(bevy::log::LogPlugin);
+    if opt.no_physics {
+        // The systems reading the collisions still need the events, there will be none.
+        app.add_event::<CollisionEvent>();
+        app.insert_resource(KinematicMovement {
+            step: (1.0 / TICK_RATE) as f32,
+            frame_delta: opt.frame_delta_movement,
+        });
+    } else {
+        add_physics_plugin(&mut app, TICK_RATE, opt.physics_substeps);
+    }
+    app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(1.0 / TICK_RATE)));
 
     app.insert_resource(Lobby::default());
+    app.insert_resource(Connections::default());
+    let soccer = opt.mode == GameMode::Soccer;
+    if soccer && opt.teams != 2 {
+        warn!("The soccer mode is played by two teams, ignoring --teams {}.", opt.teams);
+    }
+    app.insert_resource(TeamCount(if soccer { 2 } else { opt.teams }));
+    if opt.friendly_collisions == FriendlyCollisions::Off {
+        app.add_system(friendly_collisions_system);
+    }
+    let arena = Arena { width: opt.arena_width, height: opt.arena_height };
+    app.insert_resource(arena);
+    app.insert_resource(ArenaScaling { base: arena, per_player: opt.arena_per_player });
+    let teleport_pads = if opt.teleport_pads { default_teleport_pads(arena) } else { Vec::new() };
+    app.insert_resource(TeleportPads(teleport_pads));
+    app.insert_resource(WorldSyncMaxBytes(opt.world_sync_max_bytes));
+    app.insert_resource(WorldSyncBudget(opt.world_sync_budget));
+    if let Some(aoi_radius) = opt.aoi_radius {
+        app.insert_resource(AreaOfInterest::new(aoi_radius, opt.aoi_radius_max));
+    }
+    app.insert_resource(SkipIdleSyncs { enabled: opt.skip_idle_syncs, epsilon: opt.pos_epsilon });
+    if let Some(client_kbps) = opt.adaptive_sync_kbps {
+        app.insert_resource(AdaptiveSync::new(client_kbps));
+    }
+    app.insert_resource(QuantizePositions(opt.quantize_positions));
+    let reconnect_grace = Duration::from_secs_f64(opt.reconnect_grace);
+    app.insert_resource(PendingDisconnects::new(reconnect_grace));
+    app.insert_resource(FullServerPolicy {
+        max_players: opt.max_players,
+        kick_idle: opt.kick_idle_on_full,
+    });
+    app.insert_resource(MatchState::default());
+    app.insert_resource(ReadyCheck::new(Duration::from_secs_f64(opt.ready_timeout)));
+    app.insert_resource(EventLog::new(opt.event_log_size));
+    app.insert_resource(ChatRateLimit::new(
+        opt.chat_max_messages,
+        Duration::from_secs_f64(opt.chat_window),
+        Duration::from_secs_f64(opt.chat_cooldown),
+    ));
 
     app.add_plugin(RenetServerPlugin);
-    app.insert_resource(new_renet_server(opt.listen_addr));
+    app.insert_resource(new_renet_server(opt.listen_addr, opt.max_players));
+    app.insert_resource(ServerName(opt.name));
+    match ProbeSocket::bind(opt.listen_addr) {
+        Ok(socket) => app.insert_resource(socket),
+        Err(e) => {
+            eprintln!("Failed to open the probe socket next to {}: {}", opt.listen_addr, e);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(feature = "observer")]
+    if let Some(observer_addr) = opt.observer_addr {
+        match ObserverFeed::bind(observer_addr, opt.observer_interval) {
+            Ok(feed) => app.insert_resource(feed),
+            Err(e) => {
+                eprintln!("Failed to listen for observers on {}: {}", observer_addr, e);
+                std::process::exit(1);
+            }
+        };
+        app.add_system(observer_feed_system);
+    }
+    app.add_system(answer_probes_system);
+    if let Some(coordinator) = opt.coordinator {
+        let game_addr = opt.public_addr.unwrap_or(opt.listen_addr);
+        app.insert_resource(CoordinatorLink { coordinator, game_addr, last_heartbeat: None });
+        app.add_system(coordinator_heartbeat_system);
+    }
     app.add_system(server_update_system);
+    app.add_system(disconnect_clients_system);
+    app.add_system(server_receive_messages_system);
     app.add_system(server_sync_players);
-    app.add_system(move_players_system);
+    app.add_system(event_log_system);
+    app.insert_resource(InputBufferSettings {
+        size: opt.input_buffer,
+        delay: opt.input_delay,
+        tick: 0,
+    });
+    app.add_system_to_stage(CoreStage::PreUpdate, apply_buffered_inputs_system);
+    app.insert_resource(ClientPolicy {
+        trust_clients: opt.trust_clients,
+        interpolation: InterpolationParams {
+            min_delay: opt.interp_min_delay,
+            max_delay: opt.interp_max_delay,
+        },
+        body_shape: opt.shape,
+        mode: opt.mode,
+        name_filter: opt.name_filter.as_deref().map_or_else(NameFilter::default, |path| {
+            NameFilter::load(path).expect("failed to read the name filter")
+        }),
+        filter_chat: opt.filter_chat,
+    });
+    if opt.trust_clients {
+        warn!("The clients are trusted, they can move anywhere, this mode is insecure.");
+    } else {
+        app.add_system(dash_system.before("move_players"));
+        app.add_system(move_players_system.label("move_players"));
+        if opt.no_physics {
+            app.add_system(kinematic_movement_system.after("move_players"));
+        }
+    }
+    if opt.knockback > 0. {
+        app.insert_resource(KnockbackStrength(opt.knockback));
+        app.add_system(knockback_system);
+    }
+    app.add_system(expire_pending_disconnects_system);
+    if opt.spawn_protection > 0. {
+        app.insert_resource(SpawnProtection {
+            duration: Duration::from_secs_f64(opt.spawn_protection),
+            passive: opt.spawn_protection_passive,
+        });
+        app.add_system(protect_spawned_players_system);
+        app.add_system(announce_spawn_protection_system);
+        app.add_system(expire_spawn_protection_system);
+    }
+    app.add_system(teleport_players_system);
+    app.add_system(move_teleport_pads_system);
+    app.add_system(ready_check_system);
+    app.add_system(resize_arena_system);
+    if opt.eliminate_out_of_bounds {
+        let last_hit_window = Duration::from_secs_f64(opt.last_hit_window);
+        app.insert_resource(EliminationMode { last_hit_window });
+        app.add_system(record_last_hits_system);
+        app.add_system(eliminate_out_of_bounds_system.label("eliminate"));
+        app.add_system(player_scores_system.after("eliminate"));
+    }
+    if opt.arena_walls {
+        app.add_system(arena_walls_system);
+    }
+
+    let shutdown = Shutdown::default();
+    app.insert_resource(AdminConsole::spawn(&shutdown));
+    #[cfg(feature = "control-api")]
+    if let (Some(control_addr), Some(token)) = (opt.control_addr, opt.control_token.clone()) {
+        match ControlApi::spawn(control_addr, token, &shutdown) {
+            Ok(api) => app.insert_resource(api),
+            Err(e) => {
+                eprintln!("Failed to listen for the control API on {}: {}", control_addr, e);
+                std::process::exit(1);
+            }
+        };
+        app.add_system(control_api_system);
+    }
+    app.insert_resource(shutdown.clone());
+    app.add_event::<AdminCommand>();
+    app.add_system(admin_console_system);
+    app.add_system(heatmap_command_system);
+    app.add_system(spawn_dummy_command_system);
+    app.add_system(resize_command_system);
+    app.add_system(move_speed_command_system);
+    app.add_system(quit_command_system.label("quit_command"));
+    app.add_system(shutdown_on_exit_system.after("quit_command"));
+    app.add_system(announce_command_system);
+    app.add_system_to_stage(CoreStage::PreUpdate, move_dummies_system);
+    let tick_budget = Duration::from_secs_f64(1.0 / TICK_RATE);
+    app.insert_resource(TickTiming::new(tick_budget, opt.tick_histogram));
+    app.add_system_to_stage(CoreStage::First, tick_start_system);
+    app.add_system_to_stage(CoreStage::Last, tick_end_system);
+    app.add_system(tick_timing_command_system);
+    if let Some(path) = opt.record_replay {
+        let replay = Replay { tick_rate: TICK_RATE, ticks: Vec::new() };
+        app.insert_resource(ReplayRecorder { path, replay });
+        app.add_system(record_replay_system);
+        app.add_system(replay_command_system);
+    }
+    if let Some(cell_size) = opt.heatmap_cell_size {
+        app.insert_resource(Heatmap::new(arena, cell_size));
+        app.add_system(record_heatmap_system);
+    }
 
     app.add_startup_system(setup);
-    app.add_system(panic_on_error_system);
+    app.add_startup_system(spawn_teleport_pads);
+    if opt.ball || soccer {
+        app.add_startup_system(spawn_ball);
+        app.add_system(reset_ball_system);
+    }
+    if soccer {
+        app.insert_resource(Soccer::new(opt.win_score));
+        app.add_system(soccer_goal_system.label("soccer_goal"));
+        app.add_system(team_scores_system.after("soccer_goal"));
+    }
+    app.add_system(handle_renet_errors_system);
 
     app.run();
+    if !shutdown.join(SHUTDOWN_TIMEOUT) {
+        eprintln!("Some background threads didn't stop in time, exiting anyway.");
+    }
 }
 
 fn setup(_commands: Commands) {}
 
-fn new_renet_server(listen_addr: SocketAddr) -> RenetServer {
-    let socket = UdpSocket::bind(listen_addr).unwrap();
+/// Everything the server runs with, the options and the values that can't be changed.
+#[derive(Debug, Serialize)]
+struct Config<'a> {
+    protocol_id: u64,
+    tick_rate: f64,
+    channels: [(&'static str, u8); 4],
+    arena: Arena,
+    options: &'a Opt,
+}
+
+impl Config<'_> {
+    fn resolve(opt: &Opt) -> Config {
+        Config {
+            protocol_id: PROTOCOL_ID,
+            tick_rate: TICK_RATE,
+            channels: CHANNELS,
+            arena: Arena { width: opt.arena_width, height: opt.arena_height },
+            options: opt,
+        }
+    }
+}
+
+struct WorldSyncMaxBytes(usize);
+
+/// The number of teams in which the players are balanced.
+struct TeamCount(u8);
+
+/// The size limit, in bytes, of the world sent to a single client.
+struct WorldSyncBudget(Option<usize>);
+
+/// How the clients are expected to behave, it is sent to them in the `InitState`.
+struct ClientPolicy {
+    /// Whether the positions sent by the clients are accepted, instead of moving the players
+    /// with their inputs. This is insecure and only meant to prototype.
+    trust_clients: bool,
+    /// The interpolation recommended to the clients.
+    interpolation: InterpolationParams,
+    /// The shape of all the players, the clients draw them with it.
+    body_shape: BodyShape,
+    /// The rules of the match, the clients draw the goals of the soccer mode.
+    mode: GameMode,
+    /// The words the names of the players can't contain.
+    name_filter: NameFilter,
+    /// Whether the words of the name filter are also masked in the chat.
+    filter_chat: bool,
+}
+
+/// Whether the positions are sent on 16 bits per axis instead of a float.
+struct QuantizePositions(bool);
+
+/// Whether the world is not sent again when nothing moved. It is still sent
+/// regularly to correct the clients that lost the last one sent.
+struct SkipIdleSyncs {
+    enabled: bool,
+    /// The distance under which a player is not considered to have moved.
+    epsilon: f32,
+}
+
+fn new_renet_server(listen_addr: SocketAddr, max_players: usize) -> RenetServer {
+    let socket = UdpTransport::bind(listen_addr).unwrap().into_socket();
     info!("Listening on {:?}", socket);
 
-    let connection_config = RenetConnectionConfig::default();
+    // We accept one more client than the number of players so that
+    // a new player can connect and make room by kicking an idle one.
+    let connection_config = connection_config();
+    let max_clients = max_players + 1;
     let server_config =
-        ServerConfig::new(64, PROTOCOL_ID, listen_addr, ServerAuthentication::Unsecure);
+        ServerConfig::new(max_clients, PROTOCOL_ID, listen_addr, ServerAuthentication::Unsecure);
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
     RenetServer::new(current_time, server_config, connection_config, socket).unwrap()
 }
 
+/// Disconnects the player right away, without waiting for it to come back, and tells the others.
+fn kick_player(
+    commands: &mut Commands,
+    server: &mut RenetServer,
+    connections: &mut Connections,
+    pending: &mut PendingDisconnects,
+    lobby: &mut Lobby,
+    player: Player,
+    reason: &str,
+) {
+    let client_id = connections.client_id(player);
+    if let Some(client_id) = client_id {
+        connections.disconnect(server, client_id, reason);
+    }
+    pending.remove_player(player);
+    if let Some(entity) = lobby.players.remove(&player) {
+        commands.entity(entity).despawn();
+    }
+    let message = encode(&ServerMessage::PlayerDisconnected { player });
+    match client_id {
+        Some(client_id) => broadcast_except(server, CONNECTION_EVENTS_CHANNEL, client_id, message),
+        None => server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn server_update_system(
     mut server_events: EventReader<ServerEvent>,
     mut commands: Commands,
     mut lobby: ResMut<Lobby>,
     mut server: ResMut<RenetServer>,
+    (arena, teleport_pads): (Res<Arena>, Res<TeleportPads>),
+    input_buffer: Res<InputBufferSettings>,
+    match_state: Res<MatchState>,
+    ready_check: Res<ReadyCheck>,
+    mut chat_rate_limit: ResMut<ChatRateLimit>,
+    client_policy: Res<ClientPolicy>,
+    mut pending: ResMut<PendingDisconnects>,
+    team_count: Res<TeamCount>,
+    full_server: Res<FullServerPolicy>,
+    mut connections: ResMut<Connections>,
+    players: Query<(&Transform, Option<&SessionToken>, Option<&LastActivity>), With<Player>>,
+    looks: Query<(
+        Option<&Team>,
+        Option<&Skin>,
+        Option<&SizeClass>,
+        Option<&MoveSpeed>,
+        Option<&Dummy>,
+        Option<&PlayerName>,
+        Option<&Score>,
+    )>,
 ) {
+    // The players spawned during this frame are counted here.
+    let mut players_per_team = vec![0; team_count.0 as usize];
+    for team in looks.iter().filter_map(|(team, _, _, _, _, _, _)| team) {
+        if let Some(count) = players_per_team.get_mut(team.0 as usize) {
+            *count += 1;
+        }
+    }
+    // The players spawned during this frame are put on a free spawn point and added here.
+    let mut occupied: Vec<_> = players.iter().map(|(t, _, _)| t.translation.truncate()).collect();
+    let score_of =
+        |entity: Entity| looks.get(entity).ok().and_then(|looks| looks.6).map_or(0, |s| s.0);
+
     for event in server_events.iter() {
         match event {
-            ServerEvent::ClientConnected(id, _) => {
-                let player = Player { id: *id };
-                println!("{:?} connected.", player);
+            ServerEvent::ClientConnected(client_id, user_data) => {
+                let client_id = *client_id;
+                let request = match ConnectRequest::from_user_data(user_data) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        println!(
+                            "Refusing client {}, its connect request is invalid: {}.",
+                            client_id, e
+                        );
+                        let reason = format!("invalid connect request: {}", e);
+                        connections.disconnect(&mut server, client_id, &reason);
+                        continue;
+                    }
+                };
+                let token = request.session_token;
+
+                // A player that comes back keeps its id, whatever its new client id.
+                let previous = token.and_then(|t| pending.reclaim(t, Instant::now()));
+                let previous = previous.filter(|p| lobby.players.contains_key(&p.player));
+                let reconnected = previous.as_ref().map(|p| p.player);
+                let resumed = request.resume && reconnected.is_some();
+                let player = reconnected.unwrap_or(Player { id: client_id });
+                println!("{:?} connected with client {}.", player, client_id);
 
-                // Spawn player cube
-                let player_entity = spawn_player(&mut commands, player);
+                if reconnected.is_none() && lobby.players.len() >= full_server.max_players {
+                    let kicked = full_server.kick_idle.then(|| {
+                        let waiting = pending.players.values().map(|p| (p.player, p.since));
+                        let active = lobby.players.iter().filter_map(|(player, entity)| {
+                            Some((*player, players.get(*entity).ok()?.2?.0))
+                        });
+                        eviction_candidate(waiting, active)
+                    });
 
-                // We could send an InitState with all the players id and positions for the client
+                    match kicked.flatten() {
+                        Some(kicked) => {
+                            println!("The server is full, kicking {:?}.", kicked);
+                            kick_player(
+                                &mut commands,
+                                &mut server,
+                                &mut connections,
+                                &mut pending,
+                                &mut lobby,
+                                kicked,
+                                "kicked to make room for a new player",
+                            );
+                        }
+                        None => {
+                            println!("The server is full, refusing {:?}.", player);
+                            connections.disconnect(&mut server, client_id, "the server is full");
+                            continue;
+                        }
+                    }
+                }
+
+                connections.insert(client_id, player);
+                let message = encode(&ServerMessage::InitState {
+                    player,
+                    tick_rate: TICK_RATE,
+                    tick: input_buffer.tick,
+                    input_delay: input_buffer.delay,
+                    trusted_positions: client_policy.trust_clients,
+                    interpolation: client_policy.interpolation,
+                    body_shape: client_policy.body_shape,
+                    mode: client_policy.mode,
+                    arena: *arena,
+                    teleport_pads: teleport_pads.0.clone(),
+                    match_state: *match_state,
+                    resumed,
+                });
+                server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+
+                for ready_player in &ready_check.ready {
+                    let message =
+                        encode(&ServerMessage::ReadyChanged { player: *ready_player, ready: true });
+                    server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                }
+
+                // A player that kept its world is only told about the players that joined
+                // or left while it was away and the scores that changed, the others may have
+                // changed size or speed.
+                let scores =
+                    lobby.players.iter().map(|(player, entity)| (*player, score_of(*entity)));
+                let diff = match previous {
+                    Some(previous) if resumed => lobby_diff(&previous.known_players, scores),
+                    _ => LobbyDiff { joined: scores.collect(), ..default() },
+                };
+                for left_player in diff.left {
+                    let message =
+                        encode(&ServerMessage::PlayerDisconnected { player: left_player });
+                    server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                }
+
+                // We could also send all the players id and positions in the InitState
                 // but this is easier to do.
-                for lobby_player in lobby.players.keys() {
-                    let message = bincode::serialize(&ServerMessage::PlayerConnected {
+                let joined = diff.joined.iter().map(|(player, _)| player);
+                for lobby_player in joined.chain(&diff.stayed) {
+                    let entity = lobby.players[lobby_player];
+                    let (team, skin, size, speed, dummy, name, _) =
+                        looks.get(entity).unwrap_or_default();
+                    if !diff.stayed.contains(lobby_player) {
+                        let message = encode(&ServerMessage::PlayerConnected {
+                            player: *lobby_player,
+                            team: team.copied(),
+                            skin: skin.copied(),
+                            name: shown_name(*lobby_player, name),
+                            bot: dummy.is_some(),
+                        });
+                        server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                    }
+                    if let Some(size) = size {
+                        let message = encode(&ServerMessage::PlayerResized {
+                            player: *lobby_player,
+                            size: *size,
+                        });
+                        server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                    }
+                    if let Some(speed) = speed {
+                        let message = encode(&ServerMessage::PlayerSpeedChanged {
+                            player: *lobby_player,
+                            speed: *speed,
+                        });
+                        server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                    }
+                }
+
+                for (lobby_player, score) in diff.joined.iter().chain(&diff.rescored) {
+                    let message = encode(&ServerMessage::ScoreChanged {
                         player: *lobby_player,
-                    })
-                    .unwrap();
-                    server.send_message(player.id, CONNECTION_EVENTS_CHANNEL, message);
+                        score: *score,
+                    });
+                    server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
                 }
 
-                lobby.players.insert(player, player_entity);
+                let message = match reconnected {
+                    Some(player) => {
+                        println!("{:?} is back.", player);
+                        ServerMessage::PlayerReconnected { player }
+                    }
+                    None => {
+                        // Spawn player cube
+                        let position = free_spawn_position(&occupied);
+                        occupied.push(position);
+                        let shape = client_policy.body_shape;
+                        let player_entity = spawn_player(&mut commands, player, position, shape);
+                        commands.entity(player_entity).insert(LastActivity(Instant::now()));
+                        if let Some(token) = token {
+                            commands.entity(player_entity).insert(token);
+                        }
+
+                        let team = (team_count.0 > 0).then(|| {
+                            let team = balanced_team(request.team, &players_per_team);
+                            players_per_team[team.0 as usize] += 1;
+                            team
+                        });
+                        if let Some(team) = team {
+                            commands.entity(player_entity).insert(team);
+                        }
+
+                        if let Some(skin) = request.skin {
+                            commands.entity(player_entity).insert(skin);
+                        }
 
-                let message =
-                    bincode::serialize(&ServerMessage::PlayerConnected { player }).unwrap();
-                server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+                        let filter = &client_policy.name_filter;
+                        let name = PlayerName(filter.player_name(request.name.as_deref(), player));
+                        println!("{:?} is named {:?}.", player, name.0);
+                        commands.entity(player_entity).insert(name.clone());
+
+                        lobby.players.insert(player, player_entity);
+                        ServerMessage::PlayerConnected {
+                            player,
+                            team,
+                            skin: request.skin,
+                            name,
+                            bot: false,
+                        }
+                    }
+                };
+
+                // A player that came back already heard about itself with the other players.
+                let message = encode(&message);
+                if reconnected.is_none() {
+                    server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message.clone());
+                }
+                broadcast_except(&mut server, CONNECTION_EVENTS_CHANNEL, client_id, message);
+
+                let message = encode(&ServerMessage::Welcome { player });
+                server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
             }
-            ServerEvent::ClientDisconnected(id) => {
-                let player = Player { id: *id };
+            ServerEvent::ClientDisconnected(client_id) => {
+                let player = connections.remove(*client_id);
                 println!("{:?} disconnected.", player);
+                chat_rate_limit.remove(player);
 
-                if let Some(player_entity) = lobby.players.remove(&player) {
-                    commands.entity(player_entity).despawn();
+                let player_entity = lobby.players.get(&player).copied();
+                let token = player_entity.and_then(|e| players.get(e).ok()?.1);
+                match (player_entity, token) {
+                    // We keep the cube around, motionless, in case the player comes back.
+                    (Some(player_entity), Some(token)) if !pending.grace.is_zero() => {
+                        commands.entity(player_entity).insert(PlayerInput::default());
+                        let known_players =
+                            lobby.players.iter().map(|(p, e)| (*p, score_of(*e))).collect();
+                        pending.insert(*token, player, known_players, Instant::now());
+                    }
+                    // The player may already be gone if it was kicked to make room.
+                    _ => {
+                        if let Some(player_entity) = lobby.players.remove(&player) {
+                            commands.entity(player_entity).despawn();
+                            let message = encode(&ServerMessage::PlayerDisconnected { player });
+                            server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+                        }
+                    }
                 }
-
-                let message =
-                    bincode::serialize(&ServerMessage::PlayerDisconnected { player }).unwrap();
-                server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
             }
         }
     }
+}
 
-    // We move the players on the server side
+/// Moves the player where its client says it is, the physics must not move it elsewhere.
+fn store_trusted_position(commands: &mut Commands, lobby: &Lobby, player: Player, position: Vec2) {
+    if let Some(player_entity) = lobby.players.get(&player) {
+        let transform = Transform::from_translation(position.extend(0.));
+        commands.entity(*player_entity).insert(transform).insert(Velocity::default());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn buffer_input(
+    commands: &mut Commands,
+    lobby: &Lobby,
+    settings: &InputBufferSettings,
+    input_buffers: &mut Query<&mut InputBuffer>,
+    player: Player,
+    input: PlayerInput,
+    tick: u64,
+    sequence: Option<u32>,
+) {
+    if let Some(player_entity) = lobby.players.get(&player) {
+        if let Ok(mut buffer) = input_buffers.get_mut(*player_entity) {
+            buffer.push(input, sequence, settings.apply_at(tick), settings.size);
+        }
+        if input != PlayerInput::default() {
+            commands.entity(*player_entity).insert(LastActivity(Instant::now()));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn server_receive_messages_system(
+    mut commands: Commands,
+    mut server: ResMut<RenetServer>,
+    lobby: Res<Lobby>,
+    match_state: Res<MatchState>,
+    mut ready_check: ResMut<ReadyCheck>,
+    mut chat_rate_limit: ResMut<ChatRateLimit>,
+    input_buffer: Res<InputBufferSettings>,
+    client_policy: Res<ClientPolicy>,
+    mut connections: ResMut<Connections>,
+    mut aoi: Option<ResMut<AreaOfInterest>>,
+    mut input_buffers: Query<&mut InputBuffer>,
+    looks: Query<(
+        Option<&Team>,
+        Option<&Skin>,
+        Option<&SizeClass>,
+        Option<&MoveSpeed>,
+        Option<&Dummy>,
+        Option<&PlayerName>,
+    )>,
+) {
     for client_id in server.clients_id().into_iter() {
-        let player = Player { id: client_id };
-        while let Some(message) = server.receive_message(client_id, PLAYER_POSITION_CHANNEL) {
-            let player_input: PlayerInput = bincode::deserialize(&message).unwrap();
-            if let Some(player_entity) = lobby.players.get(&player) {
-                commands.entity(*player_entity).insert(player_input);
+        if connections.is_disconnecting(client_id) {
+            continue;
+        }
+        let player = connections.player(client_id);
+        let mut messages: Vec<ClientMessage> = Vec::new();
+        for channel in [CLIENT_INPUT_CHANNEL, CLIENT_MESSAGES_CHANNEL] {
+            while let Some(message) = server.receive_message(client_id, channel) {
+                match try_decode(&message) {
+                    Ok(message) => messages.push(message),
+                    Err(e) => warn!("Skipping a message from {:?}: {}", player, e),
+                }
+            }
+        }
+
+        for message in messages {
+            match message {
+                // We move the players on the server side, at the tick the client applies them.
+                ClientMessage::Input { input, tick } => buffer_input(
+                    &mut commands,
+                    &lobby,
+                    &input_buffer,
+                    &mut input_buffers,
+                    player,
+                    input,
+                    tick,
+                    None,
+                ),
+                // A tagged input is an input like the others, only echoed once applied.
+                ClientMessage::TaggedInput { input, tick, sequence } => buffer_input(
+                    &mut commands,
+                    &lobby,
+                    &input_buffer,
+                    &mut input_buffers,
+                    player,
+                    input,
+                    tick,
+                    Some(sequence),
+                ),
+                // The position is taken as is, the client can go anywhere.
+                ClientMessage::Position(position) if client_policy.trust_clients => {
+                    store_trusted_position(&mut commands, &lobby, player, position)
+                }
+                ClientMessage::Position(_) => {
+                    warn!(
+                        "Ignoring the position sent by {:?}, the clients are not trusted.",
+                        player
+                    );
+                }
+                ClientMessage::Chat(text) => match chat_rate_limit.check(player, Instant::now()) {
+                    Ok(()) => {
+                        let text = chat_text(&client_policy, text);
+                        match parse_whisper(&text) {
+                            Some(whisper) => {
+                                let connected: Vec<_> = server
+                                    .clients_id()
+                                    .into_iter()
+                                    .map(|client_id| {
+                                        let player = connections.player(client_id);
+                                        let name = lobby
+                                            .players
+                                            .get(&player)
+                                            .and_then(|entity| looks.get(*entity).ok())
+                                            .and_then(|looks| looks.5);
+                                        (player, shown_name(player, name))
+                                    })
+                                    .collect();
+                                let (recipient, message) =
+                                    whisper_message(player, whisper, &connected);
+                                if let Some(recipient) = connections.client_id(recipient) {
+                                    let message = encode(&message);
+                                    server.send_message(
+                                        recipient,
+                                        CONNECTION_EVENTS_CHANNEL,
+                                        message,
+                                    );
+                                }
+                            }
+                            None => {
+                                let message = encode(&ServerMessage::Chat { player, text });
+                                server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+                            }
+                        }
+                    }
+                    Err(retry_after) => {
+                        let message = encode(&ServerMessage::ChatThrottled { retry_after });
+                        server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                    }
+                },
+                ClientMessage::Ready(ready) => {
+                    if *match_state == MatchState::Lobby {
+                        ready_check.set_ready(player, ready, Instant::now());
+                        let message = encode(&ServerMessage::ReadyChanged { player, ready });
+                        server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+                    }
+                }
+                ClientMessage::Ping(value) => {
+                    let message = encode(&ServerMessage::Pong(value));
+                    server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                }
+                ClientMessage::WelcomeReceived => connections.set_initialized(client_id),
+                ClientMessage::ViewRadius(radius) => match aoi.as_mut() {
+                    Some(aoi) => {
+                        let radius = aoi.request(player, radius);
+                        println!("{:?} sees the players within {}.", player, radius);
+                    }
+                    None => warn!("Ignoring the view radius of {:?}, there is no AOI.", player),
+                },
+                ClientMessage::RequestFullSync => {
+                    for (lobby_player, entity) in lobby.players.iter() {
+                        let (team, skin, size, speed, dummy, name) =
+                            looks.get(*entity).unwrap_or_default();
+                        let message = encode(&ServerMessage::PlayerConnected {
+                            player: *lobby_player,
+                            team: team.copied(),
+                            skin: skin.copied(),
+                            name: shown_name(*lobby_player, name),
+                            bot: dummy.is_some(),
+                        });
+                        server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                        if let Some(size) = size {
+                            let message = encode(&ServerMessage::PlayerResized {
+                                player: *lobby_player,
+                                size: *size,
+                            });
+                            server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                        }
+                        if let Some(speed) = speed {
+                            let message = encode(&ServerMessage::PlayerSpeedChanged {
+                                player: *lobby_player,
+                                speed: *speed,
+                            });
+                            server.send_message(client_id, CONNECTION_EVENTS_CHANNEL, message);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the first spawn point that doesn't overlap any of the `occupied` positions,
+/// the players that left free their spawn point and the others may have moved onto one.
+fn free_spawn_position(occupied: &[Vec2]) -> Vec2 {
+    let size = Vec2::new(PLAYER_SQUARE_WIDTH, PLAYER_SQUARE_HEIGHT);
+    let overlaps = |a: Vec2, b: Vec2| (a - b).abs().cmplt(size).all();
+    (0..)
+        .map(spawn_position)
+        .find(|position| !occupied.iter().any(|occupied| overlaps(*position, *occupied)))
+        .unwrap()
+}
+
+/// Returns the spawn point at `index` along a square spiral around the origin,
+/// the spawn points are spaced so that the players spawned on them don't overlap.
+fn spawn_position(index: usize) -> Vec2 {
+    let (mut x, mut y) = (0i32, 0i32);
+    let (mut dx, mut dy) = (1i32, 0i32);
+    let (mut segment_length, mut segment_walked, mut turns) = (1, 0, 0);
+    for _ in 0..index {
+        x += dx;
+        y += dy;
+        segment_walked += 1;
+        if segment_walked == segment_length {
+            segment_walked = 0;
+            let (new_dx, new_dy) = (-dy, dx);
+            dx = new_dx;
+            dy = new_dy;
+            turns += 1;
+            if turns % 2 == 0 {
+                segment_length += 1;
             }
         }
     }
+
+    let spacing = Vec2::new(PLAYER_SQUARE_WIDTH, PLAYER_SQUARE_HEIGHT) * SPAWN_SPACING;
+    Vec2::new(x as f32, y as f32) * spacing
+}
+
+/// The dummies and the players spawned before they had a name are named after their id.
+fn shown_name(player: Player, name: Option<&PlayerName>) -> PlayerName {
+    name.cloned().unwrap_or_else(|| PlayerName(fallback_name(player)))
+}
+
+/// Masks the filtered words of a chat message when the server filters the chat.
+fn chat_text(client_policy: &ClientPolicy, text: String) -> String {
+    match client_policy.filter_chat {
+        true => client_policy.name_filter.mask(&text),
+        false => text,
+    }
 }
 
-fn spawn_player(commands: &mut Commands, player: Player) -> Entity {
+fn spawn_player(
+    commands: &mut Commands,
+    player: Player,
+    position: Vec2,
+    shape: BodyShape,
+) -> Entity {
     commands
         .spawn()
-        .insert(Transform::default())
+        .insert(Transform::from_translation(position.extend(0.)))
         .insert(GlobalTransform::default())
         .insert(PlayerInput::default())
+        .insert(InputBuffer::default())
+        .insert(Knockback::default())
+        .insert(DashCooldown::default())
+        .insert(Score::default())
+        .insert(Teleports::default())
         .insert(player)
         .insert(RigidBody::Dynamic)
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3::new(PLAYER_SQUARE_WIDTH / 2., PLAYER_SQUARE_HEIGHT / 2., 0.),
-            border_radius: None,
-        })
+        .insert(shape)
+        .insert(player_collision_shape(shape, SizeClass::Normal))
         .insert(Velocity::default())
         // .insert(PhysicMaterial { friction: 1.0, density: 10.0, ..Default::default() })
         .insert(RotationConstraints::lock())
         .id()
 }
 
-fn server_sync_players(mut server: ResMut<RenetServer>, query: Query<(&Transform, &Player)>) {
+#[allow(clippy::too_many_arguments)]
+fn server_sync_players(
+    mut server: ResMut<RenetServer>,
+    mut tick: Local<u64>,
+    max_bytes: Res<WorldSyncMaxBytes>,
+    budget: Res<WorldSyncBudget>,
+    quantize: Res<QuantizePositions>,
+    skip_idle: Res<SkipIdleSyncs>,
+    mut last_sent: Local<HashMap<u64, (u64, WorldSync)>>,
+    arena: Res<Arena>,
+    connections: Res<Connections>,
+    adaptive: Option<ResMut<AdaptiveSync>>,
+    aoi: Option<Res<AreaOfInterest>>,
+    query: Query<(&Transform, &Player, &Teleports)>,
+    objects: Query<(&Transform, &ObjectId)>,
+) {
+    let encode_part = |mut part: WorldSyncPart| {
+        if quantize.0 {
+            part.quantize(*arena);
+        }
+        encode(&part)
+    };
+
     let mut world = WorldSync::default();
-    for (transform, player) in query.iter() {
+    for (transform, player, teleports) in query.iter() {
+        if !connections.is_player_initialized(*player) {
+            continue;
+        }
         world.players_positions.insert(*player, transform.translation.xy());
+        if teleports.0 != 0 {
+            world.players_teleports.insert(*player, teleports.0);
+        }
+        if let Some(info) = connections.client_id(*player).and_then(|id| server.network_info(id)) {
+            let stats = ConnectionStats { rtt: info.rtt, packet_loss: info.packet_loss };
+            world.players_connections.insert(*player, stats);
+        }
+    }
+    for (transform, object) in objects.iter() {
+        world.objects.insert(*object, transform.translation.xy());
     }
 
-    let sync_message = bincode::serialize(&world).unwrap();
-    server.broadcast_message(WORLD_SYNC_CHANNEL, sync_message);
+    // The clients to which the world is sent this tick, the constrained ones are skipped.
+    let mut client_ids = connections.initialized();
+    if let Some(mut adaptive) = adaptive {
+        adaptive.retain(&client_ids);
+        let world_bytes = world.players_positions.len() * world_sync_player_bytes();
+        let world_kbps = (world_bytes * 8) as f32 * TICK_RATE as f32 / 1000.;
+        client_ids.retain(|client_id| {
+            let interval = match server.network_info(*client_id) {
+                Some(info) => {
+                    let available =
+                        available_kbps(adaptive.client_kbps, info.sent_kbps, info.packet_loss);
+                    sync_interval(available, world_kbps, info.rtt, info.packet_loss)
+                }
+                None => 1,
+            };
+            adaptive.is_due(*client_id, *tick, interval)
+        });
+    }
+
+    // Each client is compared to the last world it was sent, not all of them get it every tick.
+    if skip_idle.enabled {
+        let connected = connections.initialized();
+        last_sent.retain(|client_id, _| connected.contains(client_id));
+        let (idle, changed): (Vec<_>, Vec<_>) = client_ids.into_iter().partition(|client_id| {
+            last_sent.get(client_id).map_or(false, |(sent_tick, sent)| {
+                *tick - sent_tick < TICK_RATE as u64
+                    && !sent.positions_differ(&world, skip_idle.epsilon)
+            })
+        });
+        let sync_message = encode(&WorldSyncPart::unchanged(*tick));
+        send_to(&mut server, WORLD_SYNC_CHANNEL, idle, sync_message);
+        for client_id in &changed {
+            last_sent.insert(*client_id, (*tick, world.clone()));
+        }
+        client_ids = changed;
+    }
+
+    if budget.0.is_some() || aoi.is_some() {
+        for client_id in client_ids {
+            let own = connections.player(client_id);
+            let mut world = match &aoi {
+                Some(aoi) => players_within(&world, own, aoi.radius(own)),
+                None => world.clone(),
+            };
+            if let Some(budget) = budget.0 {
+                world = nearest_players(&world, own, budget / world_sync_player_bytes());
+            }
+            for part in split_world_sync(*tick, &world, max_bytes.0) {
+                let sync_message = encode_part(part);
+                server.send_message(client_id, WORLD_SYNC_CHANNEL, sync_message);
+            }
+        }
+    } else {
+        for part in split_world_sync(*tick, &world, max_bytes.0) {
+            let sync_message = encode_part(part);
+            send_to(&mut server, WORLD_SYNC_CHANNEL, client_ids.iter().copied(), sync_message);
+        }
+    }
+    *tick += 1;
 }
 
-fn move_players_system(mut query: Query<(&mut Velocity, &PlayerInput)>) {
-    for (mut velocity, input) in query.iter_mut() {
-        let x = (input.right as i8 - input.left as i8) as f32;
-        let y = (input.up as i8 - input.down as i8) as f32;
-        velocity.linear = Vec2::new(x, y).extend(0.) * PLAYER_MOVE_SPEED;
+fn move_players_system(
+    mut query: Query<(&mut Velocity, &PlayerInput, Option<&MoveSpeed>, &mut Knockback)>,
+) {
+    for (mut velocity, input, speed, mut knockback) in query.iter_mut() {
+        let linear = input_velocity(input, speed) + knockback.take();
+        velocity.linear = linear.extend(0.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trusted_positions_are_stored() {
+        use bevy::ecs::system::CommandQueue;
+
+        let mut world = World::new();
+        let player = Player { id: 1 };
+        let velocity = Velocity::from_linear(Vec3::new(100., 0., 0.));
+        let entity = world.spawn().insert(Transform::default()).insert(velocity).id();
+        let lobby = Lobby { players: [(player, entity)].into_iter().collect() };
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        store_trusted_position(&mut commands, &lobby, player, Vec2::new(42., -7.));
+        // The position of a player that is not in the lobby is ignored.
+        store_trusted_position(&mut commands, &lobby, Player { id: 2 }, Vec2::ZERO);
+        queue.apply(&mut world);
+
+        let transform = world.get::<Transform>(entity).unwrap();
+        assert_eq!(transform.translation, Vec3::new(42., -7., 0.));
+        assert_eq!(world.get::<Velocity>(entity).unwrap().linear, Vec3::ZERO);
+    }
+
+    fn overlap(a: Vec2, b: Vec2) -> bool {
+        (a - b).abs().cmplt(Vec2::new(PLAYER_SQUARE_WIDTH, PLAYER_SQUARE_HEIGHT)).all()
+    }
+
+    #[test]
+    fn spawn_positions_dont_overlap() {
+        let positions: Vec<_> = (0..50).map(spawn_position).collect();
+        for (i, a) in positions.iter().enumerate() {
+            for b in &positions[i + 1..] {
+                assert!(!overlap(*a, *b), "{} and {} overlap", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn spawn_in_the_slot_freed_by_a_player() {
+        let mut occupied: Vec<_> = (0..4).map(spawn_position).collect();
+        occupied.remove(1);
+        assert_eq!(free_spawn_position(&occupied), spawn_position(1));
+    }
+
+    #[test]
+    fn dont_spawn_on_a_player_that_moved() {
+        let occupied = [spawn_position(0), spawn_position(1) + Vec2::splat(1.)];
+        assert_eq!(free_spawn_position(&occupied), spawn_position(2));
+    }
+
+    #[test]
+    fn the_flags_win_over_the_config_file_which_wins_over_the_defaults() {
+        let path = env::temp_dir().join(format!("acerbus-server-config-{}", std::process::id()));
+        fs::write(&path, r#"{ "max_players": 16, "event_log_size": 5, "ball": true }"#).unwrap();
+        let args = ["acerbus-server", "--config", path.to_str().unwrap(), "--max-players", "8"];
+        let opt = parse_opt(args.iter().map(|arg| arg.to_string()).collect());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(opt.max_players, 8);
+        assert_eq!(opt.event_log_size, 5);
+        assert!(opt.ball);
+        assert_eq!(opt.chat_max_messages, 5);
+
+        let config = serde_json::to_value(Config::resolve(&opt)).unwrap();
+        assert_eq!(config["options"]["max_players"], 8);
+        assert_eq!(config["tick_rate"], TICK_RATE);
     }
 }
@@ -0,0 +1,95 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::console::AdminCommand;
+
+/// Changes the speed of a player and tells the clients, a gameplay event can call it too.
+pub fn set_move_speed(
+    commands: &mut Commands,
+    server: &mut RenetServer,
+    player: Player,
+    entity: Entity,
+    speed: MoveSpeed,
+) {
+    commands.entity(entity).insert(speed);
+    let message = encode(&ServerMessage::PlayerSpeedChanged { player, speed });
+    server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+}
+
+/// Changes the speed of a player with `speed <player id> <speed|default>`.
+pub fn move_speed_command_system(
+    mut commands: Commands,
+    mut admin_commands: EventReader<AdminCommand>,
+    mut server: ResMut<RenetServer>,
+    lobby: Res<Lobby>,
+) {
+    for AdminCommand(words) in admin_commands.iter() {
+        let words: Vec<_> = words.iter().map(String::as_str).collect();
+        let (player, speed) = match words.as_slice() {
+            ["speed", id, "default"] => match id.parse() {
+                Ok(id) => (Player { id }, MoveSpeed::default()),
+                Err(_) => {
+                    println!("usage: speed <player id> <speed|default>");
+                    continue;
+                }
+            },
+            ["speed", id, speed] => match (id.parse(), speed.parse::<f32>()) {
+                (Ok(id), Ok(speed)) if speed.is_finite() && speed >= 0. => {
+                    (Player { id }, MoveSpeed(speed))
+                }
+                _ => {
+                    println!("usage: speed <player id> <speed|default>");
+                    continue;
+                }
+            },
+            ["speed", ..] => {
+                println!("usage: speed <player id> <speed|default>");
+                continue;
+            }
+            _ => continue,
+        };
+
+        match lobby.players.get(&player) {
+            Some(entity) => {
+                set_move_speed(&mut commands, &mut server, player, *entity, speed);
+                println!("{:?} now moves at {}.", player, speed.0);
+            }
+            None => println!("{:?} is not connected.", player),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::new_renet_server;
+
+    #[test]
+    fn the_speed_command_changes_the_speed_of_a_player() {
+        let mut app = App::new();
+        app.add_event::<AdminCommand>();
+        app.insert_resource(new_renet_server("127.0.0.1:0".parse().unwrap(), 8));
+        let player = Player { id: 1 };
+        let entity = app.world.spawn().insert(player).id();
+        let mut lobby = Lobby::default();
+        lobby.players.insert(player, entity);
+        app.insert_resource(lobby);
+        app.add_system(move_speed_command_system);
+
+        let command =
+            |line: &str| AdminCommand(line.split_whitespace().map(String::from).collect());
+        app.world.send_event(command("speed 1 -5"));
+        app.world.send_event(command("speed 1 fast"));
+        app.update();
+        assert_eq!(app.world.get::<MoveSpeed>(entity), None);
+
+        app.world.send_event(command("speed 1 300"));
+        app.update();
+        assert_eq!(app.world.get::<MoveSpeed>(entity), Some(&MoveSpeed(300.)));
+
+        app.world.send_event(command("speed 1 default"));
+        app.update();
+        assert_eq!(app.world.get::<MoveSpeed>(entity), Some(&MoveSpeed::default()));
+    }
+}
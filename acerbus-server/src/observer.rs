@@ -0,0 +1,110 @@
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::elimination::Score;
+
+/// A read-only feed of the game for external tools like streaming overlays,
+/// every frame is a JSON object written on its own line to all the connected tools.
+pub struct ObserverFeed {
+    listener: TcpListener,
+    streams: Vec<TcpStream>,
+    /// A frame is only sent every this many ticks.
+    interval: u64,
+    tick: u64,
+}
+
+impl ObserverFeed {
+    pub fn bind(addr: SocketAddr, interval: u64) -> io::Result<ObserverFeed> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(ObserverFeed { listener, streams: Vec::new(), interval: interval.max(1), tick: 0 })
+    }
+}
+
+/// The state of the game as sent to the observers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ObserverFrame {
+    pub tick: u64,
+    pub players: Vec<ObserverPlayer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ObserverPlayer {
+    pub id: u64,
+    pub x: f32,
+    pub y: f32,
+    pub team: Option<u8>,
+    pub score: u32,
+}
+
+impl ObserverFrame {
+    /// Serializes the frame as a single line of JSON, ending with a new line.
+    pub fn to_json_line(&self) -> String {
+        let mut line = serde_json::to_string(self).unwrap();
+        line.push('\n');
+        line
+    }
+}
+
+pub fn observer_feed_system(
+    mut feed: ResMut<ObserverFeed>,
+    players: Query<(&Player, &Transform, &Score, Option<&Team>)>,
+) {
+    let feed = &mut *feed;
+    while let Ok((stream, addr)) = feed.listener.accept() {
+        match stream.set_nonblocking(true) {
+            Ok(()) => feed.streams.push(stream),
+            Err(e) => warn!("Failed to accept the observer {}: {}", addr, e),
+        }
+    }
+
+    feed.tick += 1;
+    if feed.streams.is_empty() || feed.tick % feed.interval != 0 {
+        return;
+    }
+
+    let mut players: Vec<_> = players
+        .iter()
+        .map(|(player, transform, score, team)| ObserverPlayer {
+            id: player.id,
+            x: transform.translation.x,
+            y: transform.translation.y,
+            team: team.map(|team| team.0),
+            score: score.0,
+        })
+        .collect();
+    players.sort_by_key(|player| player.id);
+
+    // The observers that can't keep up are dropped rather than slowing the server down.
+    let line = ObserverFrame { tick: feed.tick, players }.to_json_line();
+    feed.streams.retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_json_lines() {
+        let frame = ObserverFrame {
+            tick: 12,
+            players: vec![
+                ObserverPlayer { id: 1, x: 1.5, y: -2.0, team: Some(0), score: 3 },
+                ObserverPlayer { id: 4, x: 0.0, y: 10.0, team: None, score: 0 },
+            ],
+        };
+        assert_eq!(
+            frame.to_json_line(),
+            concat!(
+                r#"{"tick":12,"players":["#,
+                r#"{"id":1,"x":1.5,"y":-2.0,"team":0,"score":3},"#,
+                r#"{"id":4,"x":0.0,"y":10.0,"team":null,"score":0}]}"#,
+                "\n",
+            )
+        );
+    }
+}
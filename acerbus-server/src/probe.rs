@@ -0,0 +1,80 @@
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Instant;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::idle::FullServerPolicy;
+
+/// The name under which the server appears in the clients server browsers.
+pub struct ServerName(pub String);
+
+/// Answers the clients that want to know about the server before connecting.
+pub struct ProbeSocket(UdpSocket);
+
+impl ProbeSocket {
+    pub fn bind(listen_addr: SocketAddr) -> io::Result<ProbeSocket> {
+        let addr = probe_addr(listen_addr).ok_or_else(|| {
+            let message = "the probes are answered on the next port, there is none after 65535";
+            io::Error::new(io::ErrorKind::InvalidInput, message)
+        })?;
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(ProbeSocket(socket))
+    }
+}
+
+/// The coordinator this server regularly tells about itself, to be sent clients.
+pub struct CoordinatorLink {
+    pub coordinator: SocketAddr,
+    pub game_addr: SocketAddr,
+    pub last_heartbeat: Option<Instant>,
+}
+
+pub fn server_info(name: &ServerName, lobby: &Lobby, full_server: &FullServerPolicy) -> ServerInfo {
+    ServerInfo {
+        name: name.0.clone(),
+        players: lobby.players.len(),
+        max_players: full_server.max_players,
+    }
+}
+
+/// Sends a heartbeat to the coordinator every `HEARTBEAT_INTERVAL`, from the probe socket.
+pub fn coordinator_heartbeat_system(
+    socket: Res<ProbeSocket>,
+    mut link: ResMut<CoordinatorLink>,
+    name: Res<ServerName>,
+    lobby: Res<Lobby>,
+    full_server: Res<FullServerPolicy>,
+) {
+    let now = Instant::now();
+    if link.last_heartbeat.map_or(false, |last| now.duration_since(last) < HEARTBEAT_INTERVAL) {
+        return;
+    }
+
+    link.last_heartbeat = Some(now);
+    let info = server_info(&name, &lobby, &full_server);
+    let heartbeat = encode(&CoordinatorRequest::Heartbeat { game_addr: link.game_addr, info });
+    if let Err(e) = socket.0.send_to(&heartbeat, link.coordinator) {
+        warn!("Failed to send a heartbeat to the coordinator {}: {}", link.coordinator, e);
+    }
+}
+
+pub fn answer_probes_system(
+    socket: Res<ProbeSocket>,
+    name: Res<ServerName>,
+    lobby: Res<Lobby>,
+    full_server: Res<FullServerPolicy>,
+) {
+    let mut buffer = [0; 64];
+    while let Ok((len, addr)) = socket.0.recv_from(&mut buffer) {
+        if let Some(nonce) = parse_probe_request(&buffer[..len]) {
+            let info = server_info(&name, &lobby, &full_server);
+            let response = encode(&ProbeResponse { nonce, info });
+            if let Err(e) = socket.0.send_to(&response, addr) {
+                warn!("Failed to answer the probe of {}: {}", addr, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::connections::Connections;
+
+/// The players ready for the match to start, it starts when all of them are or after a timeout.
+#[derive(Debug)]
+pub struct ReadyCheck {
+    pub ready: HashSet<Player>,
+    pub timeout: Duration,
+    /// When the first player declared itself ready.
+    pub first_ready_at: Option<Instant>,
+}
+
+impl ReadyCheck {
+    pub fn new(timeout: Duration) -> ReadyCheck {
+        ReadyCheck { ready: HashSet::new(), timeout, first_ready_at: None }
+    }
+
+    pub fn set_ready(&mut self, player: Player, ready: bool, now: Instant) {
+        if ready {
+            self.ready.insert(player);
+            self.first_ready_at.get_or_insert(now);
+        } else {
+            self.ready.remove(&player);
+        }
+
+        if self.ready.is_empty() {
+            self.first_ready_at = None;
+        }
+    }
+
+    /// Whether all the connected players are ready or we waited long enough for the others.
+    pub fn should_start(&self, connected: &[Player], now: Instant) -> bool {
+        let all_ready = connected.iter().all(|p| self.ready.contains(p));
+        let timed_out =
+            self.first_ready_at.map_or(false, |at| now.duration_since(at) >= self.timeout);
+        !connected.is_empty() && (all_ready || timed_out)
+    }
+
+    pub fn reset(&mut self) {
+        self.ready.clear();
+        self.first_ready_at = None;
+    }
+}
+
+pub fn ready_check_system(
+    mut server: ResMut<RenetServer>,
+    mut ready_check: ResMut<ReadyCheck>,
+    mut match_state: ResMut<MatchState>,
+    connections: Res<Connections>,
+) {
+    let connected: Vec<_> =
+        server.clients_id().into_iter().map(|id| connections.player(id)).collect();
+    let now = Instant::now();
+
+    // The players that left are no more waited for, the others no more see them ready.
+    let gone: Vec<_> =
+        ready_check.ready.iter().filter(|p| !connected.contains(p)).copied().collect();
+    for player in gone {
+        ready_check.set_ready(player, false, now);
+        let message = encode(&ServerMessage::ReadyChanged { player, ready: false });
+        server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+    }
+
+    let new_state = match *match_state {
+        MatchState::Lobby if ready_check.should_start(&connected, now) => MatchState::Playing,
+        MatchState::Playing if connected.is_empty() => MatchState::Lobby,
+        _ => return,
+    };
+
+    info!("The match state is now {:?}.", new_state);
+    *match_state = new_state;
+    ready_check.reset();
+    let message = encode(&ServerMessage::MatchStateChanged { state: new_state });
+    server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_once_everyone_is_ready() {
+        let (a, b) = (Player { id: 1 }, Player { id: 2 });
+        let now = Instant::now();
+        let mut ready_check = ReadyCheck::new(Duration::from_secs(30));
+        assert!(!ready_check.should_start(&[], now));
+        ready_check.set_ready(a, true, now);
+        assert!(!ready_check.should_start(&[a, b], now));
+        ready_check.set_ready(b, true, now);
+        assert!(ready_check.should_start(&[a, b], now));
+        ready_check.set_ready(b, false, now);
+        assert!(!ready_check.should_start(&[a, b], now));
+    }
+
+    #[test]
+    fn starts_after_the_timeout_without_the_others() {
+        let (a, b) = (Player { id: 1 }, Player { id: 2 });
+        let now = Instant::now();
+        let mut ready_check = ReadyCheck::new(Duration::from_secs(30));
+        ready_check.set_ready(a, true, now);
+        assert!(!ready_check.should_start(&[a, b], now + Duration::from_secs(29)));
+        assert!(ready_check.should_start(&[a, b], now + Duration::from_secs(30)));
+
+        // The timeout starts again once nobody is ready.
+        ready_check.set_ready(a, false, now);
+        assert!(!ready_check.should_start(&[a, b], now + Duration::from_secs(60)));
+    }
+}
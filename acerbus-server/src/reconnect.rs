@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+/// The players that disconnected but could still come back, indexed by their session token.
+#[derive(Debug)]
+pub struct PendingDisconnects {
+    /// How long a disconnected player is kept around waiting for it to reconnect.
+    pub grace: Duration,
+    pub players: HashMap<SessionToken, PendingDisconnect>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingDisconnect {
+    pub player: Player,
+    pub since: Instant,
+    /// The players in the lobby when it disconnected with their score, the ones it knows about.
+    pub known_players: BTreeMap<Player, u32>,
+}
+
+/// What changed in the lobby while a player was away.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LobbyDiff {
+    /// The players that joined, with their score.
+    pub joined: Vec<(Player, u32)>,
+    pub left: Vec<Player>,
+    pub stayed: Vec<Player>,
+    /// The players that stayed but whose score changed, with their new score.
+    pub rescored: Vec<(Player, u32)>,
+}
+
+/// Compares the players and scores known at disconnection with the ones in the lobby now.
+pub fn lobby_diff(
+    known: &BTreeMap<Player, u32>,
+    now: impl IntoIterator<Item = (Player, u32)>,
+) -> LobbyDiff {
+    let now: BTreeMap<_, _> = now.into_iter().collect();
+    let mut diff = LobbyDiff::default();
+    for (player, score) in &now {
+        match known.get(player) {
+            Some(known_score) => {
+                diff.stayed.push(*player);
+                if known_score != score {
+                    diff.rescored.push((*player, *score));
+                }
+            }
+            None => diff.joined.push((*player, *score)),
+        }
+    }
+    diff.left = known.keys().filter(|player| !now.contains_key(player)).copied().collect();
+    diff
+}
+
+impl PendingDisconnects {
+    pub fn new(grace: Duration) -> PendingDisconnects {
+        PendingDisconnects { grace, players: HashMap::new() }
+    }
+
+    pub fn insert(
+        &mut self,
+        token: SessionToken,
+        player: Player,
+        known_players: BTreeMap<Player, u32>,
+        now: Instant,
+    ) {
+        self.players.insert(token, PendingDisconnect { player, since: now, known_players });
+    }
+
+    /// Returns the player that disconnected with this token if it is still within the grace period.
+    pub fn reclaim(&mut self, token: SessionToken, now: Instant) -> Option<PendingDisconnect> {
+        let pending = self.players.remove(&token)?;
+        (now.duration_since(pending.since) <= self.grace).then(|| pending)
+    }
+
+    /// Forgets a player that will not be allowed to reconnect, returns whether it was pending.
+    pub fn remove_player(&mut self, player: Player) -> bool {
+        let len = self.players.len();
+        self.players.retain(|_, pending| pending.player != player);
+        self.players.len() != len
+    }
+
+    /// Removes and returns the players that didn't come back in time.
+    pub fn take_expired(&mut self, now: Instant) -> Vec<Player> {
+        let grace = self.grace;
+        let mut expired = Vec::new();
+        self.players.retain(|_, pending| {
+            let alive = now.duration_since(pending.since) <= grace;
+            if !alive {
+                expired.push(pending.player);
+            }
+            alive
+        });
+        expired
+    }
+}
+
+/// Definitely removes the players that didn't reconnect within the grace period.
+pub fn expire_pending_disconnects_system(
+    mut commands: Commands,
+    mut pending: ResMut<PendingDisconnects>,
+    mut lobby: ResMut<Lobby>,
+    mut server: ResMut<RenetServer>,
+) {
+    for player in pending.take_expired(Instant::now()) {
+        println!("{:?} didn't reconnect in time.", player);
+
+        if let Some(player_entity) = lobby.players.remove(&player) {
+            commands.entity(player_entity).despawn();
+        }
+
+        let message = encode(&ServerMessage::PlayerDisconnected { player });
+        server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(id: u64) -> Player {
+        Player { id }
+    }
+
+    #[test]
+    fn reconnecting_in_time_gives_back_the_same_player() {
+        let (player, entity) = (player(1), Entity::from_raw(3));
+        let mut lobby = Lobby::default();
+        lobby.players.insert(player, entity);
+
+        let mut pending = PendingDisconnects::new(Duration::from_secs(5));
+        let now = Instant::now();
+        pending.insert(SessionToken(7), player, BTreeMap::from([(player, 0)]), now);
+        pending.insert(SessionToken(8), Player { id: 2 }, BTreeMap::new(), now);
+
+        let reconnect = now + Duration::from_secs(4);
+        assert!(pending.reclaim(SessionToken(9), reconnect).is_none());
+        let previous = pending.reclaim(SessionToken(7), reconnect).unwrap();
+        assert_eq!(previous.player, player);
+        assert_eq!(previous.known_players, BTreeMap::from([(player, 0)]));
+        // The cube was kept in the lobby, the player gets it back.
+        assert_eq!(lobby.players.get(&player), Some(&entity));
+        // A token can't be used twice and the other players are still pending.
+        assert!(pending.reclaim(SessionToken(7), reconnect).is_none());
+        assert_eq!(pending.take_expired(reconnect), vec![]);
+        assert_eq!(pending.players.len(), 1);
+    }
+
+    #[test]
+    fn players_expire_after_the_grace_period() {
+        let mut pending = PendingDisconnects::new(Duration::from_secs(5));
+        let now = Instant::now();
+        pending.insert(SessionToken(7), player(1), BTreeMap::new(), now);
+
+        let late = now + Duration::from_secs(6);
+        assert_eq!(pending.take_expired(late), vec![player(1)]);
+        assert!(pending.reclaim(SessionToken(7), late).is_none());
+    }
+
+    #[test]
+    fn diff_only_contains_the_changes_since_the_snapshot() {
+        let known = BTreeMap::from([(player(1), 0), (player(2), 3), (player(3), 1)]);
+        let now = [(player(1), 0), (player(2), 5), (player(4), 2)];
+
+        let diff = lobby_diff(&known, now);
+        assert_eq!(diff.joined, vec![(player(4), 2)]);
+        assert_eq!(diff.left, vec![player(3)]);
+        assert_eq!(diff.stayed, vec![player(1), player(2)]);
+        assert_eq!(diff.rescored, vec![(player(2), 5)]);
+    }
+
+    #[test]
+    fn nothing_changed() {
+        let known = BTreeMap::from([(player(1), 2)]);
+        let diff = lobby_diff(&known, [(player(1), 2)]);
+        assert!(diff.joined.is_empty() && diff.left.is_empty() && diff.rescored.is_empty());
+    }
+
+    #[test]
+    fn reclaim_after_the_grace_period_fails() {
+        let mut pending = PendingDisconnects::new(Duration::from_secs(5));
+        let token = SessionToken(7);
+        let now = Instant::now();
+        pending.insert(token, player(1), BTreeMap::new(), now);
+        assert!(pending.reclaim(token, now + Duration::from_secs(6)).is_none());
+        assert!(pending.players.is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+
+use crate::arena::arena_walls_system;
+use crate::console::AdminCommand;
+use crate::substeps::add_physics_plugin;
+use crate::teleport::{
+    default_teleport_pads, spawn_teleport_pads, teleport_players_system, TeleportPads,
+};
+use crate::{free_spawn_position, move_players_system, spawn_player, TICK_RATE};
+
+/// Records the inputs of the players at every tick, type `replay` to save them.
+pub struct ReplayRecorder {
+    pub path: PathBuf,
+    pub replay: Replay,
+}
+
+pub fn record_replay_system(
+    mut recorder: ResMut<ReplayRecorder>,
+    players: Query<(&Player, &PlayerInput, &Transform)>,
+) {
+    let inputs = players.iter().map(|(player, input, _)| (*player, *input)).collect();
+    let positions = players
+        .iter()
+        .map(|(player, _, transform)| (*player, transform.translation.truncate()))
+        .collect();
+    recorder.replay.ticks.push(ReplayTick { inputs, positions });
+}
+
+pub fn replay_command_system(
+    mut commands: EventReader<AdminCommand>,
+    recorder: Res<ReplayRecorder>,
+) {
+    for AdminCommand(words) in commands.iter() {
+        if words[0] == "replay" {
+            let ticks = recorder.replay.ticks.len();
+            match recorder.replay.save(&recorder.path) {
+                Ok(()) => {
+                    println!("{} ticks have been written to {}.", ticks, recorder.path.display())
+                }
+                Err(e) => {
+                    println!("Failed to write the replay to {}: {}", recorder.path.display(), e)
+                }
+            }
+        }
+    }
+}
+
+/// The replay being played by the benchmark and the tick it is at.
+struct ReplayPlayback {
+    replay: Replay,
+    tick: usize,
+}
+
+/// Spawns the players of the current tick, despawns the ones that left and applies their inputs.
+fn playback_system(
+    mut commands: Commands,
+    mut playback: ResMut<ReplayPlayback>,
+    mut lobby: ResMut<Lobby>,
+    players: Query<&Transform, With<Player>>,
+) {
+    let playback = &mut *playback;
+    let tick = match playback.replay.ticks.get(playback.tick) {
+        Some(tick) => tick,
+        None => return,
+    };
+    playback.tick += 1;
+
+    lobby.players.retain(|player, entity| {
+        let stays = tick.inputs.contains_key(player);
+        if !stays {
+            commands.entity(*entity).despawn();
+        }
+        stays
+    });
+
+    let mut occupied: Vec<_> = players.iter().map(|t| t.translation.truncate()).collect();
+    for (player, input) in &tick.inputs {
+        let entity = match lobby.players.get(player) {
+            Some(entity) => *entity,
+            None => {
+                // The shape of the players is not recorded, they are replayed as squares.
+                let position = free_spawn_position(&occupied);
+                occupied.push(position);
+                let entity = spawn_player(&mut commands, *player, position, BodyShape::default());
+                lobby.players.insert(*player, entity);
+                entity
+            }
+        };
+        commands.entity(entity).insert(*input);
+    }
+}
+
+/// What the benchmark measured.
+#[derive(Debug, Clone)]
+pub struct BenchmarkReport {
+    pub ticks: usize,
+    pub elapsed: Duration,
+    pub slowest_tick: Duration,
+    /// The time spent in every system of the simulation, in the order they run,
+    /// the physics steps and the engine take the rest.
+    pub systems: Vec<(&'static str, Duration)>,
+}
+
+/// The time spent in the timed systems, by name.
+#[derive(Debug, Default)]
+struct SystemTimings(Vec<(&'static str, Duration)>);
+
+/// A stage running a single system and adding the time it took to its timing.
+struct TimedStage {
+    name: &'static str,
+    stage: SystemStage,
+}
+
+impl Stage for TimedStage {
+    fn run(&mut self, world: &mut World) {
+        let start = Instant::now();
+        self.stage.run(world);
+        let elapsed = start.elapsed();
+        let mut timings = world.get_resource_mut::<SystemTimings>().unwrap();
+        match timings.0.iter_mut().find(|(name, _)| *name == self.name) {
+            Some((_, total)) => *total += elapsed,
+            None => timings.0.push((self.name, elapsed)),
+        }
+    }
+}
+
+/// Runs each system in its own stage, before the update stage and in this order.
+fn add_timed_systems(app: &mut App, systems: Vec<(&'static str, SystemStage)>) {
+    let mut previous = None;
+    for (name, stage) in systems {
+        let stage = TimedStage { name, stage };
+        match previous {
+            Some(previous) => app.add_stage_after(previous, name, stage),
+            None => app.add_stage_after(CoreStage::PreUpdate, name, stage),
+        };
+        previous = Some(name);
+    }
+}
+
+impl BenchmarkReport {
+    pub fn ticks_per_second(&self) -> f64 {
+        self.ticks as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Runs the simulation of a replay as fast as possible, without any socket.
+pub fn run_benchmark(replay: Replay) -> BenchmarkReport {
+    let tick_rate = if replay.tick_rate > 0. { replay.tick_rate } else { TICK_RATE };
+    let ticks = replay.ticks.len();
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins);
+    // The physics are stepped once per update whatever the real time elapsed.
+    add_physics_plugin(&mut app, tick_rate, 1);
+    let arena = Arena::default();
+    app.insert_resource(arena);
+    app.insert_resource(TeleportPads(default_teleport_pads(arena)));
+    app.add_startup_system(spawn_teleport_pads);
+    app.insert_resource(Lobby::default());
+    app.insert_resource(ReplayPlayback { replay, tick: 0 });
+    app.insert_resource(SystemTimings::default());
+    // The inputs are inserted before the players are moved.
+    add_timed_systems(
+        &mut app,
+        vec![
+            ("playback", SystemStage::single(playback_system)),
+            ("move_players", SystemStage::single(move_players_system)),
+            ("teleport_players", SystemStage::single(teleport_players_system)),
+            ("arena_walls", SystemStage::single(arena_walls_system)),
+        ],
+    );
+
+    let mut slowest_tick = Duration::ZERO;
+    let start = Instant::now();
+    for _ in 0..ticks {
+        let tick_start = Instant::now();
+        app.update();
+        slowest_tick = slowest_tick.max(tick_start.elapsed());
+    }
+
+    let systems = app.world.remove_resource::<SystemTimings>().unwrap_or_default().0;
+    BenchmarkReport { ticks, elapsed: start.elapsed(), slowest_tick, systems }
+}
+
+pub fn print_benchmark(path: &Path) {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("Failed to load the replay {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    let report = run_benchmark(replay);
+    println!(
+        "{} ticks in {:.2?}, {:.0} ticks per second, the slowest took {:.2?}.",
+        report.ticks,
+        report.elapsed,
+        report.ticks_per_second(),
+        report.slowest_tick
+    );
+    for (name, elapsed) in &report.systems {
+        let share = elapsed.as_secs_f64() / report.elapsed.as_secs_f64() * 100.;
+        println!("  {}: {:.2?} ({:.1}%)", name, elapsed, share);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn benchmark_a_tiny_replay() {
+        let player = Player { id: 1 };
+        let input = PlayerInput { right: true, ..PlayerInput::default() };
+        let tick = ReplayTick { inputs: [(player, input)].into(), positions: Default::default() };
+        let replay = Replay { tick_rate: TICK_RATE, ticks: vec![tick; 10] };
+
+        let report = run_benchmark(replay);
+        assert_eq!(report.ticks, 10);
+        assert!(report.elapsed > Duration::ZERO);
+        let names: Vec<_> = report.systems.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["playback", "move_players", "teleport_players", "arena_walls"]);
+    }
+}
@@ -0,0 +1,139 @@
+use std::iter;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::console::AdminCommand;
+
+/// How long the background threads wait for work before checking the shutdown again.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long the server waits for its background threads once the app exited.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Shared with the background threads of the server, they check it between two pieces
+/// of work and return once it is signaled, when the app exits.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    signaled: Arc<AtomicBool>,
+    workers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Shutdown {
+    pub fn signal(&self) {
+        self.signaled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_signaled(&self) -> bool {
+        self.signaled.load(Ordering::Relaxed)
+    }
+
+    /// Spawns a named background thread that is given this shutdown flag,
+    /// the threads that already returned are forgotten.
+    pub fn spawn<F>(&self, name: &str, work: F)
+    where
+        F: FnOnce(Shutdown) + Send + 'static,
+    {
+        let shutdown = self.clone();
+        let worker = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || work(shutdown))
+            .expect("failed to spawn a background thread");
+        let mut workers = self.workers.lock().unwrap();
+        workers.retain(|worker| !worker.is_finished());
+        workers.push(worker);
+    }
+
+    /// Waits for the background threads to return, returns whether they all did in time.
+    /// The handles are taken out of the lock while waiting, the threads can spawn others,
+    /// which are waited for too, and those still running at the deadline are put back.
+    pub fn join(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let workers = mem::take(&mut *self.workers.lock().unwrap());
+            if workers.is_empty() {
+                return true;
+            }
+
+            let mut workers = workers.into_iter();
+            while let Some(worker) = workers.next() {
+                while !worker.is_finished() {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        self.workers.lock().unwrap().extend(iter::once(worker).chain(workers));
+                        return false;
+                    }
+                    thread::sleep(POLL_INTERVAL.min(deadline - now));
+                }
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+/// Stops the server with `quit`, the clients are told and the background threads stop.
+pub fn quit_command_system(
+    mut admin_commands: EventReader<AdminCommand>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for AdminCommand(words) in admin_commands.iter() {
+        if words.first().map(String::as_str) == Some("quit") {
+            println!("Shutting down.");
+            exit.send(AppExit);
+        }
+    }
+}
+
+/// Signals the background threads and disconnects the clients when the app exits,
+/// the loop stops right after this frame, once the disconnections are sent,
+/// and the threads are joined.
+pub fn shutdown_on_exit_system(
+    events: EventReader<AppExit>,
+    shutdown: Res<Shutdown>,
+    mut server: ResMut<RenetServer>,
+) {
+    if !events.is_empty() {
+        shutdown.signal();
+        server.disconnect_clients();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signaled_workers_exit_in_time() {
+        let shutdown = Shutdown::default();
+        shutdown.spawn("worker", |shutdown| {
+            while !shutdown.is_signaled() {
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        assert!(!shutdown.join(Duration::from_millis(10)));
+        shutdown.signal();
+        assert!(shutdown.join(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn workers_spawned_while_joining_are_joined() {
+        let shutdown = Shutdown::default();
+        shutdown.spawn("parent", |shutdown| {
+            while !shutdown.is_signaled() {
+                thread::sleep(POLL_INTERVAL);
+            }
+            // It would deadlock if the lock was held while joining.
+            shutdown.spawn("child", |_| thread::sleep(POLL_INTERVAL));
+        });
+
+        shutdown.signal();
+        assert!(shutdown.join(Duration::from_secs(1)));
+        assert!(shutdown.workers.lock().unwrap().is_empty());
+    }
+}
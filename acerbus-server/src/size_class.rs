@@ -0,0 +1,101 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+use heron::prelude::*;
+
+use crate::console::AdminCommand;
+
+/// The collider of a player of this shape and size.
+pub fn player_collision_shape(shape: BodyShape, size: SizeClass) -> CollisionShape {
+    match shape {
+        BodyShape::Square => CollisionShape::Cuboid {
+            half_extends: size.half_extents().extend(0.),
+            border_radius: None,
+        },
+        BodyShape::Circle => CollisionShape::Sphere { radius: BodyShape::radius(size) },
+    }
+}
+
+/// Returns the position of a player of this shape and size moved back inside the arena,
+/// a player growing against a wall is pushed away from it instead of overlapping it.
+pub fn keep_inside_arena(position: Vec2, shape: BodyShape, size: SizeClass, arena: Arena) -> Vec2 {
+    let half_extents = shape.half_extents(size);
+    let max = (Vec2::new(arena.width, arena.height) / 2. - half_extents).max(Vec2::ZERO);
+    position.clamp(-max, max)
+}
+
+/// Changes the size of a player with `resize <player id> <small|normal|large>`.
+pub fn resize_command_system(
+    mut commands: Commands,
+    mut admin_commands: EventReader<AdminCommand>,
+    mut server: ResMut<RenetServer>,
+    lobby: Res<Lobby>,
+    arena: Res<Arena>,
+    mut transforms: Query<(&mut Transform, Option<&BodyShape>), With<Player>>,
+) {
+    for AdminCommand(words) in admin_commands.iter() {
+        let words: Vec<_> = words.iter().map(String::as_str).collect();
+        let (player, size) = match words.as_slice() {
+            ["resize", id, size] => match (id.parse(), size.parse()) {
+                (Ok(id), Ok(size)) => (Player { id }, size),
+                _ => {
+                    println!("usage: resize <player id> <small|normal|large>");
+                    continue;
+                }
+            },
+            ["resize", ..] => {
+                println!("usage: resize <player id> <small|normal|large>");
+                continue;
+            }
+            _ => continue,
+        };
+
+        let entity = match lobby.players.get(&player) {
+            Some(entity) => *entity,
+            None => {
+                println!("{:?} is not connected.", player);
+                continue;
+            }
+        };
+
+        if let Ok((mut transform, shape)) = transforms.get_mut(entity) {
+            let shape = shape.copied().unwrap_or_default();
+            commands.entity(entity).insert(size).insert(player_collision_shape(shape, size));
+            let position = keep_inside_arena(transform.translation.truncate(), shape, size, *arena);
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+
+        println!("{:?} is now {:?}.", player, size);
+        let message = encode(&ServerMessage::PlayerResized { player, size });
+        server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grown_players_are_pushed_away_from_the_walls() {
+        let arena = Arena { width: 400., height: 300. };
+        let corner = Vec2::new(200., -150.);
+        let half_extents = SizeClass::Large.half_extents();
+        let position = keep_inside_arena(corner, BodyShape::Square, SizeClass::Large, arena);
+        assert_eq!(position, corner - half_extents * Vec2::new(1., -1.));
+        // A player bigger than the arena stays in its center.
+        let tiny = Arena { width: 10., height: 10. };
+        assert_eq!(
+            keep_inside_arena(corner, BodyShape::Square, SizeClass::Large, tiny),
+            Vec2::ZERO
+        );
+    }
+
+    #[test]
+    fn sizes_are_parsed_from_their_names() {
+        assert_eq!("small".parse(), Ok(SizeClass::Small));
+        assert_eq!("large".parse(), Ok(SizeClass::Large));
+        assert!("huge".parse::<SizeClass>().is_err());
+        assert_eq!(SizeClass::Small.half_extents() * 4., SizeClass::Large.half_extents());
+    }
+}
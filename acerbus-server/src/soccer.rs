@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+use heron::prelude::*;
+
+use crate::ball::Ball;
+use crate::connections::Connections;
+use crate::event_log::EventLog;
+use crate::free_spawn_position;
+use crate::knockback::Knockback;
+
+/// The score of the two teams of the soccer mode, a match is won at `win_score` goals.
+#[derive(Debug)]
+pub struct Soccer {
+    pub win_score: u32,
+    pub scores: [u32; 2],
+}
+
+impl Soccer {
+    pub fn new(win_score: u32) -> Soccer {
+        Soccer { win_score, scores: [0; 2] }
+    }
+
+    /// Gives a point to this team, returns whether it won the match,
+    /// the scores are then reset for the next one.
+    pub fn score(&mut self, team: Team) -> bool {
+        let score = &mut self.scores[team.0 as usize % 2];
+        *score += 1;
+        let won = *score >= self.win_score;
+        if won {
+            self.scores = [0; 2];
+        }
+        won
+    }
+}
+
+/// Detects the ball entering a goal, gives the point and puts the ball
+/// and the players back where the match started.
+#[allow(clippy::too_many_arguments)]
+pub fn soccer_goal_system(
+    mut soccer: ResMut<Soccer>,
+    mut event_log: ResMut<EventLog>,
+    arena: Res<Arena>,
+    lobby: Res<Lobby>,
+    mut balls: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+    mut players: Query<(&mut Transform, &mut Velocity, &mut Knockback), Without<Ball>>,
+) {
+    let team = balls
+        .iter()
+        .find_map(|(transform, _)| scoring_team(transform.translation.truncate(), *arena));
+    let team = match team {
+        Some(team) => team,
+        None => return,
+    };
+
+    println!("Team {} scored.", team.0);
+    event_log.push(GameEvent::GoalScored { team });
+    if soccer.score(team) {
+        println!("Team {} won the match.", team.0);
+        event_log.push(GameEvent::MatchWon { team });
+    }
+
+    for (mut transform, mut velocity) in balls.iter_mut() {
+        transform.translation = Vec3::new(0., 0., transform.translation.z);
+        *velocity = Velocity::default();
+    }
+    // Everyone goes back to a spawn point, around the ball at the center.
+    let mut occupied = vec![Vec2::ZERO];
+    for entity in lobby.players.values() {
+        if let Ok((mut transform, mut velocity, mut knockback)) = players.get_mut(*entity) {
+            let position = free_spawn_position(&occupied);
+            occupied.push(position);
+            transform.translation = position.extend(transform.translation.z);
+            *velocity = Velocity::default();
+            *knockback = Knockback::default();
+        }
+    }
+}
+
+/// Sends the scores to everyone when they change and to the clients that just got initialized.
+pub fn team_scores_system(
+    soccer: Res<Soccer>,
+    mut server: ResMut<RenetServer>,
+    connections: Res<Connections>,
+    mut synced_clients: Local<HashSet<u64>>,
+) {
+    let message = encode(&ServerMessage::TeamScores { scores: soccer.scores.to_vec() });
+    let initialized = connections.initialized();
+    for client_id in &initialized {
+        if soccer.is_changed() || !synced_clients.contains(client_id) {
+            server.send_message(*client_id, CONNECTION_EVENTS_CHANNEL, message.clone());
+        }
+    }
+    *synced_clients = initialized.into_iter().collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_scores_are_reset_once_a_team_wins() {
+        let mut soccer = Soccer::new(2);
+        assert!(!soccer.score(Team(1)));
+        assert!(!soccer.score(Team(0)));
+        assert_eq!(soccer.scores, [1, 1]);
+        assert!(soccer.score(Team(1)));
+        assert_eq!(soccer.scores, [0, 0]);
+    }
+}
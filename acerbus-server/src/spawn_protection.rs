@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+use acerbus_common::*;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+/// How long the players that just (re)spawned can't be pushed, and whether they
+/// can't push the others either during this time.
+pub struct SpawnProtection {
+    pub duration: Duration,
+    pub passive: bool,
+}
+
+/// The player can't be pushed until this instant.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct SpawnProtected(pub Instant);
+
+impl SpawnProtected {
+    pub fn is_active(protected: Option<&SpawnProtected>, now: Instant) -> bool {
+        protected.map_or(false, |protected| now < protected.0)
+    }
+}
+
+/// Returns whether a player is pushed by another one, given whether they are protected.
+pub fn knockback_applies(target_protected: bool, source_protected: bool, passive: bool) -> bool {
+    !target_protected && !(passive && source_protected)
+}
+
+/// Protects the players that just joined.
+pub fn protect_spawned_players_system(
+    mut commands: Commands,
+    protection: Res<SpawnProtection>,
+    players: Query<Entity, Added<Player>>,
+) {
+    let until = Instant::now() + protection.duration;
+    for entity in players.iter() {
+        commands.entity(entity).insert(SpawnProtected(until));
+    }
+}
+
+/// Tells the clients about the protections that started, so that they can show them.
+pub fn announce_spawn_protection_system(
+    mut server: ResMut<RenetServer>,
+    players: Query<(&Player, &SpawnProtected), Changed<SpawnProtected>>,
+) {
+    let now = Instant::now();
+    for (player, protected) in players.iter() {
+        let duration = protected.0.saturating_duration_since(now);
+        let message = encode(&ServerMessage::SpawnProtected { player: *player, duration });
+        server.broadcast_message(CONNECTION_EVENTS_CHANNEL, message);
+    }
+}
+
+pub fn expire_spawn_protection_system(
+    mut commands: Commands,
+    players: Query<(Entity, &SpawnProtected)>,
+) {
+    let now = Instant::now();
+    for (entity, protected) in players.iter() {
+        if now >= protected.0 {
+            commands.entity(entity).remove::<SpawnProtected>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_players_are_not_pushed() {
+        assert!(knockback_applies(false, false, false));
+        assert!(!knockback_applies(true, false, false));
+        // Only a passive protection stops the protected players from pushing the others.
+        assert!(knockback_applies(false, true, false));
+        assert!(!knockback_applies(false, true, true));
+    }
+
+    #[test]
+    fn protections_expire() {
+        let now = Instant::now();
+        let protected = SpawnProtected(now + Duration::from_secs(1));
+        assert!(SpawnProtected::is_active(Some(&protected), now));
+        assert!(!SpawnProtected::is_active(Some(&protected), now + Duration::from_secs(1)));
+        assert!(!SpawnProtected::is_active(None, now));
+
+        let mut world = World::new();
+        let expired = world.spawn().insert(SpawnProtected(now)).id();
+        let active = world.spawn().insert(SpawnProtected(now + Duration::from_secs(60))).id();
+        let mut stage = SystemStage::single_threaded().with_system(expire_spawn_protection_system);
+        stage.run(&mut world);
+        assert!(world.get::<SpawnProtected>(expired).is_none());
+        assert!(world.get::<SpawnProtected>(active).is_some());
+    }
+}
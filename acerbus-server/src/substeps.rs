@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use heron::prelude::*;
+
+/// How many times the physics are stepped during a server tick.
+pub struct PhysicsSubsteps(pub u32);
+
+/// Adds the physics, stepped `substeps` times every update with a subdivided delta time.
+/// Heron steps at most once per update, the stage of the physics is run again instead.
+pub fn add_physics_plugin(app: &mut App, tick_rate: f64, substeps: u32) {
+    let substeps = substeps.max(1);
+    let step = Duration::from_secs_f64(1.0 / (tick_rate * substeps as f64));
+    app.insert_resource(PhysicsSubsteps(substeps));
+    app.insert_resource(PhysicsSteps::every_frame(step));
+    app.add_plugin(PhysicsPlugin::default());
+    app.stage(heron::stage::ROOT, |schedule: &mut Schedule| {
+        schedule.set_run_criteria(IntoSystem::into_system(run_substeps))
+    });
+}
+
+/// Runs the physics again until they were stepped as many times as there are substeps.
+fn run_substeps(substeps: Res<PhysicsSubsteps>, mut steps: Local<u32>) -> ShouldRun {
+    *steps += 1;
+    if *steps < substeps.0 {
+        ShouldRun::YesAndCheckAgain
+    } else {
+        *steps = 0;
+        ShouldRun::Yes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::transform::TransformPlugin;
+
+    use super::*;
+
+    /// Throws a small body at a thin wall, fast enough to jump over it in a single tick,
+    /// and returns where the body is after a few ticks.
+    fn throw_at_thin_wall(substeps: u32) -> f32 {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins);
+        app.add_plugin(TransformPlugin::default());
+        add_physics_plugin(&mut app, 60.0, substeps);
+
+        app.world
+            .spawn()
+            .insert_bundle(TransformBundle::from_transform(Transform::from_xyz(100., 0., 0.)))
+            .insert(RigidBody::Static)
+            .insert(CollisionShape::Cuboid {
+                half_extends: Vec3::new(2., 100., 0.),
+                border_radius: None,
+            });
+        let body = app
+            .world
+            .spawn()
+            .insert_bundle(TransformBundle::default())
+            .insert(RigidBody::Dynamic)
+            .insert(CollisionShape::Sphere { radius: 10. })
+            .insert(Velocity::from_linear(Vec3::new(12000., 0., 0.)))
+            .id();
+
+        for _ in 0..5 {
+            app.update();
+        }
+        app.world.get::<Transform>(body).unwrap().translation.x
+    }
+
+    #[test]
+    fn substeps_stop_fast_players_at_thin_walls() {
+        // A single step moves the body by 200 pixels, it goes through the wall.
+        assert!(throw_at_thin_wall(1) > 100.);
+        assert!(throw_at_thin_wall(20) < 100.);
+    }
+}
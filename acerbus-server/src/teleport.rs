@@ -0,0 +1,126 @@
+use acerbus_common::*;
+use bevy::prelude::*;
+use heron::prelude::*;
+
+/// All the teleport pads of the arena.
+#[derive(Debug, Default)]
+pub struct TeleportPads(pub Vec<TeleportPad>);
+
+/// The pad a player just landed on, it must leave it before being able to use it.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct LandedOn(pub u32);
+
+/// How many times this player was teleported, wrapping, sent to the clients
+/// so that they don't interpolate its jump across the arena.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct Teleports(pub u8);
+
+impl Teleports {
+    pub fn record(&mut self) {
+        self.0 = self.0.wrapping_add(1);
+    }
+}
+
+/// Two pads linked together on each side of the arena.
+pub fn default_teleport_pads(arena: Arena) -> Vec<TeleportPad> {
+    let x = arena.width / 4.;
+    vec![
+        TeleportPad { id: 0, position: Vec2::new(-x, 0.), target_id: 1 },
+        TeleportPad { id: 1, position: Vec2::new(x, 0.), target_id: 0 },
+    ]
+}
+
+pub fn spawn_teleport_pads(mut commands: Commands, pads: Res<TeleportPads>) {
+    for pad in &pads.0 {
+        commands
+            .spawn()
+            .insert(Transform::from_translation(pad.position.extend(0.)))
+            .insert(GlobalTransform::default())
+            .insert(*pad)
+            .insert(RigidBody::Sensor)
+            .insert(CollisionShape::Cuboid {
+                half_extends: Vec3::new(TELEPORT_PAD_SIZE / 2., TELEPORT_PAD_SIZE / 2., 0.),
+                border_radius: None,
+            });
+    }
+}
+
+/// Returns the pads at the same place relative to the size of the arena, once resized.
+pub fn scale_teleport_pads(pads: &[TeleportPad], from: Arena, to: Arena) -> Vec<TeleportPad> {
+    let scale = Vec2::new(to.width / from.width, to.height / from.height);
+    pads.iter().map(|pad| TeleportPad { position: pad.position * scale, ..*pad }).collect()
+}
+
+/// Moves the pads of the world to the position they were given, when the arena is resized.
+pub fn move_teleport_pads_system(
+    pads: Res<TeleportPads>,
+    mut pad_entities: Query<(&mut TeleportPad, &mut Transform)>,
+) {
+    if !pads.is_changed() {
+        return;
+    }
+
+    for (mut pad, mut transform) in pad_entities.iter_mut() {
+        if let Some(moved) = pads.0.iter().find(|moved| moved.id == pad.id) {
+            *pad = *moved;
+            transform.translation = moved.position.extend(transform.translation.z);
+        }
+    }
+}
+
+/// Moves the players stepping on a pad onto the linked one.
+pub fn teleport_players_system(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    pads: Res<TeleportPads>,
+    pad_entities: Query<&TeleportPad>,
+    mut players: Query<(&mut Transform, &mut Teleports, Option<&LandedOn>), With<Player>>,
+) {
+    for event in collision_events.iter() {
+        let (a, b) = event.rigid_body_entities();
+        let (pad, player_entity) = match (pad_entities.get(a), pad_entities.get(b)) {
+            (Ok(pad), _) => (pad, b),
+            (_, Ok(pad)) => (pad, a),
+            _ => continue,
+        };
+        let (mut transform, mut teleports, landed_on) = match players.get_mut(player_entity) {
+            Ok(player) => player,
+            Err(_) => continue,
+        };
+
+        let just_landed_here = landed_on.map_or(false, |landed| landed.0 == pad.id);
+        match event {
+            CollisionEvent::Started(..) if !just_landed_here => {
+                if let Some(target) = teleport_pad_target(&pads.0, pad) {
+                    transform.translation = target.position.extend(transform.translation.z);
+                    teleports.record();
+                    commands.entity(player_entity).insert(LandedOn(target.id));
+                }
+            }
+            CollisionEvent::Stopped(..) if just_landed_here => {
+                commands.entity(player_entity).remove::<LandedOn>();
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_move_with_the_arena() {
+        let from = Arena { width: 800., height: 600. };
+        let to = Arena { width: 1200., height: 900. };
+        let pads = default_teleport_pads(from);
+        assert_eq!(scale_teleport_pads(&pads, from, to), default_teleport_pads(to));
+    }
+
+    #[test]
+    fn pads_are_linked_to_each_other() {
+        let pads = default_teleport_pads(Arena::default());
+        assert_eq!(teleport_pad_target(&pads, &pads[0]), Some(&pads[1]));
+        assert_eq!(teleport_pad_target(&pads, &pads[1]), Some(&pads[0]));
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::console::AdminCommand;
+
+/// The upper bounds of the histogram buckets, relative to the tick budget.
+const HISTOGRAM_BOUNDS: [f64; 6] = [0.25, 0.5, 0.75, 1.0, 1.5, 2.0];
+
+/// Measures how long each tick takes, the simulation falls behind when it exceeds the budget.
+#[derive(Debug)]
+pub struct TickTiming {
+    pub budget: Duration,
+    pub overruns: u64,
+    pub histogram: Option<TickHistogram>,
+    tick_start: Option<Instant>,
+}
+
+impl TickTiming {
+    pub fn new(budget: Duration, histogram: bool) -> TickTiming {
+        let histogram = histogram.then(TickHistogram::default);
+        TickTiming { budget, overruns: 0, histogram, tick_start: None }
+    }
+}
+
+/// The number of ticks per duration, relative to the budget, the last bucket is for the longest.
+#[derive(Debug, Default, Clone)]
+pub struct TickHistogram {
+    pub buckets: [u64; HISTOGRAM_BOUNDS.len() + 1],
+}
+
+impl TickHistogram {
+    pub fn record(&mut self, duration: Duration, budget: Duration) {
+        let ratio = duration.as_secs_f64() / budget.as_secs_f64();
+        let index = HISTOGRAM_BOUNDS.iter().position(|bound| ratio < *bound);
+        self.buckets[index.unwrap_or(HISTOGRAM_BOUNDS.len())] += 1;
+    }
+}
+
+/// Returns whether a tick that took this long made the simulation fall behind.
+pub fn is_tick_overrun(duration: Duration, budget: Duration) -> bool {
+    duration > budget
+}
+
+pub fn tick_start_system(mut timing: ResMut<TickTiming>) {
+    timing.tick_start = Some(Instant::now());
+}
+
+pub fn tick_end_system(mut timing: ResMut<TickTiming>) {
+    let duration = match timing.tick_start.take() {
+        Some(start) => start.elapsed(),
+        None => return,
+    };
+
+    let budget = timing.budget;
+    if is_tick_overrun(duration, budget) {
+        timing.overruns += 1;
+        warn!("The tick took {:.2?}, more than the {:.2?} budget.", duration, budget);
+    }
+    if let Some(histogram) = &mut timing.histogram {
+        histogram.record(duration, budget);
+    }
+}
+
+/// Prints the ticks durations when the `ticks` command is typed.
+pub fn tick_timing_command_system(
+    mut commands: EventReader<AdminCommand>,
+    timing: Res<TickTiming>,
+) {
+    for AdminCommand(words) in commands.iter() {
+        if words[0] != "ticks" {
+            continue;
+        }
+
+        println!("{} ticks exceeded the {:.2?} budget.", timing.overruns, timing.budget);
+        if let Some(histogram) = &timing.histogram {
+            let mut lower = 0.;
+            for (count, upper) in histogram.buckets.iter().zip(HISTOGRAM_BOUNDS) {
+                println!("{:>4.0}% - {:>4.0}%: {}", lower * 100., upper * 100., count);
+                lower = upper;
+            }
+            println!(
+                "{:>4.0}% -      : {}",
+                lower * 100.,
+                histogram.buckets[HISTOGRAM_BOUNDS.len()]
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGET: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn only_ticks_longer_than_the_budget_overrun() {
+        assert!(!is_tick_overrun(Duration::from_millis(10), BUDGET));
+        assert!(!is_tick_overrun(BUDGET, BUDGET));
+        assert!(is_tick_overrun(Duration::from_millis(17), BUDGET));
+    }
+
+    #[test]
+    fn ticks_are_bucketed_relative_to_the_budget() {
+        let mut histogram = TickHistogram::default();
+        histogram.record(Duration::from_millis(2), BUDGET);
+        histogram.record(Duration::from_millis(8), BUDGET);
+        histogram.record(Duration::from_millis(20), BUDGET);
+        histogram.record(Duration::from_millis(100), BUDGET);
+        assert_eq!(histogram.buckets, [1, 0, 1, 0, 1, 0, 1]);
+    }
+}